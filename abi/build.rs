@@ -1,28 +1,88 @@
 use proto_builder_trait::tonic::BuilderAttributes;
-use std::{fs, process::Command};
+use std::{env, fs, path::PathBuf, process::Command};
+use tonic_build::Builder;
+
+/// enum fields are i32 in the messages, map them to the names of the enum in JSON
+trait EnumFields: Sized {
+    fn with_enum_fields(self, paths: &[&str], name: &str) -> Self;
+    fn with_repeated_enum_fields(self, paths: &[&str], name: &str) -> Self;
+}
 
 fn main() {
+    // fields of proto3 messages are all optional, so are they in JSON
+    let proto = fs::read_to_string("protos/reservation.proto").unwrap();
+    let messages: Vec<_> = proto
+        .lines()
+        .filter_map(|line| line.strip_prefix("message "))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|name| format!(".reservation.{}", name))
+        .collect();
+    let messages: Vec<_> = messages.iter().map(String::as_str).collect();
+
     tonic_build::configure()
         .out_dir("src/pb")
+        // for grpc server reflection, with the imports
+        .file_descriptor_set_path(
+            PathBuf::from(env::var("OUT_DIR").unwrap()).join("descriptor.bin"),
+        )
         .with_sqlx_type(&["reservation.ReservationStatus"])
         .with_derive_builder(&[
             "reservation.ReservationQuery",
             "reservation.ReservationFilter",
+            "reservation.ReservationSearch",
         ])
         .with_derive_builder_into(
             "reservation.ReservationQuery",
-            &["resource_id", "user_id", "status", "page", "desc"],
+            &[
+                "resource_id",
+                "user_id",
+                "status",
+                "page",
+                "desc",
+                "tenant_id",
+                "label_selectors",
+                "statuses",
+            ],
         )
         .with_derive_builder_into(
             "reservation.ReservationFilter",
-            &["resource_id", "user_id", "status", "desc"],
+            &[
+                "resource_id",
+                "user_id",
+                "status",
+                "desc",
+                "tenant_id",
+                "label_selectors",
+                "statuses",
+                "sort_by",
+                "page_token",
+                "with_total",
+                "backward",
+            ],
+        )
+        .with_derive_builder_into(
+            "reservation.ReservationSearch",
+            &[
+                "text",
+                "resource_id",
+                "user_id",
+                "status",
+                "offset",
+                "tenant_id",
+                "label_selectors",
+                "statuses",
+            ],
+        )
+        .with_derive_builder_option(
+            "reservation.ReservationFilter",
+            &["cursor", "cursor_key", "start", "end"],
         )
-        .with_derive_builder_option("reservation.ReservationFilter", &["cursor"])
         .with_derive_builder_option("reservation.ReservationQuery", &["start", "end"])
         .with_type_attributes(
             &[
                 "reservation.ReservationFilter",
                 "reservation.ReservationQuery",
+                "reservation.ReservationSearch",
             ],
             &[r#"#[builder(build_fn(name = "private_build"))]"#],
         )
@@ -30,12 +90,85 @@ fn main() {
             &["page_size"],
             &["#[builder(setter(into), default = \"10\")]"],
         )
-        .compile(&["protos/reservation.proto"], &["protos"])
+        .with_field_attributes(
+            &["reservation.ReservationQuery.mode"],
+            &["#[builder(setter(into), default = \"ReservationQueryMode::Overlaps as i32\")]"],
+        )
+        // JSON mapping of the messages for the HTTP gateway, see src/json.rs
+        .type_attribute(
+            ".reservation",
+            "#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]",
+        )
+        .with_field_attributes(&messages, &["#[serde(default)]"])
+        .with_field_attributes(
+            &["start", "end", "created_at"],
+            &[
+                r#"#[serde(with = "crate::json::timestamp")]"#,
+                "#[schema(value_type = Option<String>, format = DateTime)]",
+            ],
+        )
+        .with_enum_fields(
+            &[
+                "reservation.Reservation.status",
+                "reservation.ReservationQuery.status",
+                "reservation.ReservationFilter.status",
+                "reservation.ReservationSearch.status",
+            ],
+            "ReservationStatus",
+        )
+        .with_repeated_enum_fields(&["statuses"], "ReservationStatus")
+        .with_enum_fields(
+            &["reservation.ReservationQuery.mode"],
+            "ReservationQueryMode",
+        )
+        .with_enum_fields(
+            &["reservation.ReservationFilter.sort_by"],
+            "ReservationSortBy",
+        )
+        .with_enum_fields(&["reservation.LabelSelector.op"], "LabelOperator")
+        .with_enum_fields(&["reservation.ListenResponse.op"], "ReservationUpdateType")
+        .with_enum_fields(
+            &["reservation.CalendarMapping.user_source"],
+            "CalendarUserSource",
+        )
+        .with_enum_fields(
+            &["reservation.CalendarMapping.resource_source"],
+            "CalendarResourceSource",
+        )
+        .with_enum_fields(&["reservation.ImportResult.outcome"], "ImportOutcome")
+        .compile(
+            &[
+                "protos/reservation.proto",
+                "protos/google/rpc/status.proto",
+                "protos/google/rpc/error_details.proto",
+                "protos/grpc/health/v1/health.proto",
+                "protos/grpc/reflection/v1alpha/reflection.proto",
+            ],
+            &["protos"],
+        )
         .unwrap();
 
     fs::remove_file("src/pb/google.protobuf.rs").unwrap();
 
-    Command::new("cargo").args(&["fmt"]).output().unwrap();
+    Command::new("cargo").args(["fmt"]).output().unwrap();
+
+    println!("cargo:rerun-if-changed=protos");
+}
+
+impl EnumFields for Builder {
+    fn with_enum_fields(self, paths: &[&str], name: &str) -> Self {
+        let serde = format!(
+            r#"#[serde(serialize_with = "crate::json::enumeration::serialize::<{0}, _>", deserialize_with = "crate::json::enumeration::deserialize::<{0}, _>")]"#,
+            name
+        );
+        self.with_field_attributes(paths, &[&serde, "#[schema(value_type = String)]"])
+    }
 
-    println!("cargo:rerun-if-changed=protos/reservation.proto");
+    fn with_repeated_enum_fields(self, paths: &[&str], name: &str) -> Self {
+        let serde = format!(
+            r#"#[serde(serialize_with = "crate::json::enumerations::serialize::<{0}, _>", deserialize_with = "crate::json::enumerations::deserialize::<{0}, _>")]"#,
+            name
+        );
+        self.with_field_attributes(paths, &[&serde, "#[schema(value_type = Vec<String>)]"])
+    }
 }
@@ -5,29 +5,158 @@ use crate::Error;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Config {
+    /// only needed when reservations are stored in the database
+    #[serde(default)]
     pub db: DbConfig,
     pub server: ServerConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageConfig {
+    #[serde(default)]
+    pub backend: StorageBackend,
+    /// path of the data file of the embedded backend
+    #[serde(default)]
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// the database in the db section
+    #[default]
+    Db,
+    /// a database file of the process, no external database needed
+    Embedded,
+    /// nothing is persisted, for tests and demos
+    Memory,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DbConfig {
+    /// database to store reservations, sqlite needs the `sqlite` feature of the service
+    #[serde(default)]
+    pub driver: DbDriver,
+    #[serde(default)]
     pub host: String,
+    #[serde(default)]
     pub port: u16,
+    #[serde(default)]
     pub user: String,
+    #[serde(default)]
     pub password: String,
+    /// database name for postgres, path of the database file for sqlite
+    #[serde(default)]
     pub dbname: String,
     #[serde(default = "default_pool_size")]
     pub max_connections: u32,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbDriver {
+    #[default]
+    Postgres,
+    Sqlite,
+}
+
 fn default_pool_size() -> u32 {
     5
 }
 
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            driver: DbDriver::default(),
+            host: String::new(),
+            port: 0,
+            user: String::new(),
+            password: String::new(),
+            dbname: String::new(),
+            max_connections: default_pool_size(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// secret to sign page tokens. If empty, a random one is generated on start,
+    /// so the tokens won't survive a restart
+    #[serde(default)]
+    pub page_token_secret: String,
+    /// accept the deprecated raw cursor in ReservationFilter besides page tokens
+    #[serde(default = "default_allow_raw_cursor")]
+    pub allow_raw_cursor: bool,
+    /// port of the HTTP side (e.g. calendar feeds) on the same host, not served if not set. The
+    /// feeds can't be authenticated, so it can't be set together with `auth`
+    #[serde(default)]
+    pub http_port: Option<u16>,
+    /// port of the HTTP/JSON gateway of the rpcs on the same host, not served if not set
+    #[serde(default)]
+    pub gateway_port: Option<u16>,
+    /// origins allowed to call the grpc service from browsers with gRPC-Web, `*` for any. No
+    /// cross-origin call is allowed if empty
+    #[serde(default)]
+    pub cors_origins: Vec<String>,
+    /// serve the grpc port over TLS, plaintext if not set
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// require a bearer JWT on the reservation rpcs, anyone may call them if not set
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+}
+
+/// PEM files, reloaded when they change so certificates can be renewed without a restart
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// certificate chain of the server
+    pub cert: String,
+    /// private key of the server
+    pub key: String,
+    /// CA certificates of the clients. If set, clients must present a certificate signed by one of
+    /// them (mutual TLS)
+    #[serde(default)]
+    pub client_ca: Option<String>,
+}
+
+/// JWTs of an OIDC issuer, verified with the keys it publishes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// JWKS of the issuer, a file or an http(s) URL like `https://issuer/.well-known/jwks.json`
+    pub jwks: String,
+    /// expected `iss` of the tokens
+    pub issuer: String,
+    /// expected `aud` of the tokens
+    pub audience: String,
+    /// claim holding the user id of the caller
+    #[serde(default = "default_user_claim")]
+    pub user_claim: String,
+    /// claim holding the tenant of the caller, tokens without it belong to the default tenant
+    #[serde(default = "default_tenant_claim")]
+    pub tenant_claim: String,
+    /// scope of service accounts, which may act on behalf of other users
+    #[serde(default = "default_service_scope")]
+    pub service_scope: String,
+}
+
+fn default_user_claim() -> String {
+    "sub".to_string()
+}
+
+fn default_tenant_claim() -> String {
+    "tenant_id".to_string()
+}
+
+fn default_service_scope() -> String {
+    "reservation:on_behalf".to_string()
+}
+
+fn default_allow_raw_cursor() -> bool {
+    true
 }
 
 impl Config {
@@ -49,17 +178,21 @@ impl DbConfig {
         }
     }
     pub fn url(&self) -> String {
-        format!("{}/{}", self.server_url(), self.dbname)
+        match self.driver {
+            DbDriver::Postgres => format!("{}/{}", self.server_url(), self.dbname),
+            DbDriver::Sqlite => format!("sqlite://{}", self.dbname),
+        }
     }
 }
 
 impl ServerConfig {
-    pub fn url(&self, https: bool) -> String {
-        if https {
-            format!("https://{}:{}", self.host, self.port)
-        } else {
-            format!("http://{}:{}", self.host, self.port)
-        }
+    /// the url clients connect to, https if TLS is configured
+    pub fn url(&self) -> String {
+        let scheme = match self.tls {
+            Some(_) => "https",
+            None => "http",
+        };
+        format!("{}://{}:{}", scheme, self.host, self.port)
     }
 }
 
@@ -74,6 +207,7 @@ mod tests {
             config,
             Config {
                 db: DbConfig {
+                    driver: DbDriver::Postgres,
                     host: "localhost".to_string(),
                     port: 5432,
                     user: "postgres".to_string(),
@@ -84,8 +218,60 @@ mod tests {
                 server: ServerConfig {
                     host: "0.0.0.0".to_string(),
                     port: 50051,
-                }
+                    page_token_secret: "".to_string(),
+                    allow_raw_cursor: true,
+                    http_port: None,
+                    gateway_port: None,
+                    cors_origins: vec![],
+                    tls: None,
+                    auth: None,
+                },
+                storage: StorageConfig::default(),
             }
         );
     }
+
+    #[test]
+    fn tls_config_should_drive_the_url() {
+        let mut config: ServerConfig = serde_yaml::from_str(
+            "host: localhost\nport: 50051\ntls:\n  cert: server.pem\n  key: server.key",
+        )
+        .unwrap();
+        assert_eq!(config.url(), "https://localhost:50051");
+        assert_eq!(config.tls.as_ref().unwrap().client_ca, None);
+
+        config.tls = None;
+        assert_eq!(config.url(), "http://localhost:50051");
+    }
+
+    #[test]
+    fn auth_config_should_default_claims() {
+        let config: AuthConfig = serde_yaml::from_str(
+            "jwks: https://issuer/.well-known/jwks.json\nissuer: https://issuer\naudience: reservation",
+        )
+        .unwrap();
+        assert_eq!(config.user_claim, "sub");
+        assert_eq!(config.tenant_claim, "tenant_id");
+        assert_eq!(config.service_scope, "reservation:on_behalf");
+    }
+
+    #[test]
+    fn sqlite_config_should_be_loaded() {
+        let config: DbConfig =
+            serde_yaml::from_str("driver: sqlite\ndbname: /var/lib/reservation.db").unwrap();
+        assert_eq!(config.driver, DbDriver::Sqlite);
+        assert_eq!(config.max_connections, 5);
+        assert_eq!(config.url(), "sqlite:///var/lib/reservation.db");
+    }
+
+    #[test]
+    fn embedded_storage_config_should_not_need_db() {
+        let config: Config = serde_yaml::from_str(
+            "server:\n  host: 0.0.0.0\n  port: 50051\nstorage:\n  backend: embedded\n  path: ~/reservation.redb",
+        )
+        .unwrap();
+        assert_eq!(config.storage.backend, StorageBackend::Embedded);
+        assert_eq!(config.storage.path, "~/reservation.redb");
+        assert_eq!(config.db, DbConfig::default());
+    }
 }
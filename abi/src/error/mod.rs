@@ -1,14 +1,16 @@
 mod conflict;
+mod status;
 
-use sqlx::postgres::PgDatabaseError;
-
-pub use conflict::{ReservationConflict, ReservationConflictInfo, ReservationWindow};
+pub use conflict::{ConflictingReservation, ReservationConflict, ReservationWindow};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Database error")]
     DbError(sqlx::Error),
 
+    #[error("Storage error: {0}")]
+    StorageError(String),
+
     #[error("Failed to read configuration file")]
     ConfigReadError,
 
@@ -19,7 +21,7 @@ pub enum Error {
     InvalidTime,
 
     #[error("Conflict reservation")]
-    ConflictReservation(ReservationConflictInfo),
+    ConflictReservation(ReservationConflict),
 
     #[error("No reservation found by the given condition")]
     NotFound,
@@ -33,15 +35,51 @@ pub enum Error {
     #[error("Invalid resource id: {0}")]
     InvalidResourceId(String),
 
+    #[error("Invalid tenant id: {0}")]
+    InvalidTenantId(String),
+
+    #[error("Invalid label: {0}")]
+    InvalidLabel(String),
+
+    #[error("Invalid label selector: {0}")]
+    InvalidLabelSelector(String),
+
     #[error("Invalid page size: {0}")]
     InvalidPageSize(i64),
 
     #[error("Invalid cursor: {0}")]
     InvalidCursor(i64),
 
+    #[error("Invalid page token: {0}")]
+    InvalidPageToken(String),
+
+    #[error("Invalid query mode: {0}")]
+    InvalidQueryMode(i32),
+
+    #[error("Invalid sort key: {0}")]
+    InvalidSortBy(i32),
+
+    #[error("Invalid search text: {0}")]
+    InvalidSearchText(String),
+
+    #[error("Invalid offset: {0}")]
+    InvalidOffset(i64),
+
     #[error("Invalid status: {0}")]
     InvalidStatus(i32),
 
+    #[error("Invalid calendar: {0}")]
+    InvalidCalendar(String),
+
+    #[error("Invalid resource pool size: {0}")]
+    InvalidResourcePool(i64),
+
+    #[error("Unauthenticated: {0}")]
+    Unauthenticated(String),
+
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
     #[error("unknown error")]
     Unknown,
 }
@@ -51,12 +89,30 @@ impl PartialEq for Error {
         match (self, other) {
             // TODO: this is not a good way to compare DB errors, but we don't do that in the code
             (Self::DbError(_), Self::DbError(_)) => true,
+            (Self::StorageError(v1), Self::StorageError(v2)) => v1 == v2,
+            (Self::ConfigReadError, Self::ConfigReadError) => true,
+            (Self::ConfigParseError, Self::ConfigParseError) => true,
             (Self::InvalidTime, Self::InvalidTime) => true,
             (Self::ConflictReservation(v1), Self::ConflictReservation(v2)) => v1 == v2,
             (Self::NotFound, Self::NotFound) => true,
             (Self::InvalidReservationId(v1), Self::InvalidReservationId(v2)) => v1 == v2,
             (Self::InvalidUserId(v1), Self::InvalidUserId(v2)) => v1 == v2,
             (Self::InvalidResourceId(v1), Self::InvalidResourceId(v2)) => v1 == v2,
+            (Self::InvalidTenantId(v1), Self::InvalidTenantId(v2)) => v1 == v2,
+            (Self::InvalidLabel(v1), Self::InvalidLabel(v2)) => v1 == v2,
+            (Self::InvalidLabelSelector(v1), Self::InvalidLabelSelector(v2)) => v1 == v2,
+            (Self::InvalidPageSize(v1), Self::InvalidPageSize(v2)) => v1 == v2,
+            (Self::InvalidCursor(v1), Self::InvalidCursor(v2)) => v1 == v2,
+            (Self::InvalidPageToken(v1), Self::InvalidPageToken(v2)) => v1 == v2,
+            (Self::InvalidQueryMode(v1), Self::InvalidQueryMode(v2)) => v1 == v2,
+            (Self::InvalidSortBy(v1), Self::InvalidSortBy(v2)) => v1 == v2,
+            (Self::InvalidSearchText(v1), Self::InvalidSearchText(v2)) => v1 == v2,
+            (Self::InvalidOffset(v1), Self::InvalidOffset(v2)) => v1 == v2,
+            (Self::InvalidStatus(v1), Self::InvalidStatus(v2)) => v1 == v2,
+            (Self::InvalidCalendar(v1), Self::InvalidCalendar(v2)) => v1 == v2,
+            (Self::InvalidResourcePool(v1), Self::InvalidResourcePool(v2)) => v1 == v2,
+            (Self::Unauthenticated(v1), Self::Unauthenticated(v2)) => v1 == v2,
+            (Self::PermissionDenied(v1), Self::PermissionDenied(v2)) => v1 == v2,
             (Self::Unknown, Self::Unknown) => true,
             _ => false,
         }
@@ -66,41 +122,8 @@ impl PartialEq for Error {
 impl From<sqlx::Error> for Error {
     fn from(e: sqlx::Error) -> Self {
         match e {
-            sqlx::Error::Database(e) => {
-                let err: &PgDatabaseError = e.downcast_ref();
-                match (err.code(), err.schema(), err.table()) {
-                    ("23P01", Some("rsvp"), Some("reservations")) => {
-                        Error::ConflictReservation(err.detail().unwrap().parse().unwrap())
-                    }
-                    _ => Error::DbError(sqlx::Error::Database(e)),
-                }
-            }
             sqlx::Error::RowNotFound => Error::NotFound,
             _ => Error::DbError(e),
         }
     }
 }
-
-impl From<Error> for tonic::Status {
-    fn from(e: Error) -> Self {
-        match e {
-            Error::DbError(_) | Error::ConfigReadError | Error::ConfigParseError => {
-                tonic::Status::internal(e.to_string())
-            }
-            Error::InvalidTime
-            | Error::InvalidReservationId(_)
-            | Error::InvalidUserId(_)
-            | Error::InvalidResourceId(_)
-            | Error::InvalidPageSize(_)
-            | Error::InvalidCursor(_)
-            | Error::InvalidStatus(_) => tonic::Status::invalid_argument(e.to_string()),
-            Error::ConflictReservation(info) => {
-                tonic::Status::failed_precondition(format!("Conflict reservation: {:?}", info))
-            }
-            Error::NotFound => {
-                tonic::Status::not_found("No reservation found by the given condition")
-            }
-            Error::Unknown => tonic::Status::unknown("unknown error"),
-        }
-    }
-}
@@ -0,0 +1,297 @@
+use prost::Message;
+use prost_types::Any;
+use std::str::FromStr;
+use tonic::{codegen::Bytes, Code};
+
+use super::Error;
+use crate::{
+    rpc::{self, bad_request::FieldViolation, BadRequest, ErrorInfo},
+    ReservationConflictDetail,
+};
+
+/// domain of the ErrorInfo, reasons are only meaningful within it
+const DOMAIN: &str = "reservation";
+const TYPE_URL_PREFIX: &str = "type.googleapis.com/";
+const ERROR_INFO: &str = "google.rpc.ErrorInfo";
+const BAD_REQUEST: &str = "google.rpc.BadRequest";
+const CONFLICT_DETAIL: &str = "reservation.ReservationConflictDetail";
+
+impl Error {
+    /// machine readable reason of the error, sent as ErrorInfo.reason
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Error::DbError(_) => "DB_ERROR",
+            Error::StorageError(_) => "STORAGE_ERROR",
+            Error::ConfigReadError => "CONFIG_READ_ERROR",
+            Error::ConfigParseError => "CONFIG_PARSE_ERROR",
+            Error::InvalidTime => "INVALID_TIME",
+            Error::ConflictReservation(_) => "CONFLICT_RESERVATION",
+            Error::NotFound => "NOT_FOUND",
+            Error::InvalidReservationId(_) => "INVALID_RESERVATION_ID",
+            Error::InvalidUserId(_) => "INVALID_USER_ID",
+            Error::InvalidResourceId(_) => "INVALID_RESOURCE_ID",
+            Error::InvalidTenantId(_) => "INVALID_TENANT_ID",
+            Error::InvalidLabel(_) => "INVALID_LABEL",
+            Error::InvalidLabelSelector(_) => "INVALID_LABEL_SELECTOR",
+            Error::InvalidPageSize(_) => "INVALID_PAGE_SIZE",
+            Error::InvalidCursor(_) => "INVALID_CURSOR",
+            Error::InvalidPageToken(_) => "INVALID_PAGE_TOKEN",
+            Error::InvalidQueryMode(_) => "INVALID_QUERY_MODE",
+            Error::InvalidSortBy(_) => "INVALID_SORT_BY",
+            Error::InvalidSearchText(_) => "INVALID_SEARCH_TEXT",
+            Error::InvalidOffset(_) => "INVALID_OFFSET",
+            Error::InvalidStatus(_) => "INVALID_STATUS",
+            Error::InvalidCalendar(_) => "INVALID_CALENDAR",
+            Error::InvalidResourcePool(_) => "INVALID_RESOURCE_POOL",
+            Error::Unauthenticated(_) => "UNAUTHENTICATED",
+            Error::PermissionDenied(_) => "PERMISSION_DENIED",
+            Error::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// the request field failing the validation, with the rejected value
+    pub(crate) fn violation(&self) -> Option<(&'static str, String)> {
+        match self {
+            Error::InvalidTime => Some(("end", String::new())),
+            Error::InvalidReservationId(v) => Some(("id", v.to_string())),
+            Error::InvalidUserId(v) => Some(("user_id", v.clone())),
+            Error::InvalidResourceId(v) => Some(("resource_id", v.clone())),
+            Error::InvalidTenantId(v) => Some(("tenant_id", v.clone())),
+            Error::InvalidLabel(v) => Some(("labels", v.clone())),
+            Error::InvalidLabelSelector(v) => Some(("label_selectors", v.clone())),
+            Error::InvalidPageSize(v) => Some(("page_size", v.to_string())),
+            Error::InvalidCursor(v) => Some(("cursor", v.to_string())),
+            Error::InvalidPageToken(v) => Some(("page_token", v.clone())),
+            Error::InvalidQueryMode(v) => Some(("mode", v.to_string())),
+            Error::InvalidSortBy(v) => Some(("sort_by", v.to_string())),
+            Error::InvalidSearchText(v) => Some(("text", v.clone())),
+            Error::InvalidOffset(v) => Some(("offset", v.to_string())),
+            Error::InvalidStatus(v) => Some(("status", v.to_string())),
+            Error::InvalidCalendar(v) => Some(("calendar", v.clone())),
+            Error::InvalidResourcePool(v) => Some(("resource_pool", v.to_string())),
+            _ => None,
+        }
+    }
+
+    // the reverse of reason and violation, `value` is the rejected value
+    fn from_reason(
+        reason: &str,
+        value: String,
+        message: &str,
+        conflict: Option<ReservationConflictDetail>,
+    ) -> Self {
+        match reason {
+            // the sqlx error can't be sent, only its message is kept
+            "DB_ERROR" => Error::DbError(sqlx::Error::Protocol(message.to_string())),
+            "STORAGE_ERROR" => Error::StorageError(value),
+            "CONFIG_READ_ERROR" => Error::ConfigReadError,
+            "CONFIG_PARSE_ERROR" => Error::ConfigParseError,
+            "INVALID_TIME" => Error::InvalidTime,
+            "CONFLICT_RESERVATION" => match conflict {
+                Some(detail) => Error::ConflictReservation(detail.into()),
+                None => Error::Unknown,
+            },
+            "NOT_FOUND" => Error::NotFound,
+            "INVALID_RESERVATION_ID" => Error::InvalidReservationId(number(&value)),
+            "INVALID_USER_ID" => Error::InvalidUserId(value),
+            "INVALID_RESOURCE_ID" => Error::InvalidResourceId(value),
+            "INVALID_TENANT_ID" => Error::InvalidTenantId(value),
+            "INVALID_LABEL" => Error::InvalidLabel(value),
+            "INVALID_LABEL_SELECTOR" => Error::InvalidLabelSelector(value),
+            "INVALID_PAGE_SIZE" => Error::InvalidPageSize(number(&value)),
+            "INVALID_CURSOR" => Error::InvalidCursor(number(&value)),
+            "INVALID_PAGE_TOKEN" => Error::InvalidPageToken(value),
+            "INVALID_QUERY_MODE" => Error::InvalidQueryMode(number(&value)),
+            "INVALID_SORT_BY" => Error::InvalidSortBy(number(&value)),
+            "INVALID_SEARCH_TEXT" => Error::InvalidSearchText(value),
+            "INVALID_OFFSET" => Error::InvalidOffset(number(&value)),
+            "INVALID_STATUS" => Error::InvalidStatus(number(&value)),
+            "INVALID_CALENDAR" => Error::InvalidCalendar(value),
+            "INVALID_RESOURCE_POOL" => Error::InvalidResourcePool(number(&value)),
+            "UNAUTHENTICATED" => Error::Unauthenticated(value),
+            "PERMISSION_DENIED" => Error::PermissionDenied(value),
+            _ => Error::Unknown,
+        }
+    }
+
+    fn code(&self) -> Code {
+        match self {
+            Error::DbError(_)
+            | Error::StorageError(_)
+            | Error::ConfigReadError
+            | Error::ConfigParseError => Code::Internal,
+            Error::ConflictReservation(_) => Code::FailedPrecondition,
+            Error::NotFound => Code::NotFound,
+            Error::Unauthenticated(_) => Code::Unauthenticated,
+            Error::PermissionDenied(_) => Code::PermissionDenied,
+            Error::Unknown => Code::Unknown,
+            _ => Code::InvalidArgument,
+        }
+    }
+}
+
+/// the status carries a google.rpc.Status in its details, with an ErrorInfo for every error, a
+/// BadRequest for validation failures and a ReservationConflictDetail for conflicts
+impl From<Error> for tonic::Status {
+    fn from(e: Error) -> Self {
+        let code = e.code();
+        let message = match &e {
+            Error::ConflictReservation(conflict) => {
+                let ids: Vec<_> = conflict.conflicts.iter().map(|c| c.id).collect();
+                format!("{} with {:?}", e, ids)
+            }
+            _ => e.to_string(),
+        };
+
+        let violation = e.violation();
+        let value = match (&e, &violation) {
+            (Error::StorageError(v), _)
+            | (Error::Unauthenticated(v), _)
+            | (Error::PermissionDenied(v), _) => Some(v.clone()),
+            (_, Some((_, v))) => Some(v.clone()),
+            _ => None,
+        };
+        let info = ErrorInfo {
+            reason: e.reason().to_string(),
+            domain: DOMAIN.to_string(),
+            metadata: value.into_iter().map(|v| ("value".into(), v)).collect(),
+        };
+
+        let mut details = vec![pack(ERROR_INFO, &info)];
+        if let Some((field, _)) = violation {
+            let bad_request = BadRequest {
+                field_violations: vec![FieldViolation {
+                    field: field.to_string(),
+                    description: message.clone(),
+                }],
+            };
+            details.push(pack(BAD_REQUEST, &bad_request));
+        }
+        if let Error::ConflictReservation(conflict) = e {
+            let detail: ReservationConflictDetail = conflict.into();
+            details.push(pack(CONFLICT_DETAIL, &detail));
+        }
+
+        let status = rpc::Status {
+            code: code as i32,
+            message: message.clone(),
+            details,
+        };
+        tonic::Status::with_details(code, message, Bytes::from(status.encode_to_vec()))
+    }
+}
+
+/// decode the status of the service back into the error, for clients. A status without
+/// the details (e.g. from a proxy) is mapped by its code
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        let details = rpc::Status::decode(status.details()).unwrap_or_default();
+        let mut info = None;
+        let mut conflict = None;
+        for any in details.details {
+            match any.type_url.strip_prefix(TYPE_URL_PREFIX) {
+                Some(ERROR_INFO) => info = ErrorInfo::decode(any.value.as_slice()).ok(),
+                Some(CONFLICT_DETAIL) => {
+                    conflict = ReservationConflictDetail::decode(any.value.as_slice()).ok()
+                }
+                _ => {}
+            }
+        }
+
+        match info {
+            Some(mut info) if info.domain == DOMAIN => {
+                let value = info.metadata.remove("value").unwrap_or_default();
+                Error::from_reason(&info.reason, value, status.message(), conflict)
+            }
+            _ => match status.code() {
+                Code::NotFound => Error::NotFound,
+                Code::Unauthenticated => Error::Unauthenticated(status.message().into()),
+                Code::PermissionDenied => Error::PermissionDenied(status.message().into()),
+                _ => Error::Unknown,
+            },
+        }
+    }
+}
+
+fn pack(name: &str, msg: &impl Message) -> Any {
+    Any {
+        type_url: format!("{}{}", TYPE_URL_PREFIX, name),
+        value: msg.encode_to_vec(),
+    }
+}
+
+fn number<T: FromStr + Default>(value: &str) -> T {
+    value.parse().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ConflictingReservation, ReservationConflict, ReservationStatus, ReservationWindow,
+    };
+
+    #[test]
+    fn validation_error_should_carry_field_violation() {
+        let status: tonic::Status = Error::InvalidUserId("".into()).into();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let details = rpc::Status::decode(status.details()).unwrap();
+        assert_eq!(details.code, Code::InvalidArgument as i32);
+        assert_eq!(
+            details.details[0].type_url,
+            "type.googleapis.com/google.rpc.ErrorInfo"
+        );
+        let info = ErrorInfo::decode(details.details[0].value.as_slice()).unwrap();
+        assert_eq!(info.reason, "INVALID_USER_ID");
+        assert_eq!(info.domain, "reservation");
+        let bad_request = BadRequest::decode(details.details[1].value.as_slice()).unwrap();
+        assert_eq!(bad_request.field_violations[0].field, "user_id");
+
+        assert_eq!(Error::from(status), Error::InvalidUserId("".into()));
+    }
+
+    #[test]
+    fn conflict_error_should_round_trip() {
+        let window = |start: &str, end: &str| ReservationWindow {
+            rid: "ocean-view-room-713".into(),
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+        };
+        let conflict = ReservationConflict {
+            new: window("2022-12-26T15:00:00-0700", "2022-12-30T12:00:00-0700"),
+            conflicts: vec![ConflictingReservation {
+                id: 42,
+                user_id: "tyrid".into(),
+                window: window("2022-12-25T15:00:00-0700", "2022-12-28T12:00:00-0700"),
+                status: ReservationStatus::Confirmed,
+            }],
+        };
+
+        let status: tonic::Status = Error::ConflictReservation(conflict.clone()).into();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(status.message(), "Conflict reservation with [42]");
+        assert_eq!(Error::from(status), Error::ConflictReservation(conflict));
+    }
+
+    #[test]
+    fn status_without_details_should_map_by_code() {
+        assert_eq!(
+            Error::from(tonic::Status::not_found("gone")),
+            Error::NotFound
+        );
+        assert_eq!(
+            Error::from(tonic::Status::unavailable("proxy is down")),
+            Error::Unknown
+        );
+        assert_eq!(
+            Error::from(tonic::Status::unauthenticated("no token")),
+            Error::Unauthenticated("no token".into())
+        );
+        let status: tonic::Status = Error::InvalidPageSize(0).into();
+        assert_eq!(Error::from(status), Error::InvalidPageSize(0));
+        let status: tonic::Status = Error::PermissionDenied("tyr".into()).into();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert_eq!(Error::from(status), Error::PermissionDenied("tyr".into()));
+    }
+}
@@ -0,0 +1,536 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use prost_types::Timestamp;
+
+use crate::{
+    convert_to_timestamp, CalendarMapping, CalendarResourceSource, CalendarUserSource, Error,
+    ImportOutcome, ImportResult, Reservation, ReservationConflict, ReservationStatus, Validator,
+};
+
+/// label of the imported reservations, keeping the UID of the event they're made from
+pub const ICS_UID_LABEL: &str = "ics-uid";
+
+/// a VEVENT of an imported calendar
+#[derive(Debug, PartialEq)]
+pub struct ImportedEvent {
+    pub uid: String,
+    /// cancelled events aren't imported
+    pub cancelled: bool,
+    /// the reservation the event maps to, or why it can't be one
+    pub reservation: Result<Reservation, Error>,
+}
+
+/// a content line, e.g. `ORGANIZER;CN=Tyr:mailto:tyr@acme.com`
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+/// the properties of a VEVENT the mapping needs, the last one wins except ATTENDEE
+#[derive(Default)]
+struct RawEvent {
+    uid: String,
+    start: Option<Property>,
+    end: Option<Property>,
+    duration: Option<String>,
+    summary: String,
+    location: String,
+    description: String,
+    organizer: String,
+    attendees: Vec<String>,
+    status: String,
+}
+
+impl CalendarMapping {
+    /// the VEVENTs of the calendar in order. Only a text that isn't an iCalendar is an error, an
+    /// event that can't be mapped to a valid reservation is reported in its `reservation`
+    pub fn parse(&self, ics: &str) -> Result<Vec<ImportedEvent>, Error> {
+        let mut lines = unfold(ics).into_iter().filter(|l| !l.trim().is_empty());
+        match lines.next().and_then(|l| parse_property(&l)) {
+            Some(p) if p.name == "BEGIN" && p.value.eq_ignore_ascii_case("VCALENDAR") => {}
+            _ => return Err(Error::InvalidCalendar("missing BEGIN:VCALENDAR".into())),
+        }
+
+        let mut events = vec![];
+        let mut event: Option<RawEvent> = None;
+        // VALARM and the like nested in the event have their own properties, e.g. DESCRIPTION
+        let mut nested = 0;
+        for line in lines {
+            let prop = parse_property(&line)
+                .ok_or_else(|| Error::InvalidCalendar(format!("invalid content line: {}", line)))?;
+            let component = prop.value.to_ascii_uppercase();
+            match (prop.name.as_str(), event.as_mut()) {
+                ("BEGIN", None) if component == "VEVENT" => event = Some(RawEvent::default()),
+                ("BEGIN", Some(_)) => nested += 1,
+                ("END", Some(_)) if nested > 0 => nested -= 1,
+                ("END", Some(_)) if component == "VEVENT" => {
+                    events.push(self.map(event.take().unwrap()));
+                }
+                (_, Some(raw)) if nested == 0 => raw.set(prop),
+                _ => {}
+            }
+        }
+        Ok(events)
+    }
+
+    fn map(&self, raw: RawEvent) -> ImportedEvent {
+        let user = match self.user_source() {
+            CalendarUserSource::Organizer => Some(raw.organizer.as_str()),
+            CalendarUserSource::Attendee => raw.attendees.first().map(String::as_str),
+        };
+        let user_id = user.map(calendar_address).unwrap_or_default();
+        let user_id = self.users.get(user_id).map_or(user_id, String::as_str);
+
+        let resource_id = match self.resource_source() {
+            CalendarResourceSource::Location => raw.location.trim(),
+            CalendarResourceSource::Summary => raw.summary.trim(),
+        };
+        let resource_id = self
+            .resources
+            .get(resource_id)
+            .map_or(resource_id, String::as_str);
+
+        let status = match raw.status.as_str() {
+            "CONFIRMED" => ReservationStatus::Confirmed,
+            _ => ReservationStatus::Pending,
+        };
+        let reservation = self.times(&raw).and_then(|(start, end)| {
+            let mut rsvp = Reservation {
+                user_id: user_id.to_string(),
+                resource_id: resource_id.to_string(),
+                status: status as i32,
+                start: Some(start),
+                end: Some(end),
+                note: raw.description.clone(),
+                ..Default::default()
+            };
+            if !raw.uid.is_empty() {
+                rsvp.labels.insert(ICS_UID_LABEL.into(), raw.uid.clone());
+            }
+            rsvp.validate()?;
+            Ok(rsvp)
+        });
+
+        ImportedEvent {
+            cancelled: raw.status == "CANCELLED",
+            uid: raw.uid,
+            reservation,
+        }
+    }
+
+    /// start and end of the event. Without DTEND or DURATION, a date event lasts a day
+    fn times(&self, raw: &RawEvent) -> Result<(Timestamp, Timestamp), Error> {
+        let offset = self
+            .utc_offset_minutes
+            .checked_mul(60)
+            .and_then(FixedOffset::east_opt)
+            .ok_or(Error::InvalidTime)?;
+        let start_prop = raw.start.as_ref().ok_or(Error::InvalidTime)?;
+        let start = parse_time(start_prop, offset).ok_or(Error::InvalidTime)?;
+        let end = match (&raw.end, &raw.duration) {
+            (Some(end), _) => parse_time(end, offset),
+            (None, Some(duration)) => {
+                parse_duration(duration).and_then(|d| start.checked_add_signed(d))
+            }
+            (None, None) if is_date(start_prop) => start.checked_add_signed(Duration::days(1)),
+            (None, None) => Some(start),
+        };
+        let end = end.ok_or(Error::InvalidTime)?;
+        Ok((convert_to_timestamp(&start), convert_to_timestamp(&end)))
+    }
+}
+
+impl RawEvent {
+    fn set(&mut self, prop: Property) {
+        match prop.name.as_str() {
+            "UID" => self.uid = prop.value,
+            "DTSTART" => self.start = Some(prop),
+            "DTEND" => self.end = Some(prop),
+            "DURATION" => self.duration = Some(prop.value),
+            "SUMMARY" => self.summary = unescape_text(&prop.value),
+            "LOCATION" => self.location = unescape_text(&prop.value),
+            "DESCRIPTION" => self.description = unescape_text(&prop.value),
+            "ORGANIZER" => self.organizer = prop.value,
+            "ATTENDEE" => self.attendees.push(prop.value),
+            "STATUS" => self.status = prop.value.to_ascii_uppercase(),
+            _ => {}
+        }
+    }
+}
+
+impl ImportResult {
+    pub fn created(uid: String, rsvp: Reservation) -> Self {
+        Self {
+            uid,
+            outcome: ImportOutcome::Created as i32,
+            reservation: Some(rsvp),
+            ..Default::default()
+        }
+    }
+
+    pub fn conflict(uid: String, rsvp: Reservation, conflict: ReservationConflict) -> Self {
+        let description = Error::ConflictReservation(conflict.clone()).to_string();
+        Self {
+            uid,
+            outcome: ImportOutcome::Conflict as i32,
+            reservation: Some(rsvp),
+            reason: "CONFLICT_RESERVATION".into(),
+            description,
+            conflicts: conflict.conflicts.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn invalid(uid: String, e: &Error) -> Self {
+        Self {
+            uid,
+            outcome: ImportOutcome::Invalid as i32,
+            reason: e.reason().into(),
+            description: e.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn skipped(uid: String) -> Self {
+        Self {
+            uid,
+            outcome: ImportOutcome::Skipped as i32,
+            ..Default::default()
+        }
+    }
+}
+
+/// join the folded lines, see RFC 5545 3.1
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in ics.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// split the content line into its name, parameters and value. A colon in a quoted
+/// parameter value doesn't end the parameters
+fn parse_property(line: &str) -> Option<Property> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+
+    let mut parts = line[..colon].split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_ascii_uppercase(), v.trim_matches('"').to_string()))
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: line[colon + 1..].to_string(),
+    })
+}
+
+fn is_date(prop: &Property) -> bool {
+    prop.params
+        .iter()
+        .any(|(k, v)| k == "VALUE" && v.eq_ignore_ascii_case("DATE"))
+        || prop.value.len() == 8
+}
+
+/// a UTC, floating or TZID date time, or a date at midnight. Floating and TZID times are in
+/// the given offset
+fn parse_time(prop: &Property, offset: FixedOffset) -> Option<DateTime<Utc>> {
+    let value = prop.value.trim();
+    if is_date(prop) {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return local_time(date.and_hms_opt(0, 0, 0)?, offset);
+    }
+    match value.strip_suffix('Z') {
+        Some(utc) => {
+            let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+            Some(Utc.from_utc_datetime(&time))
+        }
+        None => local_time(
+            NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?,
+            offset,
+        ),
+    }
+}
+
+fn local_time(time: NaiveDateTime, offset: FixedOffset) -> Option<DateTime<Utc>> {
+    Some(
+        offset
+            .from_local_datetime(&time)
+            .single()?
+            .with_timezone(&Utc),
+    )
+}
+
+/// e.g. P1D, PT1H30M, -P1W, see RFC 5545 3.3.6. None if it's beyond what a `Duration` holds
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut seconds = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.strip_prefix('P')?.chars() {
+        let unit = match (c, in_time) {
+            ('0'..='9', _) => {
+                number.push(c);
+                continue;
+            }
+            ('T', false) => {
+                in_time = true;
+                continue;
+            }
+            ('W', false) => 7 * 24 * 3600,
+            ('D', false) => 24 * 3600,
+            ('H', true) => 3600,
+            ('M', true) => 60,
+            ('S', true) => 1,
+            _ => return None,
+        };
+        let part = number.parse::<i64>().ok()?.checked_mul(unit)?;
+        seconds = part.checked_add(seconds)?;
+        number.clear();
+    }
+    if !number.is_empty() || seconds > Duration::max_value().num_seconds() {
+        return None;
+    }
+    Some(Duration::seconds(if negative { -seconds } else { seconds }))
+}
+
+/// the address of a calendar user without the `mailto:` scheme
+fn calendar_address(value: &str) -> &str {
+    let value = value.trim();
+    match value.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &value[7..],
+        _ => value,
+    }
+}
+
+/// the reverse of `escape_text`
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_ics;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:1@old-system\r
+DTSTART:20221225T220000Z\r
+DTEND:20221228T190000Z\r
+ORGANIZER;CN=\"Chen: Tyr\":mailto:tyr@acme.com\r
+LOCATION:Ocean View 713\r
+DESCRIPTION:I'll arrive at 3pm\\; please upgrade\\,\\nif possi\r
+ ble.\r
+STATUS:CONFIRMED\r
+BEGIN:VALARM\r
+DESCRIPTION:reminder\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:2@old-system\r
+DTSTART;TZID=America/Los_Angeles:20221226T150000\r
+DURATION:PT1H30M\r
+ORGANIZER:mailto:alice@acme.com\r
+ATTENDEE:mailto:bob@acme.com\r
+LOCATION:ixia-test-1\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:3@old-system\r
+DTSTART;VALUE=DATE:20221227\r
+ORGANIZER:mailto:tyr@acme.com\r
+LOCATION:ixia-test-1\r
+STATUS:CANCELLED\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:4@old-system\r
+DTSTART:yesterday\r
+LOCATION:ixia-test-1\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn mapping() -> CalendarMapping {
+        CalendarMapping {
+            users: [("tyr@acme.com".to_string(), "tyrid".to_string())].into(),
+            resources: [(
+                "Ocean View 713".to_string(),
+                "ocean-view-room-713".to_string(),
+            )]
+            .into(),
+            utc_offset_minutes: -8 * 60,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_should_map_events_to_reservations() {
+        let events = mapping().parse(CALENDAR).unwrap();
+        let uids: Vec<_> = events.iter().map(|e| e.uid.as_str()).collect();
+        assert_eq!(
+            uids,
+            vec![
+                "1@old-system",
+                "2@old-system",
+                "3@old-system",
+                "4@old-system"
+            ]
+        );
+
+        let rsvp = events[0].reservation.as_ref().unwrap();
+        assert_eq!(rsvp.user_id, "tyrid");
+        assert_eq!(rsvp.resource_id, "ocean-view-room-713");
+        assert_eq!(rsvp.start, Some("2022-12-25T22:00:00Z".parse().unwrap()));
+        assert_eq!(rsvp.end, Some("2022-12-28T19:00:00Z".parse().unwrap()));
+        assert_eq!(
+            rsvp.note,
+            "I'll arrive at 3pm; please upgrade,\nif possible."
+        );
+        assert_eq!(rsvp.status, ReservationStatus::Confirmed as i32);
+        assert_eq!(rsvp.labels[ICS_UID_LABEL], "1@old-system");
+
+        // unmapped address and location are used as is, the time is in the offset of the mapping
+        let rsvp = events[1].reservation.as_ref().unwrap();
+        assert_eq!(rsvp.user_id, "alice@acme.com");
+        assert_eq!(rsvp.resource_id, "ixia-test-1");
+        assert_eq!(rsvp.start, Some("2022-12-26T23:00:00Z".parse().unwrap()));
+        assert_eq!(rsvp.end, Some("2022-12-27T00:30:00Z".parse().unwrap()));
+        assert_eq!(rsvp.status, ReservationStatus::Pending as i32);
+
+        assert!(events[2].cancelled);
+        let rsvp = events[2].reservation.as_ref().unwrap();
+        assert_eq!(rsvp.start, Some("2022-12-27T08:00:00Z".parse().unwrap()));
+        assert_eq!(rsvp.end, Some("2022-12-28T08:00:00Z".parse().unwrap()));
+
+        assert_eq!(events[3].reservation, Err(Error::InvalidTime));
+    }
+
+    #[test]
+    fn parse_should_take_user_from_attendee_if_configured() {
+        let mapping = CalendarMapping {
+            user_source: CalendarUserSource::Attendee as i32,
+            ..mapping()
+        };
+        let events = mapping.parse(CALENDAR).unwrap();
+        assert_eq!(
+            events[1].reservation.as_ref().unwrap().user_id,
+            "bob@acme.com"
+        );
+        // no attendee
+        assert_eq!(
+            events[0].reservation,
+            Err(Error::InvalidUserId(String::new()))
+        );
+    }
+
+    #[test]
+    fn exported_calendar_should_be_imported_back() {
+        let rsvp = Reservation {
+            id: 42,
+            ..Reservation::new_pending(
+                "tyrid",
+                "ocean-view-room-713",
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "a long note; ".repeat(10),
+            )
+        };
+        let mapping = CalendarMapping {
+            resource_source: CalendarResourceSource::Summary as i32,
+            ..Default::default()
+        };
+        let events = mapping.parse(&to_ics("tyrid", [&rsvp])).unwrap();
+        let imported = events[0].reservation.as_ref().unwrap_err();
+        // the export has no organizer
+        assert_eq!(imported, &Error::InvalidUserId(String::new()));
+
+        let events = mapping
+            .parse(&to_ics("tyrid", [&rsvp]).replace("UID:", "ORGANIZER:tyrid\r\nUID:"))
+            .unwrap();
+        let imported = events[0].reservation.as_ref().unwrap();
+        assert_eq!(imported.resource_id, rsvp.resource_id);
+        assert_eq!((&imported.start, &imported.end), (&rsvp.start, &rsvp.end));
+        assert_eq!(imported.note, rsvp.note);
+        assert_eq!(events[0].uid, rsvp.ics_uid());
+    }
+
+    #[test]
+    fn parse_should_reject_text_not_a_calendar() {
+        assert_eq!(
+            mapping().parse("hello"),
+            Err(Error::InvalidCalendar("missing BEGIN:VCALENDAR".into()))
+        );
+    }
+
+    #[test]
+    fn parse_duration_should_work() {
+        assert_eq!(parse_duration("P1W"), Some(Duration::weeks(1)));
+        assert_eq!(parse_duration("P1DT2H3M4S"), Some(Duration::seconds(93784)));
+        assert_eq!(parse_duration("-PT15M"), Some(Duration::minutes(-15)));
+        assert_eq!(parse_duration("PT1H5"), None);
+        assert_eq!(parse_duration("1H"), None);
+        assert_eq!(parse_duration("P200000000000D"), None);
+        assert_eq!(parse_duration("P9223372036854775807W"), None);
+        assert_eq!(parse_duration("P1DT9223372036854775807S"), None);
+    }
+
+    #[test]
+    fn parse_should_reject_times_out_of_range() {
+        let calendar = ["P100000000D", "P200000000000D", "P9223372036854775807W"]
+            .iter()
+            .map(|duration| {
+                format!(
+                    "BEGIN:VEVENT\r\nDTSTART:20221225T150000\r\nDURATION:{}\r\nLOCATION:ixia-test-1\r\nEND:VEVENT\r\n",
+                    duration
+                )
+            })
+            .collect::<String>();
+        let calendar = format!("BEGIN:VCALENDAR\r\n{}END:VCALENDAR\r\n", calendar);
+        let events = mapping().parse(&calendar).unwrap();
+        assert_eq!(events.len(), 3);
+        for event in events {
+            assert_eq!(event.reservation, Err(Error::InvalidTime));
+        }
+
+        let mapping = CalendarMapping {
+            utc_offset_minutes: i32::MAX,
+            ..mapping()
+        };
+        let events = mapping.parse(CALENDAR).unwrap();
+        assert_eq!(events[0].reservation, Err(Error::InvalidTime));
+    }
+}
@@ -0,0 +1,201 @@
+//! iCalendar (RFC 5545) export and import of reservations, for Outlook, Google Calendar and the like
+
+mod import;
+
+use chrono::{DateTime, Utc};
+use prost_types::Timestamp;
+
+use crate::{convert_to_utc_time, Reservation, ReservationStatus};
+
+pub use import::{ImportedEvent, ICS_UID_LABEL};
+
+const PRODID: &str = "-//reservation//reservation service//EN";
+/// right hand side of the UID of the events, the reservation id is the left hand side
+const UID_DOMAIN: &str = "reservation";
+/// lines longer than this (in octets, without the line break) are folded
+const MAX_LINE_LEN: usize = 75;
+
+/// a VCALENDAR named `name` with a VEVENT for each reservation. Lines end with CRLF
+pub fn to_ics<'a>(name: &str, rsvps: impl IntoIterator<Item = &'a Reservation>) -> String {
+    let mut ics = IcsWriter::default();
+    ics.line("BEGIN", "VCALENDAR");
+    ics.line("VERSION", "2.0");
+    ics.line("PRODID", PRODID);
+    ics.line("CALSCALE", "GREGORIAN");
+    ics.line("METHOD", "PUBLISH");
+    ics.line("X-WR-CALNAME", &escape_text(name));
+    for rsvp in rsvps {
+        rsvp.write_vevent(&mut ics);
+    }
+    ics.line("END", "VCALENDAR");
+    ics.0
+}
+
+impl Reservation {
+    /// UID of the event of the reservation, the same across exports so calendars update the event
+    /// instead of adding a new one
+    pub fn ics_uid(&self) -> String {
+        format!("{}@{}", self.id, UID_DOMAIN)
+    }
+
+    fn write_vevent(&self, ics: &mut IcsWriter) {
+        // DTSTAMP is required, use the creation time so the same reservation always exports the same
+        let stamp = self.created_at.as_ref().or(self.start.as_ref());
+
+        ics.line("BEGIN", "VEVENT");
+        ics.line("UID", &self.ics_uid());
+        if let Some(stamp) = stamp {
+            ics.line("DTSTAMP", &format_time(stamp));
+        }
+        if let Some(start) = self.start.as_ref() {
+            ics.line("DTSTART", &format_time(start));
+        }
+        if let Some(end) = self.end.as_ref() {
+            ics.line("DTEND", &format_time(end));
+        }
+        ics.line("SUMMARY", &escape_text(&self.resource_id));
+        ics.line("LOCATION", &escape_text(&self.resource_id));
+        if !self.note.is_empty() {
+            ics.line("DESCRIPTION", &escape_text(&self.note));
+        }
+        if let Some(status) = ics_status(self.status) {
+            ics.line("STATUS", status);
+        }
+        ics.line("END", "VEVENT");
+    }
+}
+
+/// STATUS of the event. A blocked resource is taken for sure, so it's confirmed as well
+fn ics_status(status: i32) -> Option<&'static str> {
+    match ReservationStatus::from_i32(status)? {
+        ReservationStatus::Pending => Some("TENTATIVE"),
+        ReservationStatus::Confirmed | ReservationStatus::Blocked => Some("CONFIRMED"),
+        ReservationStatus::Unknown => None,
+    }
+}
+
+/// UTC date time, e.g. 20221225T220000Z
+fn format_time(ts: &Timestamp) -> String {
+    let dt: DateTime<Utc> = convert_to_utc_time(ts);
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// escape the TEXT value, see RFC 5545 3.3.11
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[derive(Default)]
+struct IcsWriter(String);
+
+impl IcsWriter {
+    /// write a content line, folded at MAX_LINE_LEN octets without splitting a character
+    fn line(&mut self, name: &str, value: &str) {
+        let line = format!("{}:{}", name, value);
+        let mut rest = line.as_str();
+        let mut max = MAX_LINE_LEN;
+        while rest.len() > max {
+            let mut at = max;
+            while !rest.is_char_boundary(at) {
+                at -= 1;
+            }
+            self.0.push_str(&rest[..at]);
+            self.0.push_str("\r\n ");
+            rest = &rest[at..];
+            // the leading space of the continuation line counts
+            max = MAX_LINE_LEN - 1;
+        }
+        self.0.push_str(rest);
+        self.0.push_str("\r\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rsvp() -> Reservation {
+        Reservation {
+            id: 42,
+            status: ReservationStatus::Confirmed as i32,
+            created_at: Some("2022-12-01T08:00:00Z".parse().unwrap()),
+            ..Reservation::new_pending(
+                "tyrid",
+                "ocean-view-room-713",
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "I'll arrive at 3pm; please upgrade,\nif possible.",
+            )
+        }
+    }
+
+    #[test]
+    fn to_ics_should_write_a_vevent_per_reservation() {
+        let ics = to_ics("tyrid", &[rsvp()]);
+        assert_eq!(
+            ics,
+            [
+                "BEGIN:VCALENDAR",
+                "VERSION:2.0",
+                "PRODID:-//reservation//reservation service//EN",
+                "CALSCALE:GREGORIAN",
+                "METHOD:PUBLISH",
+                "X-WR-CALNAME:tyrid",
+                "BEGIN:VEVENT",
+                "UID:42@reservation",
+                "DTSTAMP:20221201T080000Z",
+                "DTSTART:20221225T220000Z",
+                "DTEND:20221228T190000Z",
+                "SUMMARY:ocean-view-room-713",
+                "LOCATION:ocean-view-room-713",
+                "DESCRIPTION:I'll arrive at 3pm\\; please upgrade\\,\\nif possible.",
+                "STATUS:CONFIRMED",
+                "END:VEVENT",
+                "END:VCALENDAR",
+                "",
+            ]
+            .join("\r\n")
+        );
+    }
+
+    #[test]
+    fn pending_reservation_should_be_tentative() {
+        let rsvp = Reservation {
+            status: ReservationStatus::Pending as i32,
+            ..rsvp()
+        };
+        assert!(to_ics("", &[rsvp]).contains("\r\nSTATUS:TENTATIVE\r\n"));
+    }
+
+    #[test]
+    fn long_line_should_be_folded() {
+        let rsvp = Reservation {
+            note: "会议室".repeat(30),
+            ..rsvp()
+        };
+        let ics = to_ics("", &[rsvp]);
+        let lines: Vec<_> = ics.split("\r\n").collect();
+        assert!(lines.iter().all(|l| l.len() <= MAX_LINE_LEN));
+
+        let start = lines
+            .iter()
+            .position(|l| l.starts_with("DESCRIPTION:"))
+            .unwrap();
+        let mut unfolded = lines[start].to_string();
+        for line in lines[start + 1..].iter().take_while(|l| l.starts_with(' ')) {
+            unfolded.push_str(&line[1..]);
+        }
+        assert_eq!(unfolded, format!("DESCRIPTION:{}", "会议室".repeat(30)));
+    }
+}
@@ -0,0 +1,189 @@
+//! serde helpers for the JSON mapping of the generated messages, set on their fields in build.rs.
+//! Like the proto3 JSON mapping, timestamps are RFC 3339 strings and enums are their names, numbers
+//! are accepted for enums too
+
+use crate::{
+    convert_to_timestamp, convert_to_utc_time, CalendarResourceSource, CalendarUserSource,
+    ImportOutcome, LabelOperator, ReservationQueryMode, ReservationSortBy, ReservationStatus,
+    ReservationUpdateType,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use prost_types::Timestamp;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// a generated enum, known by its name in JSON
+pub trait ProtoEnum: Sized {
+    fn from_i32(value: i32) -> Option<Self>;
+    fn as_str_name(&self) -> &'static str;
+
+    /// the value of the name, enum values are numbered from 0 without gaps
+    fn from_str_name(name: &str) -> Option<i32> {
+        (0..)
+            .map_while(|v| Self::from_i32(v).map(|e| (v, e)))
+            .find(|(_, e)| e.as_str_name() == name)
+            .map(|(v, _)| v)
+    }
+}
+
+macro_rules! impl_proto_enum {
+    ($($t:ty),*) => {
+        $(
+            impl ProtoEnum for $t {
+                fn from_i32(value: i32) -> Option<Self> {
+                    <$t>::from_i32(value)
+                }
+
+                fn as_str_name(&self) -> &'static str {
+                    <$t>::as_str_name(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_proto_enum!(
+    ReservationStatus,
+    ReservationQueryMode,
+    ReservationSortBy,
+    LabelOperator,
+    ReservationUpdateType,
+    CalendarUserSource,
+    CalendarResourceSource,
+    ImportOutcome
+);
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum NameOrNumber {
+    Name(String),
+    Number(i32),
+}
+
+/// `google.protobuf.Timestamp` as an RFC 3339 string, e.g. `2022-12-26T22:00:00Z`
+pub mod timestamp {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        ts: &Option<Timestamp>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        ts.as_ref()
+            .map(|ts| convert_to_utc_time(ts).to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Timestamp>, D::Error> {
+        let s = match Option::<String>::deserialize(deserializer)? {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        let dt = DateTime::parse_from_rfc3339(&s).map_err(de::Error::custom)?;
+        Ok(Some(convert_to_timestamp(&dt.with_timezone(&Utc))))
+    }
+}
+
+/// an enum field by the name of its value, an unknown value stays a number
+pub mod enumeration {
+    use super::*;
+
+    pub fn serialize<E: ProtoEnum, S: Serializer>(
+        value: &i32,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match E::from_i32(*value) {
+            Some(e) => serializer.serialize_str(e.as_str_name()),
+            None => serializer.serialize_i32(*value),
+        }
+    }
+
+    pub fn deserialize<'de, E: ProtoEnum, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<i32, D::Error> {
+        from_name_or_number::<E, D::Error>(NameOrNumber::deserialize(deserializer)?)
+    }
+}
+
+/// a repeated enum field by the names of its values
+pub mod enumerations {
+    use super::*;
+
+    pub fn serialize<E: ProtoEnum, S: Serializer>(
+        values: &[i32],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|v| match E::from_i32(*v) {
+            Some(e) => NameOrNumber::Name(e.as_str_name().to_string()),
+            None => NameOrNumber::Number(*v),
+        }))
+    }
+
+    pub fn deserialize<'de, E: ProtoEnum, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<i32>, D::Error> {
+        Vec::<NameOrNumber>::deserialize(deserializer)?
+            .into_iter()
+            .map(from_name_or_number::<E, D::Error>)
+            .collect()
+    }
+}
+
+fn from_name_or_number<E: ProtoEnum, Err: de::Error>(value: NameOrNumber) -> Result<i32, Err> {
+    match value {
+        NameOrNumber::Number(v) => Ok(v),
+        NameOrNumber::Name(name) => E::from_str_name(&name)
+            .ok_or_else(|| de::Error::custom(format!("unknown enum value {}", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Reservation, ReservationQuery, ReservationStatus};
+    use serde_json::json;
+
+    #[test]
+    fn reservation_should_map_to_json() {
+        let rsvp = Reservation::new_pending(
+            "tyr",
+            "room-1",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-27T12:00:00-0700".parse().unwrap(),
+            "note",
+        );
+        let value = serde_json::to_value(&rsvp).unwrap();
+        assert_eq!(value["status"], "RESERVATION_STATUS_PENDING");
+        assert_eq!(value["start"], "2022-12-26T22:00:00Z");
+        assert_eq!(value["end"], "2022-12-27T19:00:00Z");
+        assert_eq!(value["created_at"], json!(null));
+
+        let rsvp1: Reservation = serde_json::from_value(value).unwrap();
+        assert_eq!(rsvp1, rsvp);
+    }
+
+    #[test]
+    fn json_should_accept_missing_fields_and_enum_numbers() {
+        let query: ReservationQuery = serde_json::from_value(json!({
+            "user_id": "tyr",
+            "statuses": [1, "RESERVATION_STATUS_CONFIRMED"],
+            "start": "2022-12-26T15:00:00-07:00",
+        }))
+        .unwrap();
+        assert_eq!(query.user_id, "tyr");
+        assert_eq!(
+            query.statuses,
+            [
+                ReservationStatus::Pending as i32,
+                ReservationStatus::Confirmed as i32
+            ]
+        );
+        assert_eq!(query.start.unwrap().seconds, 1672092000);
+        assert_eq!(query.end, None);
+
+        let ret = serde_json::from_value::<Reservation>(json!({ "status": "PENDING" }));
+        assert!(ret
+            .unwrap_err()
+            .to_string()
+            .contains("unknown enum value PENDING"));
+    }
+}
@@ -1,18 +1,28 @@
 mod config;
 mod error;
+mod ics;
+pub mod json;
 mod pager;
 mod pb;
 mod types;
 mod utils;
 
 pub use config::*;
-pub use error::{Error, ReservationConflict, ReservationConflictInfo, ReservationWindow};
+pub use error::{ConflictingReservation, Error, ReservationConflict, ReservationWindow};
+pub use ics::{to_ics, ImportedEvent, ICS_UID_LABEL};
 pub use pb::*;
+pub use types::{
+    nearest_free_slots, suggest_window, validate_tenant_id, PagePosition, PageTokenCodec,
+    DEFAULT_SUGGEST_LIMIT, MAX_RESOURCE_POOL, SUGGEST_SPAN,
+};
 pub use utils::*;
 
+use sqlx::{Postgres, QueryBuilder};
+
 pub type ReservationId = i64;
 pub type UserId = String;
 pub type ResourceId = String;
+pub type TenantId = String;
 
 /// validate the data structure, raise error if invalid
 pub trait Validator {
@@ -32,8 +42,16 @@ pub trait Normalizer: Validator {
     fn do_normalize(&mut self);
 }
 
+/// generate parameterized sql, every value from the caller is bound as an argument
 pub trait ToSql {
-    fn to_sql(&self) -> String;
+    /// push the sql and its arguments into the query builder
+    fn push_sql<'a>(&'a self, builder: &mut QueryBuilder<'a, Postgres>);
+
+    fn to_sql(&self) -> QueryBuilder<'_, Postgres> {
+        let mut builder = QueryBuilder::new("");
+        self.push_sql(&mut builder);
+        builder
+    }
 }
 
 /// database equivalent of the "reservation_status" enum
@@ -1,9 +1,12 @@
+use crate::ReservationSortBy;
 use std::collections::VecDeque;
 
 pub struct PageInfo {
     pub cursor: Option<i64>,
     pub page_size: i64,
     pub desc: bool,
+    /// fetch the page before the cursor, data is fetched in reversed order
+    pub backward: bool,
 }
 pub struct Pager {
     pub prev: Option<i64>,
@@ -20,8 +23,17 @@ pub trait Id {
     fn id(&self) -> i64;
 }
 
+/// value of the sort key used together with the id for keyset pagination
+pub trait SortKey: Id {
+    fn sort_key(&self, sort_by: ReservationSortBy) -> i64;
+}
+
 impl Paginator for PageInfo {
     fn get_pager<T: Id>(&self, data: &mut VecDeque<T>) -> Pager {
+        if self.backward && self.cursor.is_some() {
+            return self.get_backward_pager(data);
+        }
+
         let has_prev = self.cursor.is_some();
         let prev = if has_prev {
             data.pop_front();
@@ -51,6 +63,7 @@ impl Paginator for PageInfo {
                 cursor: pager.next,
                 page_size: self.page_size,
                 desc: self.desc,
+                backward: false,
             })
         } else {
             None
@@ -63,6 +76,7 @@ impl Paginator for PageInfo {
                 cursor: pager.prev,
                 page_size: self.page_size,
                 desc: self.desc,
+                backward: true,
             })
         } else {
             None
@@ -70,9 +84,36 @@ impl Paginator for PageInfo {
     }
 }
 
+impl PageInfo {
+    // data is [cursor, items before the cursor in reversed order..., one more item if there's a prev page]
+    fn get_backward_pager<T: Id>(&self, data: &mut VecDeque<T>) -> Pager {
+        data.pop_front();
+
+        let has_prev = data.len() as i64 > self.page_size;
+        if has_prev {
+            data.pop_back();
+        }
+        data.make_contiguous().reverse();
+
+        // we came from the page after this one
+        Pager {
+            prev: if has_prev {
+                data.front().map(|v| v.id())
+            } else {
+                None
+            },
+            next: data.back().map(|v| v.id()),
+            total: None,
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod pager_test_utils {
-    use crate::pager::Id;
+    use crate::{
+        pager::{Id, SortKey},
+        ReservationSortBy,
+    };
     use std::collections::VecDeque;
 
     pub struct TestId(i64);
@@ -83,6 +124,16 @@ pub mod pager_test_utils {
         }
     }
 
+    impl SortKey for TestId {
+        // pretend items are created one second apart
+        fn sort_key(&self, sort_by: ReservationSortBy) -> i64 {
+            match sort_by {
+                ReservationSortBy::Id => self.0,
+                _ => self.0 * 1_000_000,
+            }
+        }
+    }
+
     pub fn generate_test_ids(start: i64, end: i64) -> VecDeque<TestId> {
         (start..=end).map(TestId).collect()
     }
@@ -99,6 +150,7 @@ mod tests {
             cursor: None,
             page_size: 10,
             desc: false,
+            backward: false,
         };
 
         // assume we got 11 items from db
@@ -135,5 +187,29 @@ mod tests {
             let prev_page = page.prev_page(&pager);
             assert_eq!(prev_page.unwrap().cursor, Some(21));
         }
+
+        // back to the second page, assume we got 11..=21 from db in reversed order
+        let page = page.prev_page(&pager).unwrap();
+        let mut items: VecDeque<_> = pager_test_utils::generate_test_ids(10, 21)
+            .into_iter()
+            .rev()
+            .collect();
+        let pager = page.get_pager(&mut items);
+        assert_eq!(items.front().map(|v| v.id()), Some(11));
+        assert_eq!(items.back().map(|v| v.id()), Some(20));
+        assert_eq!(pager.prev, Some(11));
+        assert_eq!(pager.next, Some(20));
+
+        // back to the first page, only 1..=11 left
+        let page = page.prev_page(&pager).unwrap();
+        let mut items: VecDeque<_> = pager_test_utils::generate_test_ids(1, 11)
+            .into_iter()
+            .rev()
+            .collect();
+        let pager = page.get_pager(&mut items);
+        assert_eq!(items.len(), 10);
+        assert_eq!(items.front().map(|v| v.id()), Some(1));
+        assert!(pager.prev.is_none());
+        assert_eq!(pager.next, Some(10));
     }
 }
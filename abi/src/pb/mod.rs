@@ -1,4 +1,22 @@
 #[allow(clippy::all, non_camel_case_types)]
 mod reservation;
 
+/// google.rpc messages for the error details of a status
+#[allow(clippy::all, non_camel_case_types)]
+#[path = "google.rpc.rs"]
+pub mod rpc;
+
+/// the standard grpc health checking protocol
+#[allow(clippy::all, non_camel_case_types)]
+#[path = "grpc.health.v1.rs"]
+pub mod health;
+
+/// the standard grpc server reflection protocol
+#[allow(clippy::all, non_camel_case_types)]
+#[path = "grpc.reflection.v1alpha.rs"]
+pub mod reflection;
+
+/// encoded `FileDescriptorSet` of the protos above and their imports, for server reflection
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/descriptor.bin"));
+
 pub use reservation::*;
@@ -1,195 +1,855 @@
 /// Core reservation object. Contains all the information for a reservation
 /// if ListenResponse op is DELETE, only id will be populated
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct Reservation {
     /// unique id for the reservation, if put into ReservationRequest, id should be empty
     #[prost(int64, tag = "1")]
+    #[serde(default)]
     pub id: i64,
     /// user id for the reservation
     #[prost(string, tag = "2")]
+    #[serde(default)]
     pub user_id: ::prost::alloc::string::String,
     /// reservation status, used for differentating purpose
     #[prost(enumeration = "ReservationStatus", tag = "3")]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<ReservationStatus, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<ReservationStatus, _>"
+    )]
+    #[schema(value_type = String)]
     pub status: i32,
     /// resource id for the reservation
     #[prost(string, tag = "4")]
+    #[serde(default)]
     pub resource_id: ::prost::alloc::string::String,
     /// start time for the reservation
     #[prost(message, optional, tag = "5")]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
     pub start: ::core::option::Option<::prost_types::Timestamp>,
     /// end time for the reservation
     #[prost(message, optional, tag = "6")]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
     pub end: ::core::option::Option<::prost_types::Timestamp>,
     /// extra note
     #[prost(string, tag = "7")]
+    #[serde(default)]
     pub note: ::prost::alloc::string::String,
+    /// user id the reservation is being transferred to, waiting for that user to accept it
+    #[prost(string, tag = "8")]
+    #[serde(default)]
+    pub pending_user_id: ::prost::alloc::string::String,
+    /// tenant the reservation belongs to, always set by the server from the caller's identity
+    #[prost(string, tag = "9")]
+    #[serde(default)]
+    pub tenant_id: ::prost::alloc::string::String,
+    /// arbitrary labels, e.g. project code or cost center
+    /// keys are alphanumerics, "-", "_", "." and "/", up to 63 chars, starting and ending with an alphanumeric
+    #[prost(map = "string, string", tag = "10")]
+    #[serde(default)]
+    pub labels:
+        ::std::collections::HashMap<::prost::alloc::string::String, ::prost::alloc::string::String>,
+    /// when the reservation is created, always set by the server
+    #[prost(message, optional, tag = "11")]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub created_at: ::core::option::Option<::prost_types::Timestamp>,
+}
+/// select reservations by their labels, similar to kubernetes label selectors
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct LabelSelector {
+    /// label key
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub key: ::prost::alloc::string::String,
+    /// how to match the label
+    #[prost(enumeration = "LabelOperator", tag = "2")]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<LabelOperator, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<LabelOperator, _>"
+    )]
+    #[schema(value_type = String)]
+    pub op: i32,
+    /// one value for EQUALS / NOT_EQUALS, one or more for IN / NOT_IN, none for EXISTS / DOES_NOT_EXIST
+    #[prost(string, repeated, tag = "3")]
+    #[serde(default)]
+    pub values: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 /// To make a reservation, send a ReservationRequest with Reservation object (id should be empty)
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct ReserveRequest {
     #[prost(message, optional, tag = "1")]
+    #[serde(default)]
     pub reservation: ::core::option::Option<Reservation>,
 }
 /// Created reservation will be returned in ReserveResponse
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct ReserveResponse {
     #[prost(message, optional, tag = "1")]
+    #[serde(default)]
     pub reservation: ::core::option::Option<Reservation>,
 }
+/// To check a reservation without making it, the same as ReserveRequest
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct CheckRequest {
+    #[prost(message, optional, tag = "1")]
+    #[serde(default)]
+    pub reservation: ::core::option::Option<Reservation>,
+}
+/// a reason the reservation could not be made
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct ReservationProblem {
+    /// the reason of the google.rpc.ErrorInfo reserve would return, e.g. INVALID_USER_ID, CONFLICT_RESERVATION
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub reason: ::prost::alloc::string::String,
+    /// field of the reservation failing the validation, empty for conflicts
+    #[prost(string, tag = "2")]
+    #[serde(default)]
+    pub field: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    #[serde(default)]
+    pub description: ::prost::alloc::string::String,
+    /// the conflicting reservation, only id, user_id, status, resource_id, start and end are populated
+    #[prost(message, optional, tag = "4")]
+    #[serde(default)]
+    pub conflict: ::core::option::Option<Reservation>,
+}
+/// Result of the check, nothing is written
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct CheckResponse {
+    /// true if the reservation could be made right now
+    #[prost(bool, tag = "1")]
+    #[serde(default)]
+    pub ok: bool,
+    /// validation failures, then the conflicts ordered by start. Conflicts are only looked up for a valid reservation
+    #[prost(message, repeated, tag = "2")]
+    #[serde(default)]
+    pub problems: ::prost::alloc::vec::Vec<ReservationProblem>,
+}
+/// To find other times or resources for a reservation, e.g. after it conflicts
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct SuggestRequest {
+    /// the reservation wanted, the suggested windows have the same duration
+    #[prost(message, optional, tag = "1")]
+    #[serde(default)]
+    pub reservation: ::core::option::Option<Reservation>,
+    /// number of the nearest free windows on the same resource, 3 if not set
+    #[prost(int32, tag = "2")]
+    #[serde(default)]
+    pub limit: i32,
+    /// resources equivalent to the one of the reservation, at most 50
+    #[prost(string, repeated, tag = "3")]
+    #[serde(default)]
+    pub resource_pool: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// a free window of a resource
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct FreeSlot {
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub resource_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub start: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "3")]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub end: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct SuggestResponse {
+    /// the nearest free windows on the same resource, ordered by distance to the requested start.
+    /// The requested window comes first if it's free. Only windows in the future and within four
+    /// durations of the requested one are suggested
+    #[prost(message, repeated, tag = "1")]
+    #[serde(default)]
+    pub slots: ::prost::alloc::vec::Vec<FreeSlot>,
+    /// resources of the pool free at the requested window
+    #[prost(string, repeated, tag = "2")]
+    #[serde(default)]
+    pub free_resources: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
 /// To update a reservation, send an UpdateRequest. Only note is updatable.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct UpdateRequest {
     #[prost(int64, tag = "1")]
+    #[serde(default)]
     pub id: i64,
     #[prost(string, tag = "2")]
+    #[serde(default)]
     pub note: ::prost::alloc::string::String,
 }
 /// Updated reservation will be returned in UpdateResponse
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct UpdateResponse {
     #[prost(message, optional, tag = "1")]
+    #[serde(default)]
     pub reservation: ::core::option::Option<Reservation>,
 }
 /// To change a reservation from pending to confirmed, send a ConfirmRequest
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct ConfirmRequest {
     #[prost(int64, tag = "1")]
+    #[serde(default)]
     pub id: i64,
 }
 /// Confirmed reservation will be returned in ConfirmResponse
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct ConfirmResponse {
     #[prost(message, optional, tag = "1")]
+    #[serde(default)]
     pub reservation: ::core::option::Option<Reservation>,
 }
 /// To cancel a reservation, send a CancelRequest
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct CancelRequest {
     #[prost(int64, tag = "1")]
+    #[serde(default)]
     pub id: i64,
 }
 /// Canceled reservation will be returned in CancelResponse
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct CancelResponse {
     #[prost(message, optional, tag = "1")]
+    #[serde(default)]
+    pub reservation: ::core::option::Option<Reservation>,
+}
+/// To transfer a reservation to another user, send a TransferRequest
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct TransferRequest {
+    #[prost(int64, tag = "1")]
+    #[serde(default)]
+    pub id: i64,
+    /// user id of the new owner
+    #[prost(string, tag = "2")]
+    #[serde(default)]
+    pub user_id: ::prost::alloc::string::String,
+    /// if true, the reservation is not moved until the new owner accepts the transfer
+    #[prost(bool, tag = "3")]
+    #[serde(default)]
+    pub require_acceptance: bool,
+}
+/// Transferred (or pending transfer) reservation will be returned in TransferResponse
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct TransferResponse {
+    #[prost(message, optional, tag = "1")]
+    #[serde(default)]
+    pub reservation: ::core::option::Option<Reservation>,
+}
+/// To accept a pending transfer, the new owner sends an AcceptTransferRequest
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct AcceptTransferRequest {
+    #[prost(int64, tag = "1")]
+    #[serde(default)]
+    pub id: i64,
+    /// user id of the new owner, must match the pending user id of the reservation
+    #[prost(string, tag = "2")]
+    #[serde(default)]
+    pub user_id: ::prost::alloc::string::String,
+}
+/// Transferred reservation will be returned in AcceptTransferResponse
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct AcceptTransferResponse {
+    #[prost(message, optional, tag = "1")]
+    #[serde(default)]
     pub reservation: ::core::option::Option<Reservation>,
 }
 /// To get a reservation, send a GetRequest
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct GetRequest {
     #[prost(int64, tag = "1")]
+    #[serde(default)]
     pub id: i64,
 }
 /// Reservation will be returned in GetResponse
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct GetResponse {
     #[prost(message, optional, tag = "1")]
+    #[serde(default)]
     pub reservation: ::core::option::Option<Reservation>,
 }
 /// query reservations with user id, resource id, start time, end time, and status
 #[derive(derive_builder::Builder)]
 #[builder(build_fn(name = "private_build"))]
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct ReservationQuery {
     /// resource id for the reservation query. If empty, query all resources
     #[prost(string, tag = "1")]
     #[builder(setter(into), default)]
+    #[serde(default)]
     pub resource_id: ::prost::alloc::string::String,
-    /// user id for the reservation query. If empty, query all users
+    /// user id for the reservation query. If empty, query all users. With auth, only service accounts
+    /// may query others, an empty one is the caller for everyone else
     #[prost(string, tag = "2")]
     #[builder(setter(into), default)]
+    #[serde(default)]
     pub user_id: ::prost::alloc::string::String,
-    /// use status to filter result. If UNKNOWN, return all reservations
+    /// deprecated, use statuses instead. If not UNKNOWN, it's treated as a single entry of statuses
     #[prost(enumeration = "ReservationStatus", tag = "3")]
     #[builder(setter(into), default)]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<ReservationStatus, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<ReservationStatus, _>"
+    )]
+    #[schema(value_type = String)]
     pub status: i32,
     /// start time for the reservation query, if 0, use Infinity for start time
     #[prost(message, optional, tag = "4")]
     #[builder(setter(into, strip_option), default)]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
     pub start: ::core::option::Option<::prost_types::Timestamp>,
     /// end time for the reservation query, if 0, use Infinity for end time
     #[prost(message, optional, tag = "5")]
     #[builder(setter(into, strip_option), default)]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
     pub end: ::core::option::Option<::prost_types::Timestamp>,
     /// sort direction
     #[prost(bool, tag = "6")]
     #[builder(setter(into), default)]
+    #[serde(default)]
     pub desc: bool,
+    /// tenant to query, always overwritten by the server with the caller's tenant
+    #[prost(string, tag = "7")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub tenant_id: ::prost::alloc::string::String,
+    /// all the selectors shall match the labels of the reservation
+    #[prost(message, repeated, tag = "8")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub label_selectors: ::prost::alloc::vec::Vec<LabelSelector>,
+    /// how start and end match the timespan of the reservation, new clients should use OVERLAPS
+    #[prost(enumeration = "ReservationQueryMode", tag = "9")]
+    #[builder(setter(into), default = "ReservationQueryMode::Overlaps as i32")]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<ReservationQueryMode, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<ReservationQueryMode, _>"
+    )]
+    #[schema(value_type = String)]
+    pub mode: i32,
+    /// return reservations in any of these statuses. If empty (or contains UNKNOWN), return all reservations
+    #[prost(enumeration = "ReservationStatus", repeated, tag = "10")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumerations::serialize::<ReservationStatus, _>",
+        deserialize_with = "crate::json::enumerations::deserialize::<ReservationStatus, _>"
+    )]
+    #[schema(value_type = Vec<String>)]
+    pub statuses: ::prost::alloc::vec::Vec<i32>,
 }
 /// To query reservations, send a QueryRequest
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct QueryRequest {
     #[prost(message, optional, tag = "1")]
+    #[serde(default)]
     pub query: ::core::option::Option<ReservationQuery>,
 }
-/// query reservations, order by reservation id
+/// To export reservations to calendar apps, send an ExportCalendarRequest
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct ExportCalendarRequest {
+    #[prost(message, optional, tag = "1")]
+    #[serde(default)]
+    pub query: ::core::option::Option<ReservationQuery>,
+}
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct ExportCalendarResponse {
+    /// iCalendar (RFC 5545) text, a VEVENT per reservation
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub calendar: ::prost::alloc::string::String,
+}
+/// how the events of an imported calendar become reservations
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct CalendarMapping {
+    #[prost(enumeration = "CalendarUserSource", tag = "1")]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<CalendarUserSource, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<CalendarUserSource, _>"
+    )]
+    #[schema(value_type = String)]
+    pub user_source: i32,
+    #[prost(enumeration = "CalendarResourceSource", tag = "2")]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<CalendarResourceSource, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<CalendarResourceSource, _>"
+    )]
+    #[schema(value_type = String)]
+    pub resource_source: i32,
+    /// calendar user addresses (without "mailto:") to user ids, an address not in it is used as the user id
+    #[prost(map = "string, string", tag = "3")]
+    #[serde(default)]
+    pub users:
+        ::std::collections::HashMap<::prost::alloc::string::String, ::prost::alloc::string::String>,
+    /// locations (or summaries) to resource ids, a location not in it is used as the resource id
+    #[prost(map = "string, string", tag = "4")]
+    #[serde(default)]
+    pub resources:
+        ::std::collections::HashMap<::prost::alloc::string::String, ::prost::alloc::string::String>,
+    /// offset from UTC in minutes for floating times and times with a TZID, time zones aren't resolved
+    #[prost(int32, tag = "5")]
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+/// To create reservations from the VEVENTs of an iCalendar, send an ImportCalendarRequest
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct ImportCalendarRequest {
+    /// iCalendar (RFC 5545) text
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub calendar: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    #[serde(default)]
+    pub mapping: ::core::option::Option<CalendarMapping>,
+    /// report what would happen without creating anything
+    #[prost(bool, tag = "3")]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct ImportResult {
+    /// UID of the event
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub uid: ::prost::alloc::string::String,
+    #[prost(enumeration = "ImportOutcome", tag = "2")]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<ImportOutcome, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<ImportOutcome, _>"
+    )]
+    #[schema(value_type = String)]
+    pub outcome: i32,
+    /// the reservation created (or would be created), not set if the event is invalid
+    #[prost(message, optional, tag = "3")]
+    #[serde(default)]
+    pub reservation: ::core::option::Option<Reservation>,
+    /// same as ErrorInfo.reason, empty if created
+    #[prost(string, tag = "4")]
+    #[serde(default)]
+    pub reason: ::prost::alloc::string::String,
+    /// human readable description of the error
+    #[prost(string, tag = "5")]
+    #[serde(default)]
+    pub description: ::prost::alloc::string::String,
+    /// reservations conflicting with the event. In a dry run, events earlier in the calendar are included with id 0
+    #[prost(message, repeated, tag = "6")]
+    #[serde(default)]
+    pub conflicts: ::prost::alloc::vec::Vec<Reservation>,
+}
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct ImportCalendarResponse {
+    /// a result for each VEVENT, in the order of the calendar
+    #[prost(message, repeated, tag = "1")]
+    #[serde(default)]
+    pub results: ::prost::alloc::vec::Vec<ImportResult>,
+}
+/// query reservations page by page, order by the sort key and then reservation id
 #[derive(derive_builder::Builder)]
 #[builder(build_fn(name = "private_build"))]
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct ReservationFilter {
     /// resource id for the reservation query. If empty, query all resources
     #[prost(string, tag = "1")]
     #[builder(setter(into), default)]
+    #[serde(default)]
     pub resource_id: ::prost::alloc::string::String,
-    /// user id for the reservation query. If empty, query all users
+    /// user id for the reservation query. If empty, query all users. With auth, only service accounts
+    /// may query others, an empty one is the caller for everyone else
     #[prost(string, tag = "2")]
     #[builder(setter(into), default)]
+    #[serde(default)]
     pub user_id: ::prost::alloc::string::String,
-    /// use status to filter result. If UNKNOWN, return all reservations
+    /// deprecated, use statuses instead. If not UNKNOWN, it's treated as a single entry of statuses
     #[prost(enumeration = "ReservationStatus", tag = "3")]
     #[builder(setter(into), default)]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<ReservationStatus, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<ReservationStatus, _>"
+    )]
+    #[schema(value_type = String)]
     pub status: i32,
+    /// deprecated, use page_token instead. Id of the cursor reservation
     #[prost(int64, optional, tag = "4")]
     #[builder(setter(into, strip_option), default)]
+    #[serde(default)]
     pub cursor: ::core::option::Option<i64>,
     /// page size for the query
     #[prost(int64, tag = "5")]
     #[builder(setter(into), default = "10")]
+    #[serde(default)]
     pub page_size: i64,
     /// sort direction
     #[prost(bool, tag = "6")]
     #[builder(setter(into), default)]
+    #[serde(default)]
     pub desc: bool,
+    /// tenant to filter, always overwritten by the server with the caller's tenant
+    #[prost(string, tag = "7")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub tenant_id: ::prost::alloc::string::String,
+    /// all the selectors shall match the labels of the reservation
+    #[prost(message, repeated, tag = "8")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub label_selectors: ::prost::alloc::vec::Vec<LabelSelector>,
+    /// return reservations in any of these statuses. If empty (or contains UNKNOWN), return all reservations
+    #[prost(enumeration = "ReservationStatus", repeated, tag = "9")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumerations::serialize::<ReservationStatus, _>",
+        deserialize_with = "crate::json::enumerations::deserialize::<ReservationStatus, _>"
+    )]
+    #[schema(value_type = Vec<String>)]
+    pub statuses: ::prost::alloc::vec::Vec<i32>,
+    /// start time of the window, reservations overlapping with the window are returned. If not set, no lower bound
+    #[prost(message, optional, tag = "10")]
+    #[builder(setter(into, strip_option), default)]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub start: ::core::option::Option<::prost_types::Timestamp>,
+    /// end time of the window. If not set, no upper bound
+    #[prost(message, optional, tag = "11")]
+    #[builder(setter(into, strip_option), default)]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub end: ::core::option::Option<::prost_types::Timestamp>,
+    /// sort key of the result
+    #[prost(enumeration = "ReservationSortBy", tag = "12")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<ReservationSortBy, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<ReservationSortBy, _>"
+    )]
+    #[schema(value_type = String)]
+    pub sort_by: i32,
+    /// deprecated, use page_token instead. Sort key of the cursor reservation, microseconds since
+    /// epoch for time based sort keys. Shall be set together with cursor unless sorted by id
+    #[prost(int64, optional, tag = "13")]
+    #[builder(setter(into, strip_option), default)]
+    #[serde(default)]
+    pub cursor_key: ::core::option::Option<i64>,
+    /// opaque token from the pager of the previous response, only valid for the same filter
+    #[prost(string, tag = "14")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub page_token: ::prost::alloc::string::String,
+    /// also return the total number of matched reservations in the pager, it costs an extra COUNT query
+    #[prost(bool, tag = "15")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub with_total: bool,
+    /// deprecated, use page_token instead. Get the page before the cursor rather than after it,
+    /// reservations are still returned in the requested order
+    #[prost(bool, tag = "16")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub backward: bool,
 }
 /// To query reservations, send a QueryRequest
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct FilterRequest {
     #[prost(message, optional, tag = "1")]
+    #[serde(default)]
     pub filter: ::core::option::Option<ReservationFilter>,
 }
-/// filter pager info
-#[derive(Clone, PartialEq, ::prost::Message)]
+/// filter pager info, prev/next and their keys are deprecated in favor of the tokens
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct FilterPager {
     #[prost(int64, optional, tag = "1")]
+    #[serde(default)]
     pub prev: ::core::option::Option<i64>,
     #[prost(int64, optional, tag = "2")]
+    #[serde(default)]
     pub next: ::core::option::Option<i64>,
+    /// total number of matched reservations, only set if with_total is requested
     #[prost(int64, optional, tag = "3")]
+    #[serde(default)]
     pub total: ::core::option::Option<i64>,
+    /// sort key of the prev cursor, only set if not sorted by id
+    #[prost(int64, optional, tag = "4")]
+    #[serde(default)]
+    pub prev_key: ::core::option::Option<i64>,
+    /// sort key of the next cursor, only set if not sorted by id
+    #[prost(int64, optional, tag = "5")]
+    #[serde(default)]
+    pub next_key: ::core::option::Option<i64>,
+    /// opaque token to get the previous page, empty if there's no previous page
+    #[prost(string, tag = "6")]
+    #[serde(default)]
+    pub prev_token: ::prost::alloc::string::String,
+    /// opaque token to get the next page, empty if there's no next page
+    #[prost(string, tag = "7")]
+    #[serde(default)]
+    pub next_token: ::prost::alloc::string::String,
 }
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct FilterResponse {
     #[prost(message, repeated, tag = "1")]
+    #[serde(default)]
     pub reservations: ::prost::alloc::vec::Vec<Reservation>,
     #[prost(message, optional, tag = "2")]
+    #[serde(default)]
     pub pager: ::core::option::Option<FilterPager>,
 }
+/// full text search over reservation notes, results are ranked by relevance
+#[derive(derive_builder::Builder)]
+#[builder(build_fn(name = "private_build"))]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct ReservationSearch {
+    /// text to search, supports web search syntax: "quoted phrase", or, -excluded
+    #[prost(string, tag = "1")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub text: ::prost::alloc::string::String,
+    /// resource id for the search. If empty, search all resources
+    #[prost(string, tag = "2")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub resource_id: ::prost::alloc::string::String,
+    /// user id for the search. If empty, search all users. With auth, only service accounts may
+    /// search others, an empty one is the caller for everyone else
+    #[prost(string, tag = "3")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub user_id: ::prost::alloc::string::String,
+    /// deprecated, use statuses instead. If not UNKNOWN, it's treated as a single entry of statuses
+    #[prost(enumeration = "ReservationStatus", tag = "4")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<ReservationStatus, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<ReservationStatus, _>"
+    )]
+    #[schema(value_type = String)]
+    pub status: i32,
+    /// page size for the search
+    #[prost(int64, tag = "5")]
+    #[builder(setter(into), default = "10")]
+    #[serde(default)]
+    pub page_size: i64,
+    /// number of hits to skip, ranked results are paged by offset
+    #[prost(int64, tag = "6")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub offset: i64,
+    /// tenant to search, always overwritten by the server with the caller's tenant
+    #[prost(string, tag = "7")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub tenant_id: ::prost::alloc::string::String,
+    /// all the selectors shall match the labels of the reservation
+    #[prost(message, repeated, tag = "8")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    pub label_selectors: ::prost::alloc::vec::Vec<LabelSelector>,
+    /// return reservations in any of these statuses. If empty (or contains UNKNOWN), return all reservations
+    #[prost(enumeration = "ReservationStatus", repeated, tag = "9")]
+    #[builder(setter(into), default)]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumerations::serialize::<ReservationStatus, _>",
+        deserialize_with = "crate::json::enumerations::deserialize::<ReservationStatus, _>"
+    )]
+    #[schema(value_type = Vec<String>)]
+    pub statuses: ::prost::alloc::vec::Vec<i32>,
+}
+/// To search reservations, send a SearchRequest
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct SearchRequest {
+    #[prost(message, optional, tag = "1")]
+    #[serde(default)]
+    pub search: ::core::option::Option<ReservationSearch>,
+}
+/// a reservation matching the search
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct SearchHit {
+    #[prost(message, optional, tag = "1")]
+    #[serde(default)]
+    pub reservation: ::core::option::Option<Reservation>,
+    /// relevance of the note to the search text, higher is better
+    #[prost(float, tag = "2")]
+    #[serde(default)]
+    pub rank: f32,
+    /// fragments of the note with matched words wrapped in <b></b>
+    #[prost(string, tag = "3")]
+    #[serde(default)]
+    pub snippet: ::prost::alloc::string::String,
+}
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct SearchResponse {
+    #[prost(message, repeated, tag = "1")]
+    #[serde(default)]
+    pub hits: ::prost::alloc::vec::Vec<SearchHit>,
+    /// offset for the next page, empty if this is the last page
+    #[prost(int64, optional, tag = "2")]
+    #[serde(default)]
+    pub next_offset: ::core::option::Option<i64>,
+}
 /// Client can listen to reservation updates by sending a ListenRequest
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct ListenRequest {}
 /// Server will send ListenResponse to client in streaming response
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
 pub struct ListenResponse {
     /// update type
     #[prost(enumeration = "ReservationUpdateType", tag = "1")]
+    #[serde(default)]
+    #[serde(
+        serialize_with = "crate::json::enumeration::serialize::<ReservationUpdateType, _>",
+        deserialize_with = "crate::json::enumeration::deserialize::<ReservationUpdateType, _>"
+    )]
+    #[schema(value_type = String)]
     pub op: i32,
     /// id for updated reservation
     #[prost(message, optional, tag = "2")]
+    #[serde(default)]
     pub reservation: ::core::option::Option<Reservation>,
 }
+/// detail of the status for a conflict reservation error, along with google.rpc.ErrorInfo
+#[derive(
+    serde::Serialize, serde::Deserialize, utoipa::ToSchema, Clone, PartialEq, ::prost::Message,
+)]
+pub struct ReservationConflictDetail {
+    /// window of the new reservation
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub resource_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub start: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "3")]
+    #[serde(default)]
+    #[serde(with = "crate::json::timestamp")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub end: ::core::option::Option<::prost_types::Timestamp>,
+    /// every existing reservation overlapping the window ordered by start, only id, user_id, status,
+    /// resource_id, start and end are populated
+    #[prost(message, repeated, tag = "4")]
+    #[serde(default)]
+    pub conflicts: ::prost::alloc::vec::Vec<Reservation>,
+}
 /// reservation status for a given time period
 #[derive(
-    sqlx::Type, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+    sqlx::Type,
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
 )]
 #[repr(i32)]
 pub enum ReservationStatus {
@@ -212,14 +872,158 @@ impl ReservationStatus {
         }
     }
 }
+/// how the time window of a query matches the timespan of a reservation
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
+)]
+#[repr(i32)]
+pub enum ReservationQueryMode {
+    /// not set (e.g. by old clients), same as CONTAINED_IN
+    Unspecified = 0,
+    /// reservation overlaps with the window
+    Overlaps = 1,
+    /// reservation is fully inside the window
+    ContainedIn = 2,
+    /// reservation covers the whole window
+    Contains = 3,
+    /// reservation starts inside the window
+    StartsWithin = 4,
+}
+impl ReservationQueryMode {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ReservationQueryMode::Unspecified => "RESERVATION_QUERY_MODE_UNSPECIFIED",
+            ReservationQueryMode::Overlaps => "RESERVATION_QUERY_MODE_OVERLAPS",
+            ReservationQueryMode::ContainedIn => "RESERVATION_QUERY_MODE_CONTAINED_IN",
+            ReservationQueryMode::Contains => "RESERVATION_QUERY_MODE_CONTAINS",
+            ReservationQueryMode::StartsWithin => "RESERVATION_QUERY_MODE_STARTS_WITHIN",
+        }
+    }
+}
+/// sort key of the filter result, ties are always broken by reservation id
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
+)]
+#[repr(i32)]
+pub enum ReservationSortBy {
+    Id = 0,
+    /// start time of the reservation
+    Start = 1,
+    /// end time of the reservation
+    End = 2,
+    /// creation time of the reservation
+    CreatedAt = 3,
+}
+impl ReservationSortBy {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ReservationSortBy::Id => "RESERVATION_SORT_BY_ID",
+            ReservationSortBy::Start => "RESERVATION_SORT_BY_START",
+            ReservationSortBy::End => "RESERVATION_SORT_BY_END",
+            ReservationSortBy::CreatedAt => "RESERVATION_SORT_BY_CREATED_AT",
+        }
+    }
+}
+/// how a label selector matches the labels of a reservation
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
+)]
+#[repr(i32)]
+pub enum LabelOperator {
+    Unknown = 0,
+    /// label exists and equals to the value
+    Equals = 1,
+    /// label doesn't exist or doesn't equal to the value
+    NotEquals = 2,
+    /// label exists and equals to one of the values
+    In = 3,
+    /// label doesn't exist or doesn't equal to any of the values
+    NotIn = 4,
+    /// label exists, whatever the value is
+    Exists = 5,
+    /// label doesn't exist
+    DoesNotExist = 6,
+}
+impl LabelOperator {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            LabelOperator::Unknown => "LABEL_OPERATOR_UNKNOWN",
+            LabelOperator::Equals => "LABEL_OPERATOR_EQUALS",
+            LabelOperator::NotEquals => "LABEL_OPERATOR_NOT_EQUALS",
+            LabelOperator::In => "LABEL_OPERATOR_IN",
+            LabelOperator::NotIn => "LABEL_OPERATOR_NOT_IN",
+            LabelOperator::Exists => "LABEL_OPERATOR_EXISTS",
+            LabelOperator::DoesNotExist => "LABEL_OPERATOR_DOES_NOT_EXIST",
+        }
+    }
+}
 /// when reservation is updated, record the update type
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
+)]
 #[repr(i32)]
 pub enum ReservationUpdateType {
     Unknown = 0,
     Create = 1,
     Update = 2,
     Delete = 3,
+    Transfer = 4,
 }
 impl ReservationUpdateType {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -232,6 +1036,111 @@ impl ReservationUpdateType {
             ReservationUpdateType::Create => "RESERVATION_UPDATE_TYPE_CREATE",
             ReservationUpdateType::Update => "RESERVATION_UPDATE_TYPE_UPDATE",
             ReservationUpdateType::Delete => "RESERVATION_UPDATE_TYPE_DELETE",
+            ReservationUpdateType::Transfer => "RESERVATION_UPDATE_TYPE_TRANSFER",
+        }
+    }
+}
+/// where the user id of an imported event comes from
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
+)]
+#[repr(i32)]
+pub enum CalendarUserSource {
+    Organizer = 0,
+    /// the first attendee
+    Attendee = 1,
+}
+impl CalendarUserSource {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            CalendarUserSource::Organizer => "CALENDAR_USER_SOURCE_ORGANIZER",
+            CalendarUserSource::Attendee => "CALENDAR_USER_SOURCE_ATTENDEE",
+        }
+    }
+}
+/// where the resource id of an imported event comes from
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
+)]
+#[repr(i32)]
+pub enum CalendarResourceSource {
+    Location = 0,
+    Summary = 1,
+}
+impl CalendarResourceSource {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            CalendarResourceSource::Location => "CALENDAR_RESOURCE_SOURCE_LOCATION",
+            CalendarResourceSource::Summary => "CALENDAR_RESOURCE_SOURCE_SUMMARY",
+        }
+    }
+}
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
+)]
+#[repr(i32)]
+pub enum ImportOutcome {
+    Unknown = 0,
+    /// the reservation is created, or would be created in a dry run
+    Created = 1,
+    Conflict = 2,
+    Invalid = 3,
+    /// cancelled events aren't imported
+    Skipped = 4,
+}
+impl ImportOutcome {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ImportOutcome::Unknown => "IMPORT_OUTCOME_UNKNOWN",
+            ImportOutcome::Created => "IMPORT_OUTCOME_CREATED",
+            ImportOutcome::Conflict => "IMPORT_OUTCOME_CONFLICT",
+            ImportOutcome::Invalid => "IMPORT_OUTCOME_INVALID",
+            ImportOutcome::Skipped => "IMPORT_OUTCOME_SKIPPED",
         }
     }
 }
@@ -298,17 +1207,49 @@ pub mod reservation_service_client {
             self.inner = self.inner.send_compressed(encoding);
             self
         }
-        /// Enable decompressing responses.
-        #[must_use]
-        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.inner = self.inner.accept_compressed(encoding);
-            self
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// make a reservation
+        pub async fn reserve(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ReserveRequest>,
+        ) -> Result<tonic::Response<super::ReserveResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.ReservationService/reserve");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// check a reservation without making it: run the validation and look for the conflicts
+        pub async fn check(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CheckRequest>,
+        ) -> Result<tonic::Response<super::CheckResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.ReservationService/check");
+            self.inner.unary(request.into_request(), path, codec).await
         }
-        /// make a reservation
-        pub async fn reserve(
+        /// suggest the nearest free windows of the resource, and the equivalent resources free at the time
+        pub async fn suggest(
             &mut self,
-            request: impl tonic::IntoRequest<super::ReserveRequest>,
-        ) -> Result<tonic::Response<super::ReserveResponse>, tonic::Status> {
+            request: impl tonic::IntoRequest<super::SuggestRequest>,
+        ) -> Result<tonic::Response<super::SuggestResponse>, tonic::Status> {
             self.inner.ready().await.map_err(|e| {
                 tonic::Status::new(
                     tonic::Code::Unknown,
@@ -317,7 +1258,7 @@ pub mod reservation_service_client {
             })?;
             let codec = tonic::codec::ProstCodec::default();
             let path =
-                http::uri::PathAndQuery::from_static("/reservation.ReservationService/reserve");
+                http::uri::PathAndQuery::from_static("/reservation.ReservationService/suggest");
             self.inner.unary(request.into_request(), path, codec).await
         }
         /// confirm a pending reservation, if reservation is not pending, do nothing
@@ -368,6 +1309,39 @@ pub mod reservation_service_client {
                 http::uri::PathAndQuery::from_static("/reservation.ReservationService/cancel");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        /// transfer a reservation to another user, optionally waiting for the new owner to accept it
+        pub async fn transfer(
+            &mut self,
+            request: impl tonic::IntoRequest<super::TransferRequest>,
+        ) -> Result<tonic::Response<super::TransferResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.ReservationService/transfer");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// accept a pending transfer, NOT_FOUND if there's no pending transfer for the user
+        pub async fn accept_transfer(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AcceptTransferRequest>,
+        ) -> Result<tonic::Response<super::AcceptTransferResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/reservation.ReservationService/accept_transfer",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
         /// get a reservation by id
         pub async fn get(
             &mut self,
@@ -402,6 +1376,40 @@ pub mod reservation_service_client {
                 .server_streaming(request.into_request(), path, codec)
                 .await
         }
+        /// export the reservations of the query as an iCalendar
+        pub async fn export_calendar(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExportCalendarRequest>,
+        ) -> Result<tonic::Response<super::ExportCalendarResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/reservation.ReservationService/export_calendar",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// create reservations from the events of an iCalendar, with a result for each event
+        pub async fn import_calendar(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ImportCalendarRequest>,
+        ) -> Result<tonic::Response<super::ImportCalendarResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/reservation.ReservationService/import_calendar",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
         /// filter reservations, order by reservation id
         pub async fn filter(
             &mut self,
@@ -418,11 +1426,27 @@ pub mod reservation_service_client {
                 http::uri::PathAndQuery::from_static("/reservation.ReservationService/filter");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        /// full text search over reservation notes, ranked by relevance
+        pub async fn search(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SearchRequest>,
+        ) -> Result<tonic::Response<super::SearchResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.ReservationService/search");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
         /// another system could monitor newly added/confirmed/cancelled reservations
         pub async fn listen(
             &mut self,
             request: impl tonic::IntoRequest<super::ListenRequest>,
-        ) -> Result<tonic::Response<tonic::codec::Streaming<super::Reservation>>, tonic::Status>
+        ) -> Result<tonic::Response<tonic::codec::Streaming<super::ListenResponse>>, tonic::Status>
         {
             self.inner.ready().await.map_err(|e| {
                 tonic::Status::new(
@@ -451,6 +1475,16 @@ pub mod reservation_service_server {
             &self,
             request: tonic::Request<super::ReserveRequest>,
         ) -> Result<tonic::Response<super::ReserveResponse>, tonic::Status>;
+        /// check a reservation without making it: run the validation and look for the conflicts
+        async fn check(
+            &self,
+            request: tonic::Request<super::CheckRequest>,
+        ) -> Result<tonic::Response<super::CheckResponse>, tonic::Status>;
+        /// suggest the nearest free windows of the resource, and the equivalent resources free at the time
+        async fn suggest(
+            &self,
+            request: tonic::Request<super::SuggestRequest>,
+        ) -> Result<tonic::Response<super::SuggestResponse>, tonic::Status>;
         /// confirm a pending reservation, if reservation is not pending, do nothing
         async fn confirm(
             &self,
@@ -466,6 +1500,16 @@ pub mod reservation_service_server {
             &self,
             request: tonic::Request<super::CancelRequest>,
         ) -> Result<tonic::Response<super::CancelResponse>, tonic::Status>;
+        /// transfer a reservation to another user, optionally waiting for the new owner to accept it
+        async fn transfer(
+            &self,
+            request: tonic::Request<super::TransferRequest>,
+        ) -> Result<tonic::Response<super::TransferResponse>, tonic::Status>;
+        /// accept a pending transfer, NOT_FOUND if there's no pending transfer for the user
+        async fn accept_transfer(
+            &self,
+            request: tonic::Request<super::AcceptTransferRequest>,
+        ) -> Result<tonic::Response<super::AcceptTransferResponse>, tonic::Status>;
         /// get a reservation by id
         async fn get(
             &self,
@@ -480,13 +1524,28 @@ pub mod reservation_service_server {
             &self,
             request: tonic::Request<super::QueryRequest>,
         ) -> Result<tonic::Response<Self::queryStream>, tonic::Status>;
+        /// export the reservations of the query as an iCalendar
+        async fn export_calendar(
+            &self,
+            request: tonic::Request<super::ExportCalendarRequest>,
+        ) -> Result<tonic::Response<super::ExportCalendarResponse>, tonic::Status>;
+        /// create reservations from the events of an iCalendar, with a result for each event
+        async fn import_calendar(
+            &self,
+            request: tonic::Request<super::ImportCalendarRequest>,
+        ) -> Result<tonic::Response<super::ImportCalendarResponse>, tonic::Status>;
         /// filter reservations, order by reservation id
         async fn filter(
             &self,
             request: tonic::Request<super::FilterRequest>,
         ) -> Result<tonic::Response<super::FilterResponse>, tonic::Status>;
+        /// full text search over reservation notes, ranked by relevance
+        async fn search(
+            &self,
+            request: tonic::Request<super::SearchRequest>,
+        ) -> Result<tonic::Response<super::SearchResponse>, tonic::Status>;
         ///Server streaming response type for the listen method.
-        type listenStream: futures_core::Stream<Item = Result<super::Reservation, tonic::Status>>
+        type listenStream: futures_core::Stream<Item = Result<super::ListenResponse, tonic::Status>>
             + Send
             + 'static;
         /// another system could monitor newly added/confirmed/cancelled reservations
@@ -580,6 +1639,68 @@ pub mod reservation_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/reservation.ReservationService/check" => {
+                    #[allow(non_camel_case_types)]
+                    struct checkSvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService> tonic::server::UnaryService<super::CheckRequest> for checkSvc<T> {
+                        type Response = super::CheckResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CheckRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).check(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = checkSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/reservation.ReservationService/suggest" => {
+                    #[allow(non_camel_case_types)]
+                    struct suggestSvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService> tonic::server::UnaryService<super::SuggestRequest> for suggestSvc<T> {
+                        type Response = super::SuggestResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SuggestRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).suggest(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = suggestSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/reservation.ReservationService/confirm" => {
                     #[allow(non_camel_case_types)]
                     struct confirmSvc<T: ReservationService>(pub Arc<T>);
@@ -673,6 +1794,71 @@ pub mod reservation_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/reservation.ReservationService/transfer" => {
+                    #[allow(non_camel_case_types)]
+                    struct transferSvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService> tonic::server::UnaryService<super::TransferRequest> for transferSvc<T> {
+                        type Response = super::TransferResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::TransferRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).transfer(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = transferSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/reservation.ReservationService/accept_transfer" => {
+                    #[allow(non_camel_case_types)]
+                    struct accept_transferSvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService>
+                        tonic::server::UnaryService<super::AcceptTransferRequest>
+                        for accept_transferSvc<T>
+                    {
+                        type Response = super::AcceptTransferResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AcceptTransferRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).accept_transfer(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = accept_transferSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/reservation.ReservationService/get" => {
                     #[allow(non_camel_case_types)]
                     struct getSvc<T: ReservationService>(pub Arc<T>);
@@ -739,6 +1925,74 @@ pub mod reservation_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/reservation.ReservationService/export_calendar" => {
+                    #[allow(non_camel_case_types)]
+                    struct export_calendarSvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService>
+                        tonic::server::UnaryService<super::ExportCalendarRequest>
+                        for export_calendarSvc<T>
+                    {
+                        type Response = super::ExportCalendarResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExportCalendarRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).export_calendar(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = export_calendarSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/reservation.ReservationService/import_calendar" => {
+                    #[allow(non_camel_case_types)]
+                    struct import_calendarSvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService>
+                        tonic::server::UnaryService<super::ImportCalendarRequest>
+                        for import_calendarSvc<T>
+                    {
+                        type Response = super::ImportCalendarResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ImportCalendarRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).import_calendar(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = import_calendarSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/reservation.ReservationService/filter" => {
                     #[allow(non_camel_case_types)]
                     struct filterSvc<T: ReservationService>(pub Arc<T>);
@@ -770,6 +2024,37 @@ pub mod reservation_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/reservation.ReservationService/search" => {
+                    #[allow(non_camel_case_types)]
+                    struct searchSvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService> tonic::server::UnaryService<super::SearchRequest> for searchSvc<T> {
+                        type Response = super::SearchResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SearchRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).search(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = searchSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/reservation.ReservationService/listen" => {
                     #[allow(non_camel_case_types)]
                     struct listenSvc<T: ReservationService>(pub Arc<T>);
@@ -777,7 +2062,7 @@ pub mod reservation_service_server {
                         tonic::server::ServerStreamingService<super::ListenRequest>
                         for listenSvc<T>
                     {
-                        type Response = super::Reservation;
+                        type Response = super::ListenResponse;
                         type ResponseStream = T::listenStream;
                         type Future =
                             BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
@@ -0,0 +1,159 @@
+use chrono::{DateTime, Utc};
+use prost_types::Timestamp;
+
+use crate::{convert_micros_to_utc_time, convert_to_timestamp, FreeSlot, Reservation};
+
+use super::TimeRange;
+
+/// number of the suggested windows if the request doesn't say
+pub const DEFAULT_SUGGEST_LIMIT: usize = 3;
+/// free windows are only looked for this many durations of the reservation before and after it
+pub const SUGGEST_SPAN: i64 = 4;
+/// the most resources of a pool a suggestion checks
+pub const MAX_RESOURCE_POOL: usize = 50;
+
+/// the window free windows are looked for in, None if the reservation has no valid time
+pub fn suggest_window(rsvp: &Reservation) -> Option<(Timestamp, Timestamp)> {
+    let (start, end) = window(rsvp)?;
+    Some((micros_to_timestamp(start), micros_to_timestamp(end)))
+}
+
+/// the `limit` free windows of the same duration on the resource of the reservation, nearest to its
+/// start first, within the `suggest_window` and not before `now`. `busy` needs to hold the
+/// reservations of that window. A free window nearest to the requested one always starts at the
+/// requested start, at the end of a busy window, or right before the start of a busy window, so only
+/// those are checked
+pub fn nearest_free_slots(
+    rsvp: &Reservation,
+    busy: &[Reservation],
+    limit: usize,
+    now: DateTime<Utc>,
+) -> Vec<FreeSlot> {
+    let (start, end) = match (TimeRange::of(rsvp).start(), TimeRange::of(rsvp).end()) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        _ => return vec![],
+    };
+    let duration = end - start;
+    let (window_start, window_end) = window(rsvp).unwrap();
+    let not_before = window_start.max(now.timestamp_micros());
+    let busy: Vec<_> = busy
+        .iter()
+        .filter(|r| r.resource_id == rsvp.resource_id)
+        .filter_map(|r| Some((TimeRange::of(r).start()?, TimeRange::of(r).end()?)))
+        .collect();
+
+    let mut candidates: Vec<_> = busy
+        .iter()
+        .flat_map(|&(busy_start, busy_end)| [busy_end, busy_start - duration])
+        .chain([start])
+        .filter(|t| *t >= not_before && t.saturating_add(duration) <= window_end)
+        .filter(|t| {
+            busy.iter()
+                .all(|&(busy_start, busy_end)| t + duration <= busy_start || busy_end <= *t)
+        })
+        .collect();
+    candidates.sort_by_key(|t| ((t - start).abs(), *t));
+    candidates.dedup();
+
+    candidates
+        .into_iter()
+        .take(limit)
+        .map(|t| FreeSlot {
+            resource_id: rsvp.resource_id.clone(),
+            start: Some(convert_to_timestamp(&convert_micros_to_utc_time(t))),
+            end: Some(convert_to_timestamp(&convert_micros_to_utc_time(
+                t + duration,
+            ))),
+        })
+        .collect()
+}
+
+/// start and end of the suggest window in micros
+fn window(rsvp: &Reservation) -> Option<(i64, i64)> {
+    let (start, end) = match (TimeRange::of(rsvp).start(), TimeRange::of(rsvp).end()) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        _ => return None,
+    };
+    let span = (end - start).saturating_mul(SUGGEST_SPAN);
+    Some((start.saturating_sub(span), end.saturating_add(span)))
+}
+
+fn micros_to_timestamp(micros: i64) -> Timestamp {
+    Timestamp {
+        seconds: micros.div_euclid(1_000_000),
+        nanos: (micros.rem_euclid(1_000_000) * 1_000) as _,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rsvp(start: &str, end: &str) -> Reservation {
+        Reservation::new_pending(
+            "tyrid",
+            "ocean-view-room-713",
+            start.parse().unwrap(),
+            end.parse().unwrap(),
+            "",
+        )
+    }
+
+    fn time(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn nearest_free_slots_should_work() {
+        let now = time("2022-01-01T00:00:00Z");
+        let busy = vec![
+            rsvp("2022-12-10T00:00:00Z", "2022-12-12T00:00:00Z"),
+            rsvp("2022-12-13T00:00:00Z", "2022-12-15T00:00:00Z"),
+        ];
+        let wanted = rsvp("2022-12-11T00:00:00Z", "2022-12-12T00:00:00Z");
+
+        let slots = nearest_free_slots(&wanted, &busy, 3, now);
+        let starts: Vec<_> = slots
+            .iter()
+            .map(|s| s.start.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            starts,
+            vec![
+                "2022-12-12T00:00:00Z",
+                "2022-12-09T00:00:00Z",
+                "2022-12-15T00:00:00Z"
+            ]
+        );
+        assert_eq!(
+            slots[0].end.as_ref().unwrap().to_string(),
+            "2022-12-13T00:00:00Z"
+        );
+
+        // the requested window comes first if it's free
+        let wanted = rsvp("2022-12-20T00:00:00Z", "2022-12-21T00:00:00Z");
+        let slots = nearest_free_slots(&wanted, &busy, 1, now);
+        assert_eq!(slots[0].start, wanted.start);
+    }
+
+    #[test]
+    fn nearest_free_slots_should_stay_in_the_window_and_the_future() {
+        let busy = vec![
+            rsvp("2022-12-10T00:00:00Z", "2022-12-12T00:00:00Z"),
+            rsvp("2022-12-13T00:00:00Z", "2022-12-15T00:00:00Z"),
+            rsvp("2022-12-15T00:00:00Z", "2022-12-16T00:00:00Z"),
+        ];
+        let wanted = rsvp("2022-12-11T00:00:00Z", "2022-12-12T00:00:00Z");
+        let (start, end) = suggest_window(&wanted).unwrap();
+        assert_eq!(start.to_string(), "2022-12-07T00:00:00Z");
+        assert_eq!(end.to_string(), "2022-12-16T00:00:00Z");
+
+        // the day after the last busy window is out of the window, the day before the first is past
+        let slots = nearest_free_slots(&wanted, &busy, 10, time("2022-12-10T00:00:00Z"));
+        let starts: Vec<_> = slots
+            .iter()
+            .map(|s| s.start.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(starts, vec!["2022-12-12T00:00:00Z"]);
+    }
+}
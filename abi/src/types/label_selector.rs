@@ -0,0 +1,304 @@
+use crate::{Error, LabelOperator, LabelSelector, ToSql, Validator};
+use sqlx::{types::Json, Postgres, QueryBuilder};
+use std::{collections::HashMap, str::FromStr};
+
+/// the longest label key, same as the name part of a kubernetes label key
+const MAX_LABEL_KEY_LEN: usize = 63;
+
+impl LabelSelector {
+    pub fn new(
+        key: impl Into<String>,
+        op: LabelOperator,
+        values: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            key: key.into(),
+            op: op as i32,
+            values: values.into_iter().map(|v| v.into()).collect(),
+        }
+    }
+
+    pub fn get_op(&self) -> LabelOperator {
+        LabelOperator::from_i32(self.op).unwrap_or(LabelOperator::Unknown)
+    }
+
+    /// check the labels of a reservation in memory, same as the generated sql
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        let value = labels.get(&self.key);
+        let any_of = || value.is_some_and(|v| self.values.contains(v));
+        match self.get_op() {
+            LabelOperator::Equals | LabelOperator::In => any_of(),
+            LabelOperator::NotEquals | LabelOperator::NotIn => !any_of(),
+            LabelOperator::Exists => value.is_some(),
+            LabelOperator::DoesNotExist => value.is_none(),
+            LabelOperator::Unknown => false,
+        }
+    }
+
+    /// parse comma separated selectors, e.g. "env=prod,tier in (db, cache),!deprecated"
+    pub fn parse_all(s: &str) -> Result<Vec<Self>, Error> {
+        let mut selectors = vec![];
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    selectors.push(s[start..i].parse()?);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        if !s[start..].trim().is_empty() {
+            selectors.push(s[start..].parse()?);
+        }
+        Ok(selectors)
+    }
+}
+
+impl Validator for LabelSelector {
+    fn validate(&self) -> Result<(), Error> {
+        let err = || Error::InvalidLabelSelector(format!("{:?}", self));
+        if !is_label_key(&self.key) {
+            return Err(err());
+        }
+
+        let valid = match self.get_op() {
+            LabelOperator::Unknown => false,
+            LabelOperator::Equals | LabelOperator::NotEquals => self.values.len() == 1,
+            LabelOperator::In | LabelOperator::NotIn => !self.values.is_empty(),
+            LabelOperator::Exists | LabelOperator::DoesNotExist => self.values.is_empty(),
+        };
+        if !valid {
+            return Err(err());
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = Error;
+
+    // supported forms: "key", "!key", "key=value", "key==value", "key!=value", "key in (v1, v2)", "key notin (v1, v2)"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::InvalidLabelSelector(s.to_string());
+        let s = s.trim();
+
+        let selector = if let Some(key) = s.strip_prefix('!') {
+            LabelSelector::new(
+                key.trim(),
+                LabelOperator::DoesNotExist,
+                Vec::<String>::new(),
+            )
+        } else if let Some((key, value)) = s.split_once("!=") {
+            LabelSelector::new(key.trim(), LabelOperator::NotEquals, [value.trim()])
+        } else if let Some((key, value)) = s.split_once("==").or_else(|| s.split_once('=')) {
+            LabelSelector::new(key.trim(), LabelOperator::Equals, [value.trim()])
+        } else if let Some((key, rest)) = s.split_once(char::is_whitespace) {
+            let rest = rest.trim_start();
+            let (op, values) = if let Some(values) = rest.strip_prefix("notin") {
+                (LabelOperator::NotIn, values)
+            } else if let Some(values) = rest.strip_prefix("in") {
+                (LabelOperator::In, values)
+            } else {
+                return Err(err());
+            };
+            let values = values
+                .trim()
+                .strip_prefix('(')
+                .and_then(|v| v.strip_suffix(')'))
+                .ok_or_else(err)?;
+            LabelSelector::new(key, op, values.split(',').map(|v| v.trim()))
+        } else {
+            LabelSelector::new(s, LabelOperator::Exists, Vec::<String>::new())
+        };
+
+        selector.validate().map_err(|_| err())?;
+        Ok(selector)
+    }
+}
+
+impl ToSql for LabelSelector {
+    fn push_sql<'a>(&'a self, builder: &mut QueryBuilder<'a, Postgres>) {
+        let any_of = |builder: &mut QueryBuilder<'a, Postgres>| {
+            builder.push("(");
+            for (i, v) in self.values.iter().enumerate() {
+                if i > 0 {
+                    builder.push(" OR ");
+                }
+                builder.push("labels @> ").push_bind(label(&self.key, v));
+            }
+            builder.push(")");
+        };
+
+        // keep the conditions in a form that the GIN index on labels could be used
+        match self.get_op() {
+            LabelOperator::Equals => {
+                builder
+                    .push("labels @> ")
+                    .push_bind(label(&self.key, &self.values[0]));
+            }
+            LabelOperator::NotEquals => {
+                builder
+                    .push("NOT labels @> ")
+                    .push_bind(label(&self.key, &self.values[0]));
+            }
+            LabelOperator::In => any_of(builder),
+            LabelOperator::NotIn => {
+                builder.push("NOT ");
+                any_of(builder);
+            }
+            LabelOperator::Exists => {
+                builder.push("labels ? ").push_bind(&self.key);
+            }
+            LabelOperator::DoesNotExist => {
+                builder.push("NOT labels ? ").push_bind(&self.key);
+            }
+            LabelOperator::Unknown => {
+                builder.push("FALSE");
+            }
+        }
+    }
+}
+
+/// validate labels of a reservation, keys shall be the ones a selector accepts, so every label can be selected
+pub fn validate_labels(labels: &HashMap<String, String>) -> Result<(), Error> {
+    match labels.keys().find(|k| !is_label_key(k)) {
+        Some(key) => Err(Error::InvalidLabel(key.clone())),
+        None => Ok(()),
+    }
+}
+
+// alphanumerics, '-', '_', '.' and '/', starting and ending with an alphanumeric
+fn is_label_key(key: &str) -> bool {
+    let is_edge = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+    key.len() <= MAX_LABEL_KEY_LEN
+        && is_edge(key.chars().next())
+        && is_edge(key.chars().last())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+}
+
+/// push all the selectors, each one as an AND condition
+pub(crate) fn push_label_selectors<'a>(
+    builder: &mut QueryBuilder<'a, Postgres>,
+    selectors: &'a [LabelSelector],
+) {
+    for selector in selectors {
+        builder.push(" AND ");
+        selector.push_sql(builder);
+    }
+}
+
+/// check all the selectors in memory
+pub(crate) fn matches_label_selectors(
+    labels: &HashMap<String, String>,
+    selectors: &[LabelSelector],
+) -> bool {
+    selectors.iter().all(|s| s.matches(labels))
+}
+
+/// single label as a jsonb object
+fn label<'a>(key: &'a str, value: &'a str) -> Json<HashMap<&'a str, &'a str>> {
+    Json(HashMap::from([(key, value)]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_selector_should_parse() {
+        let selectors =
+            LabelSelector::parse_all("env=prod, tier in (db, cache),!deprecated,team, cc!=42")
+                .unwrap();
+        assert_eq!(
+            selectors,
+            vec![
+                LabelSelector::new("env", LabelOperator::Equals, ["prod"]),
+                LabelSelector::new("tier", LabelOperator::In, ["db", "cache"]),
+                LabelSelector::new(
+                    "deprecated",
+                    LabelOperator::DoesNotExist,
+                    Vec::<String>::new()
+                ),
+                LabelSelector::new("team", LabelOperator::Exists, Vec::<String>::new()),
+                LabelSelector::new("cc", LabelOperator::NotEquals, ["42"]),
+            ]
+        );
+
+        let selector: LabelSelector = "tier notin (db)".parse().unwrap();
+        assert_eq!(
+            selector,
+            LabelSelector::new("tier", LabelOperator::NotIn, ["db"])
+        );
+    }
+
+    #[test]
+    fn invalid_label_selector_should_be_rejected() {
+        assert!("=prod".parse::<LabelSelector>().is_err());
+        assert!("tier in db".parse::<LabelSelector>().is_err());
+
+        let selector = LabelSelector::new("env", LabelOperator::In, Vec::<String>::new());
+        assert!(selector.validate().is_err());
+        let selector = LabelSelector::new("env", LabelOperator::Exists, ["prod"]);
+        assert!(selector.validate().is_err());
+        assert!("env prod".parse::<LabelSelector>().is_err());
+        assert!("-env".parse::<LabelSelector>().is_err());
+        let key = "k".repeat(MAX_LABEL_KEY_LEN + 1);
+        assert!(key.parse::<LabelSelector>().is_err());
+    }
+
+    #[test]
+    fn validate_labels_should_report_the_bad_key() {
+        let labels = HashMap::from([
+            ("cost-center".to_string(), "42".to_string()),
+            ("example.com/team".to_string(), "infra".to_string()),
+        ]);
+        assert!(validate_labels(&labels).is_ok());
+
+        for key in ["", "env=prod", "tier in", "!env", "-env", "env_"] {
+            let labels = HashMap::from([(key.to_string(), "x".to_string())]);
+            let err = validate_labels(&labels).unwrap_err();
+            assert_eq!(err, Error::InvalidLabel(key.to_string()));
+        }
+        let key = "k".repeat(MAX_LABEL_KEY_LEN + 1);
+        let labels = HashMap::from([(key.clone(), "x".to_string())]);
+        assert_eq!(
+            validate_labels(&labels).unwrap_err(),
+            Error::InvalidLabel(key)
+        );
+    }
+
+    #[test]
+    fn label_selector_should_generate_correct_sql() {
+        let selector = LabelSelector::new("env", LabelOperator::Equals, ["prod"]);
+        assert_eq!(selector.to_sql().sql(), "labels @> $1");
+
+        let selector = LabelSelector::new("tier", LabelOperator::NotIn, ["db", "cache"]);
+        assert_eq!(
+            selector.to_sql().sql(),
+            "NOT (labels @> $1 OR labels @> $2)"
+        );
+
+        let selector = LabelSelector::new("team", LabelOperator::Exists, Vec::<String>::new());
+        assert_eq!(selector.to_sql().sql(), "labels ? $1");
+    }
+
+    #[test]
+    fn label_selector_should_match_labels() {
+        let labels = HashMap::from([("env".to_string(), "prod".to_string())]);
+        let matches = |s: &str| s.parse::<LabelSelector>().unwrap().matches(&labels);
+
+        assert!(matches("env=prod"));
+        assert!(!matches("env!=prod"));
+        assert!(matches("env in (dev, prod)"));
+        assert!(matches("tier notin (db)"));
+        assert!(matches("env"));
+        assert!(matches("!tier"));
+        assert!(!matches("tier=db"));
+    }
+}
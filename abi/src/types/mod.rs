@@ -1,15 +1,30 @@
+mod free_slot;
+mod label_selector;
+mod page_token;
 mod request;
 mod reservation;
 mod reservation_filter;
 mod reservation_query;
+mod reservation_search;
 mod reservation_status;
 
-use crate::{convert_to_utc_time, Error};
+use crate::{convert_to_micros, convert_to_utc_time, Error, Reservation};
 use chrono::{DateTime, Utc};
 use prost_types::Timestamp;
-use sqlx::postgres::types::PgRange;
+use sqlx::{postgres::types::PgRange, Postgres, QueryBuilder};
 use std::ops::Bound;
 
+pub use free_slot::{
+    nearest_free_slots, suggest_window, DEFAULT_SUGGEST_LIMIT, MAX_RESOURCE_POOL, SUGGEST_SPAN,
+};
+pub use label_selector::validate_labels;
+pub use page_token::{PagePosition, PageTokenCodec};
+
+use label_selector::{matches_label_selectors, push_label_selectors};
+use reservation_status::{
+    get_statuses, matches_statuses, normalize_statuses, push_statuses, validate_statuses,
+};
+
 pub fn validate_range(start: Option<&Timestamp>, end: Option<&Timestamp>) -> Result<(), Error> {
     if start.is_none() || end.is_none() {
         return Err(Error::InvalidTime);
@@ -24,6 +39,110 @@ pub fn validate_range(start: Option<&Timestamp>, end: Option<&Timestamp>) -> Res
     Ok(())
 }
 
+/// tenant id could be empty (the default tenant), otherwise it's up to 64 ascii letters, digits, '-', '_' or '.'
+pub fn validate_tenant_id(tenant_id: &str) -> Result<(), Error> {
+    let valid = tenant_id.len() <= 64
+        && tenant_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if !valid {
+        return Err(Error::InvalidTenantId(tenant_id.to_string()));
+    }
+    Ok(())
+}
+
+/// push the conditions on user and resource, an empty id matches all
+pub(crate) fn push_user_resource<'a>(
+    builder: &mut QueryBuilder<'a, Postgres>,
+    user_id: &'a str,
+    resource_id: &'a str,
+) {
+    if !user_id.is_empty() {
+        builder.push(" AND user_id = ").push_bind(user_id);
+    }
+    if !resource_id.is_empty() {
+        builder.push(" AND resource_id = ").push_bind(resource_id);
+    }
+}
+
+/// push a tstzrange of the window, a missing bound is unbounded
+pub(crate) fn push_time_range(
+    builder: &mut QueryBuilder<'_, Postgres>,
+    start: Option<&Timestamp>,
+    end: Option<&Timestamp>,
+) {
+    builder
+        .push("tstzrange(")
+        .push_bind(start.map(convert_to_utc_time))
+        .push(", ")
+        .push_bind(end.map(convert_to_utc_time))
+        .push(")");
+}
+
+/// check the user and resource in memory, an empty id matches all
+pub(crate) fn matches_user_resource(rsvp: &Reservation, user_id: &str, resource_id: &str) -> bool {
+    (user_id.is_empty() || rsvp.user_id == user_id)
+        && (resource_id.is_empty() || rsvp.resource_id == resource_id)
+}
+
+/// in memory counterpart of tstzrange(start, end): microseconds since epoch, start inclusive,
+/// end exclusive, a missing bound is unbounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TimeRange {
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+impl TimeRange {
+    pub(crate) fn new(start: Option<&Timestamp>, end: Option<&Timestamp>) -> Self {
+        Self {
+            start: start.map(convert_to_micros),
+            end: end.map(convert_to_micros),
+        }
+    }
+
+    pub(crate) fn of(rsvp: &Reservation) -> Self {
+        Self::new(rsvp.start.as_ref(), rsvp.end.as_ref())
+    }
+
+    pub(crate) fn start(&self) -> Option<i64> {
+        self.start
+    }
+
+    pub(crate) fn end(&self) -> Option<i64> {
+        self.end
+    }
+
+    /// same as `&&`
+    pub(crate) fn overlaps(&self, other: &Self) -> bool {
+        let before = |start: Option<i64>, end: Option<i64>| match (start, end) {
+            (Some(start), Some(end)) => start < end,
+            _ => true,
+        };
+        before(self.start, other.end) && before(other.start, self.end)
+    }
+
+    /// same as `@>`
+    pub(crate) fn contains(&self, other: &Self) -> bool {
+        let start_ok = match (self.start, other.start) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(a), Some(b)) => a <= b,
+        };
+        let end_ok = match (self.end, other.end) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(a), Some(b)) => b <= a,
+        };
+        start_ok && end_ok
+    }
+
+    /// same as `@>` with a single point
+    pub(crate) fn contains_point(&self, point: i64) -> bool {
+        self.start.is_none_or(|start| start <= point) && self.end.is_none_or(|end| point < end)
+    }
+}
+
 pub fn get_timespan(start: Option<&Timestamp>, end: Option<&Timestamp>) -> PgRange<DateTime<Utc>> {
     let start = convert_to_utc_time(start.as_ref().unwrap());
     let end = convert_to_utc_time(end.as_ref().unwrap());
@@ -66,6 +185,35 @@ mod tests {
         assert!(validate_range(Some(&start), Some(&end)).is_err());
     }
 
+    #[test]
+    fn validate_tenant_id_should_reject_unsafe_chars() {
+        assert!(validate_tenant_id("").is_ok());
+        assert!(validate_tenant_id("acme-corp_1.eu").is_ok());
+        assert_eq!(
+            validate_tenant_id("acme' OR '1'='1"),
+            Err(Error::InvalidTenantId("acme' OR '1'='1".into()))
+        );
+        assert!(validate_tenant_id(&"a".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn time_range_should_follow_tstzrange_semantics() {
+        let range = |start: Option<i64>, end: Option<i64>| TimeRange { start, end };
+
+        // end is exclusive, so adjacent ranges don't overlap
+        assert!(!range(Some(1), Some(2)).overlaps(&range(Some(2), Some(3))));
+        assert!(range(Some(1), Some(3)).overlaps(&range(Some(2), Some(4))));
+        assert!(range(None, None).overlaps(&range(Some(2), Some(3))));
+        assert!(range(Some(1), None).overlaps(&range(Some(2), Some(3))));
+
+        assert!(range(Some(1), Some(3)).contains(&range(Some(1), Some(3))));
+        assert!(range(None, Some(3)).contains(&range(Some(1), Some(2))));
+        assert!(!range(Some(1), Some(3)).contains(&range(Some(1), None)));
+
+        assert!(range(Some(1), Some(3)).contains_point(1));
+        assert!(!range(Some(1), Some(3)).contains_point(3));
+    }
+
     #[test]
     fn get_timestamp_should_work_for_valid_start_end() {
         let start = Timestamp {
@@ -0,0 +1,294 @@
+use crate::{Error, FilterPager, ReservationFilter};
+use hmac::{Hmac, Mac};
+use prost::Message;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+use super::normalize_statuses;
+
+const VERSION: u8 = 1;
+const FINGERPRINT_LEN: usize = 16;
+const MAC_LEN: usize = 16;
+const FLAG_HAS_KEY: u8 = 1;
+const FLAG_BACKWARD: u8 = 2;
+// version + cursor + flags + key + fingerprint + mac
+const TOKEN_LEN: usize = 1 + 8 + 1 + 8 + FINGERPRINT_LEN + MAC_LEN;
+
+/// position of a page in a filter result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PagePosition {
+    pub cursor: i64,
+    pub cursor_key: Option<i64>,
+    pub backward: bool,
+}
+
+/// signs and verifies the opaque page tokens of ReservationFilter.
+/// A token carries the position (sort key and id) of the cursor reservation and
+/// a fingerprint of the filter it's issued for, so it can't be forged or reused with another filter.
+#[derive(Clone)]
+pub struct PageTokenCodec {
+    key: Vec<u8>,
+}
+
+impl fmt::Debug for PageTokenCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PageTokenCodec").finish_non_exhaustive()
+    }
+}
+
+impl PageTokenCodec {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    pub fn encode(&self, filter: &ReservationFilter, pos: PagePosition) -> String {
+        let mut flags = 0;
+        if pos.cursor_key.is_some() {
+            flags |= FLAG_HAS_KEY;
+        }
+        if pos.backward {
+            flags |= FLAG_BACKWARD;
+        }
+
+        let mut buf = Vec::with_capacity(TOKEN_LEN);
+        buf.push(VERSION);
+        buf.extend_from_slice(&pos.cursor.to_be_bytes());
+        buf.push(flags);
+        buf.extend_from_slice(&pos.cursor_key.unwrap_or_default().to_be_bytes());
+        buf.extend_from_slice(&fingerprint(filter));
+        let mac = self.mac(&buf);
+        buf.extend_from_slice(&mac);
+        base64::encode_config(buf, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// returns the page position if the token is issued by us for the same filter
+    pub fn decode(&self, filter: &ReservationFilter, token: &str) -> Result<PagePosition, Error> {
+        let err = || Error::InvalidPageToken(token.to_string());
+        let buf = base64::decode_config(token, base64::URL_SAFE_NO_PAD).map_err(|_| err())?;
+        if buf.len() != TOKEN_LEN || buf[0] != VERSION {
+            return Err(err());
+        }
+
+        let (data, mac) = buf.split_at(TOKEN_LEN - MAC_LEN);
+        let mut verifier = self.hmac();
+        verifier.update(data);
+        verifier.verify_truncated_left(mac).map_err(|_| err())?;
+
+        if data[18..] != fingerprint(filter) {
+            return Err(err());
+        }
+
+        let flags = data[9];
+        let cursor_key = i64::from_be_bytes(data[10..18].try_into().unwrap());
+        Ok(PagePosition {
+            cursor: i64::from_be_bytes(data[1..9].try_into().unwrap()),
+            cursor_key: (flags & FLAG_HAS_KEY != 0).then_some(cursor_key),
+            backward: flags & FLAG_BACKWARD != 0,
+        })
+    }
+
+    fn hmac(&self) -> Hmac<Sha256> {
+        Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any size")
+    }
+
+    fn mac(&self, data: &[u8]) -> Vec<u8> {
+        let mut mac = self.hmac();
+        mac.update(data);
+        mac.finalize().into_bytes()[..MAC_LEN].to_vec()
+    }
+}
+
+impl ReservationFilter {
+    /// resolve the page token into the cursor. Raw cursors are deprecated and only accepted if allowed
+    pub fn apply_page_token(
+        &mut self,
+        codec: &PageTokenCodec,
+        allow_raw_cursor: bool,
+    ) -> Result<(), Error> {
+        if !self.page_token.is_empty() {
+            let pos = codec.decode(self, &self.page_token)?;
+            self.cursor = Some(pos.cursor);
+            self.cursor_key = pos.cursor_key;
+            self.backward = pos.backward;
+        } else if let (Some(cursor), false) = (self.cursor, allow_raw_cursor) {
+            return Err(Error::InvalidCursor(cursor));
+        }
+        Ok(())
+    }
+}
+
+impl FilterPager {
+    /// sign the prev and next positions of the pager as page tokens for the filter
+    pub fn sign(&mut self, filter: &ReservationFilter, codec: &PageTokenCodec) {
+        if let Some(prev) = self.prev {
+            let pos = PagePosition {
+                cursor: prev,
+                cursor_key: self.prev_key,
+                backward: true,
+            };
+            self.prev_token = codec.encode(filter, pos);
+        }
+        if let Some(next) = self.next {
+            let pos = PagePosition {
+                cursor: next,
+                cursor_key: self.next_key,
+                backward: false,
+            };
+            self.next_token = codec.encode(filter, pos);
+        }
+    }
+}
+
+/// hash of everything deciding the result set of the filter, i.e. all but the position and page size
+fn fingerprint(filter: &ReservationFilter) -> [u8; FINGERPRINT_LEN] {
+    let mut filter = filter.clone();
+    normalize_statuses(filter.status, &mut filter.statuses);
+    filter.status = 0;
+    filter.cursor = None;
+    filter.cursor_key = None;
+    filter.page_token.clear();
+    filter.page_size = 0;
+    filter.with_total = false;
+    filter.backward = false;
+
+    let hash = Sha256::digest(filter.encode_to_vec());
+    hash[..FINGERPRINT_LEN].try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReservationFilterBuilder, ReservationSortBy, ReservationStatus};
+
+    fn codec() -> PageTokenCodec {
+        PageTokenCodec::new("secret")
+    }
+
+    fn pos(cursor: i64, cursor_key: Option<i64>) -> PagePosition {
+        PagePosition {
+            cursor,
+            cursor_key,
+            backward: false,
+        }
+    }
+
+    #[test]
+    fn page_token_should_round_trip() {
+        let filter = ReservationFilterBuilder::default()
+            .user_id("tyr")
+            .sort_by(ReservationSortBy::Start as i32)
+            .build()
+            .unwrap();
+        let token = codec().encode(&filter, pos(42, Some(1_669_878_000_123_456)));
+
+        // page size and position are not part of the filter fingerprint
+        let mut next = ReservationFilterBuilder::default()
+            .user_id("tyr")
+            .sort_by(ReservationSortBy::Start as i32)
+            .page_size(20)
+            .page_token(token)
+            .build()
+            .unwrap();
+        next.apply_page_token(&codec(), false).unwrap();
+        assert_eq!(next.cursor, Some(42));
+        assert_eq!(next.cursor_key, Some(1_669_878_000_123_456));
+    }
+
+    #[test]
+    fn page_token_for_another_filter_should_be_rejected() {
+        let filter = ReservationFilterBuilder::default()
+            .user_id("tyr")
+            .build()
+            .unwrap();
+        let token = codec().encode(&filter, pos(42, None));
+
+        let mut other = ReservationFilterBuilder::default()
+            .user_id("alice")
+            .page_token(token.clone())
+            .build()
+            .unwrap();
+        let err = other.apply_page_token(&codec(), true).unwrap_err();
+        assert_eq!(err, Error::InvalidPageToken(token.clone()));
+
+        // tokens don't cross tenants either
+        let mut other = ReservationFilterBuilder::default()
+            .user_id("tyr")
+            .tenant_id("acme")
+            .page_token(token.clone())
+            .build()
+            .unwrap();
+        assert!(other.apply_page_token(&codec(), true).is_err());
+
+        // but the deprecated status is the same as a single entry of statuses
+        let filter = ReservationFilterBuilder::default()
+            .status(ReservationStatus::Pending as i32)
+            .build()
+            .unwrap();
+        let token = codec().encode(&filter, pos(42, None));
+        let mut other = ReservationFilterBuilder::default()
+            .statuses(vec![ReservationStatus::Pending as i32])
+            .page_token(token)
+            .build()
+            .unwrap();
+        assert!(other.apply_page_token(&codec(), false).is_ok());
+    }
+
+    #[test]
+    fn forged_page_token_should_be_rejected() {
+        let filter = ReservationFilterBuilder::default().build().unwrap();
+        let token = codec().encode(&filter, pos(42, None));
+
+        let other = PageTokenCodec::new("another secret");
+        assert!(other.decode(&filter, &token).is_err());
+
+        let mut buf = base64::decode_config(&token, base64::URL_SAFE_NO_PAD).unwrap();
+        buf[8] = 43;
+        let forged = base64::encode_config(buf, base64::URL_SAFE_NO_PAD);
+        assert!(codec().decode(&filter, &forged).is_err());
+
+        assert!(codec().decode(&filter, "not a token").is_err());
+    }
+
+    #[test]
+    fn raw_cursor_should_only_be_accepted_if_allowed() {
+        let mut filter = ReservationFilterBuilder::default()
+            .cursor(42)
+            .build()
+            .unwrap();
+        assert!(filter.apply_page_token(&codec(), true).is_ok());
+        assert_eq!(
+            filter.apply_page_token(&codec(), false),
+            Err(Error::InvalidCursor(42))
+        );
+    }
+
+    #[test]
+    fn pager_should_be_signed() {
+        let filter = ReservationFilterBuilder::default().build().unwrap();
+        let mut pager = FilterPager {
+            next: Some(10),
+            ..Default::default()
+        };
+        pager.sign(&filter, &codec());
+        assert!(pager.prev_token.is_empty());
+        assert_eq!(
+            codec().decode(&filter, &pager.next_token).unwrap(),
+            pos(10, None)
+        );
+
+        let mut pager = FilterPager {
+            prev: Some(11),
+            prev_key: Some(11_000_000),
+            ..Default::default()
+        };
+        pager.sign(&filter, &codec());
+        let mut prev = ReservationFilter {
+            page_token: pager.prev_token,
+            ..filter
+        };
+        prev.apply_page_token(&codec(), false).unwrap();
+        assert_eq!(prev.cursor, Some(11));
+        assert_eq!(prev.cursor_key, Some(11_000_000));
+        assert!(prev.backward);
+    }
+}
@@ -1,6 +1,9 @@
 use crate::{
-    CancelRequest, ConfirmRequest, FilterRequest, GetRequest, QueryRequest, Reservation,
-    ReservationFilter, ReservationQuery, ReserveRequest, UpdateRequest,
+    AcceptTransferRequest, CancelRequest, CheckRequest, CheckResponse, ConfirmRequest,
+    ExportCalendarRequest, FilterRequest, GetRequest, ListenResponse, QueryRequest, Reservation,
+    ReservationConflict, ReservationFilter, ReservationProblem, ReservationQuery,
+    ReservationSearch, ReservationUpdateType, ReserveRequest, SearchRequest, SuggestRequest,
+    TransferRequest, UpdateRequest,
 };
 
 macro_rules! impl_new {
@@ -23,8 +26,11 @@ macro_rules! impl_new {
 }
 
 impl_new!(ReserveRequest, reservation, Reservation);
+impl_new!(CheckRequest, reservation, Reservation);
 impl_new!(FilterRequest, filter, ReservationFilter);
 impl_new!(QueryRequest, query, ReservationQuery);
+impl_new!(ExportCalendarRequest, query, ReservationQuery);
+impl_new!(SearchRequest, search, ReservationSearch);
 impl_new!(ConfirmRequest);
 impl_new!(GetRequest);
 impl_new!(CancelRequest);
@@ -34,3 +40,74 @@ impl UpdateRequest {
         Self { id, note }
     }
 }
+
+impl TransferRequest {
+    pub fn new(id: i64, user_id: impl Into<String>, require_acceptance: bool) -> Self {
+        Self {
+            id,
+            user_id: user_id.into(),
+            require_acceptance,
+        }
+    }
+}
+
+impl SuggestRequest {
+    pub fn new(rsvp: Reservation, limit: i32, resource_pool: Vec<String>) -> Self {
+        Self {
+            reservation: Some(rsvp),
+            limit,
+            resource_pool,
+        }
+    }
+}
+
+impl ListenResponse {
+    pub fn new(op: ReservationUpdateType, reservation: Reservation) -> Self {
+        Self {
+            op: op as i32,
+            reservation: Some(reservation),
+        }
+    }
+}
+
+impl AcceptTransferRequest {
+    pub fn new(id: i64, user_id: impl Into<String>) -> Self {
+        Self {
+            id,
+            user_id: user_id.into(),
+        }
+    }
+}
+
+impl CheckResponse {
+    /// the problems of the reservation: its validation errors and the reservations conflicting with it
+    pub fn new(rsvp: &Reservation, conflicts: &[Reservation]) -> Self {
+        let mut problems: Vec<ReservationProblem> = rsvp
+            .validation_errors()
+            .into_iter()
+            .map(|e| {
+                let (field, _) = e.violation().unwrap_or_default();
+                ReservationProblem {
+                    reason: e.reason().to_string(),
+                    field: field.to_string(),
+                    description: e.to_string(),
+                    conflict: None,
+                }
+            })
+            .collect();
+        if !conflicts.is_empty() {
+            let conflict = ReservationConflict::new(rsvp, conflicts);
+            problems.extend(conflict.conflicts.into_iter().map(|c| ReservationProblem {
+                reason: "CONFLICT_RESERVATION".to_string(),
+                field: String::new(),
+                description: format!("Conflict with reservation {} of {}", c.id, c.user_id),
+                conflict: Some(c.into()),
+            }));
+        }
+
+        Self {
+            ok: problems.is_empty(),
+            problems,
+        }
+    }
+}
@@ -1,14 +1,19 @@
 use crate::{
-    convert_to_timestamp, pager::Id, Error, Reservation, ReservationStatus, RsvpStatus, Validator,
+    convert_to_micros, convert_to_timestamp, convert_to_utc_time,
+    pager::{Id, SortKey},
+    Error, Reservation, ReservationSortBy, ReservationStatus, ReservationWindow, RsvpStatus,
+    Validator,
 };
 use chrono::{DateTime, FixedOffset, Utc};
+use prost_types::Timestamp;
 use sqlx::{
     postgres::{types::PgRange, PgRow},
+    types::Json,
     FromRow, Row,
 };
-use std::ops::Bound;
+use std::{collections::HashMap, ops::Bound};
 
-use super::{get_timespan, validate_range};
+use super::{get_timespan, validate_labels, validate_range, validate_tenant_id, TimeRange};
 
 impl Reservation {
     pub fn new_pending(
@@ -26,12 +31,57 @@ impl Reservation {
             end: Some(convert_to_timestamp(&end.with_timezone(&Utc))),
             note: note.into(),
             status: ReservationStatus::Pending as i32,
+            pending_user_id: String::new(),
+            tenant_id: String::new(),
+            labels: HashMap::new(),
+            created_at: None,
         }
     }
 
+    pub fn with_label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.insert(key.into(), value.into());
+        self
+    }
+
     pub fn get_timespan(&self) -> PgRange<DateTime<Utc>> {
         get_timespan(self.start.as_ref(), self.end.as_ref())
     }
+
+    /// same resource of the same tenant in an overlapping window, what the exclusion constraint rejects
+    pub fn conflicts_with(&self, other: &Reservation) -> bool {
+        self.tenant_id == other.tenant_id
+            && self.resource_id == other.resource_id
+            && TimeRange::of(self).overlaps(&TimeRange::of(other))
+    }
+
+    /// every validation failure of the reservation, `validate` only reports the first one
+    pub fn validation_errors(&self) -> Vec<Error> {
+        let mut errors = vec![];
+        if self.user_id.is_empty() {
+            errors.push(Error::InvalidUserId(self.user_id.clone()));
+        }
+
+        if self.resource_id.is_empty() {
+            errors.push(Error::InvalidResourceId(self.resource_id.clone()));
+        }
+
+        let checks = [
+            validate_tenant_id(&self.tenant_id),
+            validate_labels(&self.labels),
+            validate_range(self.start.as_ref(), self.end.as_ref()),
+        ];
+        errors.extend(checks.into_iter().filter_map(Result::err));
+        errors
+    }
+
+    pub fn get_window(&self) -> ReservationWindow {
+        let time = |ts: Option<&Timestamp>| convert_to_utc_time(ts.unwrap());
+        ReservationWindow {
+            rid: self.resource_id.clone(),
+            start: time(self.start.as_ref()),
+            end: time(self.end.as_ref()),
+        }
+    }
 }
 
 impl Id for Reservation {
@@ -40,19 +90,24 @@ impl Id for Reservation {
     }
 }
 
-impl Validator for Reservation {
-    fn validate(&self) -> Result<(), Error> {
-        if self.user_id.is_empty() {
-            return Err(Error::InvalidUserId(self.user_id.clone()));
+impl SortKey for Reservation {
+    fn sort_key(&self, sort_by: ReservationSortBy) -> i64 {
+        let micros = |ts: Option<&Timestamp>| ts.map(convert_to_micros).unwrap_or(0);
+        match sort_by {
+            ReservationSortBy::Id => self.id,
+            ReservationSortBy::Start => micros(self.start.as_ref()),
+            ReservationSortBy::End => micros(self.end.as_ref()),
+            ReservationSortBy::CreatedAt => micros(self.created_at.as_ref()),
         }
+    }
+}
 
-        if self.resource_id.is_empty() {
-            return Err(Error::InvalidResourceId(self.resource_id.clone()));
+impl Validator for Reservation {
+    fn validate(&self) -> Result<(), Error> {
+        match self.validation_errors().into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
         }
-
-        validate_range(self.start.as_ref(), self.end.as_ref())?;
-
-        Ok(())
     }
 }
 
@@ -70,6 +125,9 @@ impl FromRow<'_, PgRow> for Reservation {
         let end = range.end.unwrap();
 
         let status: RsvpStatus = row.get("status");
+        let pending_user_id: Option<String> = row.get("pending_user_id");
+        let labels: Json<HashMap<String, String>> = row.get("labels");
+        let created_at: DateTime<Utc> = row.get("created_at");
 
         Ok(Self {
             id,
@@ -79,6 +137,10 @@ impl FromRow<'_, PgRow> for Reservation {
             end: Some(convert_to_timestamp(&end)),
             note: row.get("note"),
             status: ReservationStatus::from(status) as i32,
+            pending_user_id: pending_user_id.unwrap_or_default(),
+            tenant_id: row.get("tenant_id"),
+            labels: labels.0,
+            created_at: Some(convert_to_timestamp(&created_at)),
         })
     }
 }
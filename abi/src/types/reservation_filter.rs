@@ -1,9 +1,18 @@
 use std::collections::VecDeque;
 
 use crate::{
-    pager::{Id, PageInfo, Pager, Paginator},
-    Error, FilterPager, Normalizer, ReservationFilter, ReservationFilterBuilder, ReservationStatus,
-    ToSql, Validator,
+    convert_micros_to_utc_time,
+    pager::{PageInfo, Pager, Paginator, SortKey},
+    Error, FilterPager, Normalizer, Reservation, ReservationFilter, ReservationFilterBuilder,
+    ReservationSortBy, ReservationStatus, ToSql, Validator,
+};
+
+use sqlx::{Postgres, QueryBuilder};
+
+use super::{
+    get_statuses, matches_label_selectors, matches_statuses, matches_user_resource,
+    normalize_statuses, push_label_selectors, push_statuses, push_time_range, push_user_resource,
+    validate_statuses, validate_tenant_id, TimeRange,
 };
 
 impl ReservationFilterBuilder {
@@ -22,13 +31,32 @@ impl Validator for ReservationFilter {
             return Err(Error::InvalidPageSize(self.page_size));
         }
 
+        let sort_by =
+            ReservationSortBy::from_i32(self.sort_by).ok_or(Error::InvalidSortBy(self.sort_by))?;
+
         if let Some(cursor) = self.cursor {
             if cursor < 0 {
                 return Err(Error::InvalidCursor(cursor));
             }
+            // the cursor is (sort_key, id), sort key is required unless sorted by id
+            if sort_by != ReservationSortBy::Id && self.cursor_key.is_none() {
+                return Err(Error::InvalidCursor(cursor));
+            }
         }
 
-        ReservationStatus::from_i32(self.status).ok_or(Error::InvalidStatus(self.status))?;
+        validate_statuses(self.status, &self.statuses)?;
+
+        validate_tenant_id(&self.tenant_id)?;
+
+        for selector in &self.label_selectors {
+            selector.validate()?;
+        }
+
+        if let (Some(start), Some(end)) = (self.start.as_ref(), self.end.as_ref()) {
+            if start.seconds >= end.seconds {
+                return Err(Error::InvalidTime);
+            }
+        }
 
         Ok(())
     }
@@ -36,8 +64,10 @@ impl Validator for ReservationFilter {
 
 impl Normalizer for ReservationFilter {
     fn do_normalize(&mut self) {
-        if self.status == ReservationStatus::Unknown as i32 {
-            self.status = ReservationStatus::Pending as i32;
+        normalize_statuses(self.status, &mut self.statuses);
+        // there's nothing before the first page
+        if self.cursor.is_none() {
+            self.backward = false;
         }
     }
 }
@@ -48,6 +78,7 @@ impl From<Pager> for FilterPager {
             prev: pager.prev,
             next: pager.next,
             total: pager.total,
+            ..Default::default()
         }
     }
 }
@@ -63,76 +94,216 @@ impl From<&FilterPager> for Pager {
 }
 
 impl ReservationFilter {
-    pub fn get_pager<T: Id>(&self, data: &mut VecDeque<T>) -> FilterPager {
+    pub fn get_pager<T: SortKey>(&self, data: &mut VecDeque<T>) -> FilterPager {
         let page_info = self.page_info();
-        let pager = page_info.get_pager(data);
-        pager.into()
+        let mut pager: FilterPager = page_info.get_pager(data).into();
+
+        // after get_pager, the first and last items are the prev and next cursors
+        let sort_by = self.get_sort_by();
+        if sort_by != ReservationSortBy::Id {
+            if pager.prev.is_some() {
+                pager.prev_key = data.front().map(|v| v.sort_key(sort_by));
+            }
+            if pager.next.is_some() {
+                pager.next_key = data.back().map(|v| v.sort_key(sort_by));
+            }
+        }
+        pager
     }
 
     pub fn get_cursor(&self) -> i64 {
         self.cursor.unwrap_or(if self.desc { i64::MAX } else { 0 })
     }
 
-    pub fn get_status(&self) -> ReservationStatus {
-        ReservationStatus::from_i32(self.status).unwrap()
+    /// empty if reservations in all statuses shall be returned
+    pub fn get_statuses(&self) -> Vec<ReservationStatus> {
+        get_statuses(&self.statuses)
+    }
+
+    /// the order of the filter, an unknown one (rejected by validate) is taken as by id
+    pub fn get_sort_by(&self) -> ReservationSortBy {
+        ReservationSortBy::from_i32(self.sort_by).unwrap_or(ReservationSortBy::Id)
     }
 
     pub fn next_page(&self, pager: &FilterPager) -> Option<Self> {
         let page_info = self.page_info();
-        let pager = pager.into();
-        let page_info = page_info.next_page(&pager);
+        let page_info = page_info.next_page(&pager.into());
         page_info.map(|page_info| Self {
             cursor: page_info.cursor,
+            cursor_key: pager.next_key,
+            page_token: String::new(),
             page_size: page_info.page_size,
             desc: page_info.desc,
-            status: self.status,
-            resource_id: self.resource_id.clone(),
-            user_id: self.user_id.clone(),
+            backward: page_info.backward,
+            ..self.clone()
         })
     }
 
+    pub fn prev_page(&self, pager: &FilterPager) -> Option<Self> {
+        let page_info = self.page_info();
+        let page_info = page_info.prev_page(&pager.into());
+        page_info.map(|page_info| Self {
+            cursor: page_info.cursor,
+            cursor_key: pager.prev_key,
+            page_token: String::new(),
+            page_size: page_info.page_size,
+            desc: page_info.desc,
+            backward: page_info.backward,
+            ..self.clone()
+        })
+    }
+
+    /// check a reservation in memory regardless of the page, same as the generated count sql
+    pub fn matches(&self, rsvp: &Reservation) -> bool {
+        let in_window = if self.start.is_some() || self.end.is_some() {
+            TimeRange::of(rsvp).overlaps(&TimeRange::new(self.start.as_ref(), self.end.as_ref()))
+        } else {
+            true
+        };
+
+        rsvp.tenant_id == self.tenant_id
+            && matches_statuses(&self.statuses, rsvp.status)
+            && in_window
+            && matches_user_resource(rsvp, &self.user_id, &self.resource_id)
+            && matches_label_selectors(&rsvp.labels, &self.label_selectors)
+    }
+
+    /// pick the rows of the page in memory, same as the generated sql. The result is ready for `get_pager`
+    pub fn fetch_page<'a>(
+        &self,
+        rsvps: impl IntoIterator<Item = &'a Reservation>,
+    ) -> VecDeque<Reservation> {
+        let sort_by = self.get_sort_by();
+        let key = |rsvp: &Reservation| (rsvp.sort_key(sort_by), rsvp.id);
+        let desc = self.fetch_desc();
+        let cursor = match (sort_by, self.cursor, self.cursor_key) {
+            (ReservationSortBy::Id, _, _) => Some((self.get_cursor(), self.get_cursor())),
+            (_, Some(cursor), Some(cursor_key)) => Some((cursor_key, cursor)),
+            _ => None,
+        };
+
+        let mut rows: Vec<_> = rsvps
+            .into_iter()
+            .filter(|rsvp| self.matches(rsvp))
+            .filter(|rsvp| match cursor {
+                Some(cursor) if desc => key(rsvp) <= cursor,
+                Some(cursor) => key(rsvp) >= cursor,
+                None => true,
+            })
+            .collect();
+        rows.sort_by_key(|rsvp| key(rsvp));
+        if desc {
+            rows.reverse();
+        }
+
+        let limit = self.fetch_limit() as usize;
+        rows.into_iter().take(limit).cloned().collect()
+    }
+
+    /// the order rows are fetched in, going backward it's the rows before the cursor in reversed order
+    pub fn fetch_desc(&self) -> bool {
+        self.desc != (self.backward && self.cursor.is_some())
+    }
+
+    /// how many rows to fetch for the page: one more to know the next page, and the cursor row
+    /// itself unless on the first page
+    pub fn fetch_limit(&self) -> i64 {
+        let middle_plus = if self.cursor.is_none() { 0 } else { 1 };
+        self.page_size + 1 + middle_plus
+    }
+
     fn page_info(&self) -> PageInfo {
         PageInfo {
             cursor: self.cursor,
             page_size: self.page_size,
             desc: self.desc,
+            backward: self.backward,
         }
     }
 }
 
 impl ToSql for ReservationFilter {
-    fn to_sql(&self) -> String {
-        let middle_plus = if self.cursor.is_none() { 0 } else { 1 };
-        let limit = self.page_size + 1 + middle_plus;
+    fn push_sql<'a>(&'a self, builder: &mut QueryBuilder<'a, Postgres>) {
+        let limit = self.fetch_limit();
 
-        let status = self.get_status();
+        builder.push("SELECT * FROM rsvp.reservations WHERE ");
+        self.push_conditions(builder, true);
 
-        let cursor_cond = if self.desc {
-            format!("id <= {}", self.get_cursor())
-        } else {
-            format!("id >= {}", self.get_cursor())
+        let direction = if self.fetch_desc() { "DESC" } else { "ASC" };
+        let order_by = match self.sort_key() {
+            Some(key) => format!("{} {}, id {}", key, direction, direction),
+            None => format!("id {}", direction),
         };
+        builder
+            .push(format!(" ORDER BY {} LIMIT ", order_by))
+            .push_bind(limit);
+    }
+}
 
-        let user_resource_cond = match (self.user_id.is_empty(), self.resource_id.is_empty()) {
-            (true, true) => "TRUE".into(),
-            (true, false) => format!("resource_id = '{}'", self.resource_id),
-            (false, true) => format!("user_id = '{}'", self.user_id),
-            (false, false) => format!(
-                "user_id = '{}' AND resource_id = '{}'",
-                self.user_id, self.resource_id
-            ),
-        };
+impl ReservationFilter {
+    /// count all the reservations matched by the filter, regardless of the page
+    pub fn to_count_sql(&self) -> QueryBuilder<'_, Postgres> {
+        let mut builder = QueryBuilder::new("SELECT COUNT(*) FROM rsvp.reservations WHERE ");
+        self.push_conditions(&mut builder, false);
+        builder
+    }
+
+    fn sort_key(&self) -> Option<&'static str> {
+        match self.get_sort_by() {
+            ReservationSortBy::Id => None,
+            ReservationSortBy::Start => Some("lower(timespan)"),
+            ReservationSortBy::End => Some("upper(timespan)"),
+            ReservationSortBy::CreatedAt => Some("created_at"),
+        }
+    }
+
+    fn push_conditions<'a>(&'a self, builder: &mut QueryBuilder<'a, Postgres>, with_cursor: bool) {
+        builder.push("tenant_id = ").push_bind(&self.tenant_id);
+        push_statuses(builder, &self.statuses);
 
-        let direction = if self.desc { "DESC" } else { "ASC" };
+        if with_cursor {
+            self.push_cursor(builder);
+        }
+
+        if self.start.is_some() || self.end.is_some() {
+            builder.push(" AND timespan && ");
+            push_time_range(builder, self.start.as_ref(), self.end.as_ref());
+        }
 
-        format!("SELECT * FROM rsvp.reservations WHERE status = '{}'::rsvp.reservation_status AND {} AND {} ORDER BY id {} LIMIT {}", status, cursor_cond, user_resource_cond, direction, limit)
+        push_user_resource(builder, &self.user_id, &self.resource_id);
+        push_label_selectors(builder, &self.label_selectors);
+    }
+
+    fn push_cursor(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+        let op = if self.fetch_desc() { "<=" } else { ">=" };
+        match (self.sort_key(), self.cursor, self.cursor_key) {
+            (None, _, _) => {
+                builder
+                    .push(format!(" AND id {} ", op))
+                    .push_bind(self.get_cursor());
+            }
+            (Some(key), Some(cursor), Some(cursor_key)) => {
+                builder
+                    .push(format!(" AND ({}, id) {} (", key, op))
+                    .push_bind(convert_micros_to_utc_time(cursor_key))
+                    .push(", ")
+                    .push_bind(cursor)
+                    .push(")");
+            }
+            // first page
+            _ => {}
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{pager::pager_test_utils::generate_test_ids, ReservationFilterBuilder};
+    use crate::{
+        pager::{pager_test_utils::generate_test_ids, Id},
+        LabelSelector, ReservationFilterBuilder,
+    };
+    use prost_types::Timestamp;
 
     #[test]
     fn filter_should_generate_correct_pager() {}
@@ -144,10 +315,10 @@ mod tests {
             .build()
             .unwrap();
 
-        let sql = filter.to_sql();
+        let sql = filter.to_sql().into_sql();
         assert_eq!(
             sql,
-            "SELECT * FROM rsvp.reservations WHERE status = 'pending'::rsvp.reservation_status AND id >= 0 AND user_id = 'tyr' ORDER BY id ASC LIMIT 11"
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND id >= $2 AND user_id = $3 ORDER BY id ASC LIMIT $4"
         );
 
         let filter = ReservationFilterBuilder::default()
@@ -155,10 +326,10 @@ mod tests {
             .resource_id("test")
             .build()
             .unwrap();
-        let sql = filter.to_sql();
+        let sql = filter.to_sql().into_sql();
         assert_eq!(
             sql,
-            "SELECT * FROM rsvp.reservations WHERE status = 'pending'::rsvp.reservation_status AND id >= 0 AND user_id = 'tyr' AND resource_id = 'test' ORDER BY id ASC LIMIT 11"
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND id >= $2 AND user_id = $3 AND resource_id = $4 ORDER BY id ASC LIMIT $5"
         );
 
         let filter = ReservationFilterBuilder::default()
@@ -166,10 +337,10 @@ mod tests {
             .build()
             .unwrap();
 
-        let sql = filter.to_sql();
+        let sql = filter.to_sql().into_sql();
         assert_eq!(
             sql,
-            "SELECT * FROM rsvp.reservations WHERE status = 'pending'::rsvp.reservation_status AND id <= 9223372036854775807 AND TRUE ORDER BY id DESC LIMIT 11"
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND id <= $2 ORDER BY id DESC LIMIT $3"
         );
 
         let filter = ReservationFilterBuilder::default()
@@ -178,10 +349,10 @@ mod tests {
             .build()
             .unwrap();
 
-        let sql = filter.to_sql();
+        let sql = filter.to_sql().into_sql();
         assert_eq!(
             sql,
-            "SELECT * FROM rsvp.reservations WHERE status = 'pending'::rsvp.reservation_status AND id >= 100 AND user_id = 'tyr' ORDER BY id ASC LIMIT 12"
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND id >= $2 AND user_id = $3 ORDER BY id ASC LIMIT $4"
         );
 
         let filter = ReservationFilterBuilder::default()
@@ -191,10 +362,232 @@ mod tests {
             .build()
             .unwrap();
 
-        let sql = filter.to_sql();
+        let sql = filter.to_sql().into_sql();
+        assert_eq!(
+            sql,
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND id <= $2 AND user_id = $3 ORDER BY id DESC LIMIT $4"
+        );
+    }
+
+    #[test]
+    fn filter_with_statuses_should_generate_correct_sql() {
+        let filter = ReservationFilterBuilder::default()
+            .user_id("tyr")
+            .statuses(vec![
+                ReservationStatus::Confirmed as i32,
+                ReservationStatus::Pending as i32,
+            ])
+            .build()
+            .unwrap();
+        assert_eq!(
+            filter.get_statuses(),
+            vec![ReservationStatus::Pending, ReservationStatus::Confirmed]
+        );
+        assert_eq!(
+            filter.to_sql().sql(),
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND status = ANY($2::rsvp.reservation_status[]) AND id >= $3 AND user_id = $4 ORDER BY id ASC LIMIT $5"
+        );
+
+        // deprecated status still works, and is kept when paging
+        let filter = ReservationFilterBuilder::default()
+            .status(ReservationStatus::Blocked as i32)
+            .build()
+            .unwrap();
+        assert_eq!(
+            filter.to_sql().sql(),
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND status = ANY($2::rsvp.reservation_status[]) AND id >= $3 ORDER BY id ASC LIMIT $4"
+        );
+        let mut data = generate_test_ids(1, 11);
+        let pager = filter.get_pager(&mut data);
+        let filter = filter.next_page(&pager).unwrap();
+        assert_eq!(filter.get_statuses(), vec![ReservationStatus::Blocked]);
+
+        let err = ReservationFilterBuilder::default()
+            .statuses(vec![42])
+            .build()
+            .unwrap_err();
+        assert_eq!(err, Error::InvalidStatus(42));
+    }
+
+    #[test]
+    fn filter_with_window_and_sort_key_should_generate_correct_sql() {
+        let filter = ReservationFilterBuilder::default()
+            .user_id("tyr")
+            .start("2022-12-01T00:00:00-0700".parse::<Timestamp>().unwrap())
+            .sort_by(ReservationSortBy::Start as i32)
+            .page_size(20)
+            .build()
+            .unwrap();
+        assert_eq!(
+            filter.to_sql().sql(),
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND timespan && tstzrange($2, $3) AND user_id = $4 ORDER BY lower(timespan) ASC, id ASC LIMIT $5"
+        );
+
+        let filter = ReservationFilterBuilder::default()
+            .sort_by(ReservationSortBy::CreatedAt as i32)
+            .cursor(42)
+            .cursor_key(1_669_878_000_123_456i64)
+            .desc(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            filter.to_sql().sql(),
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND (created_at, id) <= ($2, $3) ORDER BY created_at DESC, id DESC LIMIT $4"
+        );
+        assert_eq!(
+            convert_micros_to_utc_time(1_669_878_000_123_456).to_rfc3339(),
+            "2022-12-01T07:00:00.123456+00:00"
+        );
+    }
+
+    #[test]
+    fn filter_with_sort_key_should_page_by_sort_key_and_id() {
+        let filter = ReservationFilterBuilder::default()
+            .sort_by(ReservationSortBy::End as i32)
+            .build()
+            .unwrap();
+        let mut data = generate_test_ids(1, 11);
+        let pager = filter.get_pager(&mut data);
+        assert_eq!(pager.next, Some(10));
+        assert_eq!(pager.next_key, Some(10_000_000));
+
+        let filter = filter.next_page(&pager).unwrap();
+        assert_eq!(filter.cursor, Some(10));
+        assert_eq!(filter.cursor_key, Some(10_000_000));
+        assert_eq!(filter.get_sort_by(), ReservationSortBy::End);
+        assert_eq!(
+            filter.to_sql().sql(),
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND (upper(timespan), id) >= ($2, $3) ORDER BY upper(timespan) ASC, id ASC LIMIT $4"
+        );
+
+        let mut data = generate_test_ids(10, 15);
+        let pager = filter.get_pager(&mut data);
+        assert_eq!(pager.prev, Some(11));
+        assert_eq!(pager.prev_key, Some(11_000_000));
+        assert_eq!(pager.next, None);
+        assert_eq!(pager.next_key, None);
+    }
+
+    #[test]
+    fn filter_prev_page_should_reverse_direction() {
+        for desc in [false, true] {
+            let (op, rev_op, dir, rev_dir) = if desc {
+                ("<=", ">=", "DESC", "ASC")
+            } else {
+                (">=", "<=", "ASC", "DESC")
+            };
+            let filter = ReservationFilterBuilder::default()
+                .resource_id("router-1")
+                .desc(desc)
+                .build()
+                .unwrap();
+
+            // first page has no prev page
+            let mut data = generate_test_ids(1, 11);
+            let pager = filter.get_pager(&mut data);
+            assert!(filter.prev_page(&pager).is_none());
+
+            // second page
+            let filter = filter.next_page(&pager).unwrap();
+            assert_eq!(filter.to_sql().sql(), format!("SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND id {} $2 AND resource_id = $3 ORDER BY id {} LIMIT $4", op, dir));
+            let mut data = generate_test_ids(10, 21);
+            let pager = filter.get_pager(&mut data);
+
+            // back to the first page, rows before the cursor are fetched in reversed order
+            let filter = filter.prev_page(&pager).unwrap();
+            assert!(filter.backward);
+            assert_eq!(filter.to_sql().sql(), format!("SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND id {} $2 AND resource_id = $3 ORDER BY id {} LIMIT $4", rev_op, rev_dir));
+
+            let mut data: VecDeque<_> = generate_test_ids(1, 11).into_iter().rev().collect();
+            let pager = filter.get_pager(&mut data);
+            assert_eq!(data.len(), 10);
+            assert_eq!(data.front().map(|v| v.id()), Some(1));
+            assert_eq!(pager.prev, None);
+            assert_eq!(pager.next, Some(10));
+            assert!(filter.prev_page(&pager).is_none());
+
+            // and forward again
+            let filter = filter.next_page(&pager).unwrap();
+            assert!(!filter.backward);
+            assert_eq!(filter.cursor, Some(10));
+        }
+    }
+
+    #[test]
+    fn filter_should_generate_count_sql() {
+        let filter = ReservationFilterBuilder::default()
+            .user_id("tyr")
+            .cursor(100)
+            .with_total(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            filter.to_count_sql().sql(),
+            "SELECT COUNT(*) FROM rsvp.reservations WHERE tenant_id = $1 AND user_id = $2"
+        );
+    }
+
+    #[test]
+    fn filter_with_invalid_sort_cursor_should_be_rejected() {
+        let err = ReservationFilterBuilder::default()
+            .sort_by(ReservationSortBy::Start as i32)
+            .cursor(10)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, Error::InvalidCursor(10));
+
+        let err = ReservationFilterBuilder::default()
+            .sort_by(42)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, Error::InvalidSortBy(42));
+
+        // not validated, e.g. straight from the wire
+        let filter = ReservationFilter {
+            sort_by: 42,
+            ..Default::default()
+        };
+        assert_eq!(filter.get_sort_by(), ReservationSortBy::Id);
+
+        let err = ReservationFilterBuilder::default()
+            .start("2022-12-02T00:00:00-0700".parse::<Timestamp>().unwrap())
+            .end("2022-12-01T00:00:00-0700".parse::<Timestamp>().unwrap())
+            .build()
+            .unwrap_err();
+        assert_eq!(err, Error::InvalidTime);
+    }
+
+    #[test]
+    fn filter_should_be_scoped_to_tenant() {
+        let filter = ReservationFilterBuilder::default()
+            .tenant_id("acme")
+            .resource_id("router-1")
+            .build()
+            .unwrap();
+        let sql = filter.to_sql().into_sql();
+        assert_eq!(
+            sql,
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND id >= $2 AND resource_id = $3 ORDER BY id ASC LIMIT $4"
+        );
+
+        // tenant is kept when paging
+        let mut data = generate_test_ids(1, 11);
+        let pager = filter.get_pager(&mut data);
+        let filter = filter.next_page(&pager).unwrap();
+        assert_eq!(filter.tenant_id, "acme");
+    }
+
+    #[test]
+    fn filter_with_label_selectors_should_generate_correct_sql() {
+        let filter = ReservationFilterBuilder::default()
+            .user_id("tyr")
+            .label_selectors(LabelSelector::parse_all("project=apollo,!archived").unwrap())
+            .build()
+            .unwrap();
+        let sql = filter.to_sql().into_sql();
         assert_eq!(
             sql,
-            "SELECT * FROM rsvp.reservations WHERE status = 'pending'::rsvp.reservation_status AND id <= 10 AND user_id = 'tyr' ORDER BY id DESC LIMIT 12"
+            r#"SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND id >= $2 AND user_id = $3 AND labels @> $4 AND NOT labels ? $5 ORDER BY id ASC LIMIT $6"#
         );
     }
 
@@ -210,10 +603,10 @@ mod tests {
         assert_eq!(pager.next, Some(10));
 
         let filter = filter.next_page(&pager).unwrap();
-        let sql = filter.to_sql();
+        let sql = filter.to_sql().into_sql();
         assert_eq!(
             sql,
-            "SELECT * FROM rsvp.reservations WHERE status = 'pending'::rsvp.reservation_status AND id >= 10 AND resource_id = 'router-1' ORDER BY id ASC LIMIT 12"
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND id >= $2 AND resource_id = $3 ORDER BY id ASC LIMIT $4"
         );
 
         let mut data = generate_test_ids(10, 20);
@@ -221,4 +614,65 @@ mod tests {
         assert_eq!(pager.prev, Some(11));
         assert_eq!(pager.next, None);
     }
+
+    #[test]
+    fn filter_should_fetch_page_in_memory() {
+        // later ids start earlier, so sorting by start reverses the id order
+        let rsvps: Vec<_> = (1..=25)
+            .map(|id| {
+                let start = Timestamp {
+                    seconds: 100 - id,
+                    nanos: 0,
+                };
+                let end = Timestamp {
+                    seconds: 200,
+                    nanos: 0,
+                };
+                Reservation {
+                    id,
+                    user_id: "tyr".into(),
+                    resource_id: format!("router-{}", id % 2),
+                    start: Some(start),
+                    end: Some(end),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        let ids = |data: &VecDeque<Reservation>| data.iter().map(|r| r.id).collect::<Vec<_>>();
+
+        let filter = ReservationFilterBuilder::default()
+            .resource_id("router-1")
+            .build()
+            .unwrap();
+        let mut data = filter.fetch_page(&rsvps);
+        let pager = filter.get_pager(&mut data);
+        assert_eq!(ids(&data), (1..=19).step_by(2).collect::<Vec<_>>());
+        assert_eq!(pager.next, Some(19));
+
+        let filter = filter.next_page(&pager).unwrap();
+        let mut data = filter.fetch_page(&rsvps);
+        let pager = filter.get_pager(&mut data);
+        assert_eq!(ids(&data), vec![21, 23, 25]);
+        assert_eq!(pager.next, None);
+
+        let filter = filter.prev_page(&pager).unwrap();
+        let mut data = filter.fetch_page(&rsvps);
+        filter.get_pager(&mut data);
+        assert_eq!(ids(&data), (1..=19).step_by(2).collect::<Vec<_>>());
+
+        let filter = ReservationFilterBuilder::default()
+            .sort_by(ReservationSortBy::Start as i32)
+            .page_size(20)
+            .build()
+            .unwrap();
+        let mut data = filter.fetch_page(&rsvps);
+        let pager = filter.get_pager(&mut data);
+        assert_eq!(ids(&data), (6..=25).rev().collect::<Vec<_>>());
+        assert_eq!(pager.next_key, Some(94_000_000));
+
+        let filter = filter.next_page(&pager).unwrap();
+        let mut data = filter.fetch_page(&rsvps);
+        filter.get_pager(&mut data);
+        assert_eq!(ids(&data), (1..=5).rev().collect::<Vec<_>>());
+    }
 }
@@ -1,8 +1,14 @@
+use sqlx::{Postgres, QueryBuilder};
+
+use super::{
+    get_statuses, matches_label_selectors, matches_statuses, matches_user_resource,
+    normalize_statuses, push_label_selectors, push_statuses, push_time_range, push_user_resource,
+    validate_statuses, validate_tenant_id, TimeRange,
+};
 use crate::{
-    convert_to_utc_time, Error, Normalizer, ReservationQuery, ReservationQueryBuilder,
-    ReservationStatus, ToSql, Validator,
+    Error, Normalizer, Reservation, ReservationQuery, ReservationQueryBuilder,
+    ReservationQueryMode, ReservationStatus, ToSql, Validator,
 };
-use prost_types::Timestamp;
 
 impl ReservationQueryBuilder {
     pub fn build(&self) -> Result<ReservationQuery, Error> {
@@ -15,14 +21,50 @@ impl ReservationQueryBuilder {
 }
 
 impl ReservationQuery {
-    pub fn get_status(&self) -> ReservationStatus {
-        ReservationStatus::from_i32(self.status).unwrap()
+    /// empty if reservations in all statuses shall be returned
+    pub fn get_statuses(&self) -> Vec<ReservationStatus> {
+        get_statuses(&self.statuses)
+    }
+
+    /// the mode of the query, an unknown one (rejected by validate) is taken as unspecified
+    pub fn get_mode(&self) -> ReservationQueryMode {
+        ReservationQueryMode::from_i32(self.mode).unwrap_or(ReservationQueryMode::Unspecified)
+    }
+
+    /// check a reservation in memory, same as the generated sql
+    pub fn matches(&self, rsvp: &Reservation) -> bool {
+        let range = TimeRange::new(self.start.as_ref(), self.end.as_ref());
+        let timespan = TimeRange::of(rsvp);
+        let in_range = match self.get_mode() {
+            ReservationQueryMode::Overlaps => timespan.overlaps(&range),
+            ReservationQueryMode::Unspecified | ReservationQueryMode::ContainedIn => {
+                range.contains(&timespan)
+            }
+            ReservationQueryMode::Contains => timespan.contains(&range),
+            ReservationQueryMode::StartsWithin => {
+                timespan.start().is_some_and(|s| range.contains_point(s))
+            }
+        };
+
+        rsvp.tenant_id == self.tenant_id
+            && in_range
+            && matches_statuses(&self.statuses, rsvp.status)
+            && matches_user_resource(rsvp, &self.user_id, &self.resource_id)
+            && matches_label_selectors(&rsvp.labels, &self.label_selectors)
     }
 }
 
 impl Validator for ReservationQuery {
     fn validate(&self) -> Result<(), Error> {
-        ReservationStatus::from_i32(self.status).ok_or(Error::InvalidStatus(self.status))?;
+        validate_statuses(self.status, &self.statuses)?;
+
+        ReservationQueryMode::from_i32(self.mode).ok_or(Error::InvalidQueryMode(self.mode))?;
+
+        validate_tenant_id(&self.tenant_id)?;
+
+        for selector in &self.label_selectors {
+            selector.validate()?;
+        }
 
         if let (Some(start), Some(end)) = (self.start.as_ref(), self.end.as_ref()) {
             if start.seconds >= end.seconds {
@@ -36,48 +78,52 @@ impl Validator for ReservationQuery {
 
 impl Normalizer for ReservationQuery {
     fn do_normalize(&mut self) {
-        if self.status == ReservationStatus::Unknown as i32 {
-            self.status = ReservationStatus::Pending as i32;
-        }
+        normalize_statuses(self.status, &mut self.statuses);
     }
 }
 
 impl ToSql for ReservationQuery {
-    fn to_sql(&self) -> String {
-        let status = self.get_status();
+    fn push_sql<'a>(&'a self, builder: &mut QueryBuilder<'a, Postgres>) {
+        builder
+            .push("SELECT * FROM rsvp.reservations WHERE tenant_id = ")
+            .push_bind(&self.tenant_id)
+            .push(" AND ");
 
-        let timespan = format!(
-            "tstzrange('{}', '{}')",
-            get_time_string(self.start.as_ref(), true),
-            get_time_string(self.end.as_ref(), false)
-        );
+        let (start, end) = (self.start.as_ref(), self.end.as_ref());
+        // old clients don't set mode, keep the containment semantics for them
+        match self.get_mode() {
+            ReservationQueryMode::Overlaps => {
+                builder.push("timespan && ");
+                push_time_range(builder, start, end);
+            }
+            ReservationQueryMode::Unspecified | ReservationQueryMode::ContainedIn => {
+                push_time_range(builder, start, end);
+                builder.push(" @> timespan");
+            }
+            ReservationQueryMode::Contains => {
+                builder.push("timespan @> ");
+                push_time_range(builder, start, end);
+            }
+            ReservationQueryMode::StartsWithin => {
+                push_time_range(builder, start, end);
+                builder.push(" @> lower(timespan)");
+            }
+        }
 
-        let condition = match (self.user_id.is_empty(), self.resource_id.is_empty()) {
-            (true, true) => "TRUE".into(),
-            (true, false) => format!("resource_id = '{}'", self.resource_id),
-            (false, true) => format!("user_id = '{}'", self.user_id),
-            (false, false) => format!(
-                "user_id = '{}' AND resource_id = '{}'",
-                self.user_id, self.resource_id
-            ),
-        };
+        push_statuses(builder, &self.statuses);
+        push_user_resource(builder, &self.user_id, &self.resource_id);
+        push_label_selectors(builder, &self.label_selectors);
 
         let direction = if self.desc { "DESC" } else { "ASC" };
-
-        format!("SELECT * FROM rsvp.reservations WHERE {} @> timespan AND status = '{}'::rsvp.reservation_status AND {} ORDER BY lower(timespan) {}", timespan, status, condition, direction)
-    }
-}
-
-fn get_time_string(ts: Option<&Timestamp>, start: bool) -> String {
-    match ts {
-        Some(ts) => convert_to_utc_time(ts).to_rfc3339(),
-        None => (if start { "-infinity" } else { "infinity" }).into(),
+        builder.push(format!(" ORDER BY lower(timespan) {}", direction));
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{LabelOperator, LabelSelector};
+    use prost_types::Timestamp;
 
     #[test]
     fn query_should_generate_valid_sql() {
@@ -86,9 +132,9 @@ mod tests {
             .build()
             .unwrap();
 
-        let sql = query.to_sql();
+        let sql = query.to_sql().into_sql();
 
-        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tstzrange('-infinity', 'infinity') @> timespan AND status = 'pending'::rsvp.reservation_status AND user_id = 'tyr' ORDER BY lower(timespan) ASC");
+        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND timespan && tstzrange($2, $3) AND user_id = $4 ORDER BY lower(timespan) ASC");
 
         let query = ReservationQueryBuilder::default()
             .resource_id("test")
@@ -96,15 +142,172 @@ mod tests {
             .build()
             .unwrap();
 
-        let sql = query.to_sql();
-        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tstzrange('2021-11-01T22:00:00+00:00', 'infinity') @> timespan AND status = 'pending'::rsvp.reservation_status AND resource_id = 'test' ORDER BY lower(timespan) ASC");
+        let sql = query.to_sql().into_sql();
+        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND timespan && tstzrange($2, $3) AND resource_id = $4 ORDER BY lower(timespan) ASC");
 
         let query = ReservationQueryBuilder::default()
             .end("2021-11-01T16:00:00-0700".parse::<Timestamp>().unwrap())
             .build()
             .unwrap();
 
-        let sql = query.to_sql();
-        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tstzrange('-infinity', '2021-11-01T23:00:00+00:00') @> timespan AND status = 'pending'::rsvp.reservation_status AND TRUE ORDER BY lower(timespan) ASC");
+        let sql = query.to_sql().into_sql();
+        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND timespan && tstzrange($2, $3) ORDER BY lower(timespan) ASC");
+
+        let query = ReservationQueryBuilder::default()
+            .tenant_id("acme")
+            .user_id("tyr")
+            .build()
+            .unwrap();
+
+        let sql = query.to_sql().into_sql();
+        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND timespan && tstzrange($2, $3) AND user_id = $4 ORDER BY lower(timespan) ASC");
+    }
+
+    #[test]
+    fn query_with_statuses_should_generate_valid_sql() {
+        let query = ReservationQueryBuilder::default()
+            .user_id("tyr")
+            .status(ReservationStatus::Pending as i32)
+            .statuses(vec![ReservationStatus::Confirmed as i32])
+            .build()
+            .unwrap();
+
+        let sql = query.to_sql().into_sql();
+        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND timespan && tstzrange($2, $3) AND status = ANY($4::rsvp.reservation_status[]) AND user_id = $5 ORDER BY lower(timespan) ASC");
+
+        // unknown means all statuses
+        let query = ReservationQueryBuilder::default()
+            .user_id("tyr")
+            .statuses(vec![
+                ReservationStatus::Unknown as i32,
+                ReservationStatus::Blocked as i32,
+            ])
+            .build()
+            .unwrap();
+        assert!(query.get_statuses().is_empty());
+    }
+
+    #[test]
+    fn query_mode_should_generate_valid_sql() {
+        let builder = ReservationQueryBuilder::default()
+            .resource_id("test")
+            .start("2021-11-01T15:00:00-0700".parse::<Timestamp>().unwrap())
+            .end("2021-11-08T15:00:00-0700".parse::<Timestamp>().unwrap())
+            .to_owned();
+
+        let cases = [
+            (
+                ReservationQueryMode::Overlaps,
+                "timespan && tstzrange($2, $3)",
+            ),
+            (
+                ReservationQueryMode::ContainedIn,
+                "tstzrange($2, $3) @> timespan",
+            ),
+            (
+                ReservationQueryMode::Unspecified,
+                "tstzrange($2, $3) @> timespan",
+            ),
+            (
+                ReservationQueryMode::Contains,
+                "timespan @> tstzrange($2, $3)",
+            ),
+            (
+                ReservationQueryMode::StartsWithin,
+                "tstzrange($2, $3) @> lower(timespan)",
+            ),
+        ];
+
+        for (mode, cond) in cases {
+            let query = builder.clone().mode(mode as i32).build().unwrap();
+            assert_eq!(query.to_sql().sql(), format!("SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND {} AND resource_id = $4 ORDER BY lower(timespan) ASC", cond));
+        }
+
+        let err = builder.clone().mode(42).build().unwrap_err();
+        assert_eq!(err, Error::InvalidQueryMode(42));
+
+        // not validated, e.g. straight from the wire
+        let query = ReservationQuery {
+            mode: 42,
+            ..Default::default()
+        };
+        assert_eq!(query.get_mode(), ReservationQueryMode::Unspecified);
+    }
+
+    #[test]
+    fn query_mode_should_match_in_memory() {
+        let builder = ReservationQueryBuilder::default()
+            .resource_id("test")
+            .start("2021-11-01T15:00:00-0700".parse::<Timestamp>().unwrap())
+            .end("2021-11-08T15:00:00-0700".parse::<Timestamp>().unwrap())
+            .to_owned();
+        // starts within the window, but ends after it
+        let rsvp = Reservation::new_pending(
+            "tyr",
+            "test",
+            "2021-11-07T15:00:00-0700".parse().unwrap(),
+            "2021-11-09T15:00:00-0700".parse().unwrap(),
+            "",
+        );
+
+        let cases = [
+            (ReservationQueryMode::Overlaps, true),
+            (ReservationQueryMode::ContainedIn, false),
+            (ReservationQueryMode::Unspecified, false),
+            (ReservationQueryMode::Contains, false),
+            (ReservationQueryMode::StartsWithin, true),
+        ];
+        for (mode, matched) in cases {
+            let query = builder.clone().mode(mode as i32).build().unwrap();
+            assert_eq!(query.matches(&rsvp), matched, "{:?}", mode);
+        }
+
+        let query = builder.clone().resource_id("other").build().unwrap();
+        assert!(!query.matches(&rsvp));
+        let query = builder.clone().tenant_id("acme").build().unwrap();
+        assert!(!query.matches(&rsvp));
+        let query = builder
+            .clone()
+            .status(ReservationStatus::Confirmed as i32)
+            .build()
+            .unwrap();
+        assert!(!query.matches(&rsvp));
+    }
+
+    #[test]
+    fn query_with_label_selectors_should_generate_valid_sql() {
+        let query = ReservationQueryBuilder::default()
+            .resource_id("test")
+            .label_selectors(vec![
+                LabelSelector::new("cost-center", LabelOperator::In, ["42", "43"]),
+                LabelSelector::new("team", LabelOperator::Exists, Vec::<String>::new()),
+            ])
+            .build()
+            .unwrap();
+
+        let sql = query.to_sql().into_sql();
+        assert_eq!(
+            sql,
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND timespan && tstzrange($2, $3) AND resource_id = $4 AND (labels @> $5 OR labels @> $6) AND labels ? $7 ORDER BY lower(timespan) ASC"
+        );
+
+        let err = ReservationQueryBuilder::default()
+            .label_selectors(vec![LabelSelector::new(
+                "team",
+                LabelOperator::Equals,
+                Vec::<String>::new(),
+            )])
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidLabelSelector(_)));
+    }
+
+    #[test]
+    fn query_with_invalid_tenant_should_be_rejected() {
+        let err = ReservationQueryBuilder::default()
+            .tenant_id("acme' OR TRUE --")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, Error::InvalidTenantId("acme' OR TRUE --".into()));
     }
 }
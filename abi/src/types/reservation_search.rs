@@ -0,0 +1,340 @@
+use crate::{
+    Error, Normalizer, Reservation, ReservationSearch, ReservationSearchBuilder, SearchHit, ToSql,
+    Validator,
+};
+use sqlx::{postgres::PgRow, FromRow, Postgres, QueryBuilder, Row};
+
+use super::{
+    matches_label_selectors, matches_statuses, matches_user_resource, normalize_statuses,
+    push_label_selectors, push_statuses, push_user_resource, validate_statuses, validate_tenant_id,
+};
+
+// a subset of the postgres english stop words, they're never matched
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
+    "no", "not", "of", "on", "or", "so", "such", "that", "the", "their", "then", "there", "these",
+    "they", "this", "to", "was", "will", "with", "i", "me", "my", "we", "you",
+];
+
+impl ReservationSearchBuilder {
+    pub fn build(&self) -> Result<ReservationSearch, Error> {
+        let mut search = self
+            .private_build()
+            .expect("failed to build ReservationSearch");
+        search.normalize()?;
+        Ok(search)
+    }
+}
+
+impl ReservationSearch {
+    /// offset of the next page if there're more hits than page size
+    pub fn next_offset(&self, hits: &mut Vec<SearchHit>) -> Option<i64> {
+        if hits.len() as i64 > self.page_size {
+            hits.truncate(self.page_size as usize);
+            Some(self.offset + self.page_size)
+        } else {
+            None
+        }
+    }
+
+    /// search in memory, hits are ranked and paged like the generated sql (one more hit to know
+    /// the next page). It approximates websearch_to_tsquery: words are matched case insensitively
+    /// without stop words and plural 's', "or" joins alternatives and a leading '-' excludes a word
+    pub fn hits<'a>(&self, rsvps: impl IntoIterator<Item = &'a Reservation>) -> Vec<SearchHit> {
+        let (groups, excluded) = parse_terms(&self.text);
+        if groups.is_empty() {
+            return vec![];
+        }
+
+        let mut hits: Vec<_> = rsvps
+            .into_iter()
+            .filter(|rsvp| {
+                rsvp.tenant_id == self.tenant_id
+                    && matches_statuses(&self.statuses, rsvp.status)
+                    && matches_user_resource(rsvp, &self.user_id, &self.resource_id)
+                    && matches_label_selectors(&rsvp.labels, &self.label_selectors)
+            })
+            .filter_map(|rsvp| {
+                let words: Vec<_> = words(&rsvp.note)
+                    .into_iter()
+                    .map(|(i, w)| (i, w, normalize_word(w)))
+                    .collect();
+                let has = |term: &String| words.iter().any(|(_, _, w)| w.as_ref() == Some(term));
+                if !groups.iter().all(|g| g.iter().any(has)) || excluded.iter().any(has) {
+                    return None;
+                }
+
+                let is_term = |w: &Option<String>| {
+                    w.as_ref()
+                        .is_some_and(|w| groups.iter().flatten().any(|t| t == w))
+                };
+                let matched = words.iter().filter(|(_, _, w)| is_term(w)).count();
+                let total = words.iter().filter(|(_, _, w)| w.is_some()).count();
+
+                let mut snippet = String::new();
+                let mut last = 0;
+                for (i, word, w) in &words {
+                    if is_term(w) {
+                        snippet.push_str(&rsvp.note[last..*i]);
+                        snippet.push_str(&format!("<b>{}</b>", word));
+                        last = i + word.len();
+                    }
+                }
+                snippet.push_str(&rsvp.note[last..]);
+
+                Some(SearchHit {
+                    reservation: Some(rsvp.clone()),
+                    rank: matched as f32 / total as f32,
+                    snippet,
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            let id = |h: &SearchHit| h.reservation.as_ref().map(|r| r.id);
+            b.rank.total_cmp(&a.rank).then_with(|| id(a).cmp(&id(b)))
+        });
+        hits.into_iter()
+            .skip(self.offset as usize)
+            .take(self.page_size as usize + 1)
+            .collect()
+    }
+}
+
+/// words to match, all of the groups shall match, any word of a group is enough
+fn parse_terms(text: &str) -> (Vec<Vec<String>>, Vec<String>) {
+    let mut groups: Vec<Vec<String>> = vec![];
+    let mut excluded = vec![];
+    let mut or = false;
+    for token in text.split_whitespace() {
+        if token.eq_ignore_ascii_case("or") {
+            or = true;
+            continue;
+        }
+        let (exclude, token) = match token.strip_prefix('-') {
+            Some(token) => (true, token),
+            None => (false, token),
+        };
+        for (_, word) in words(token) {
+            let word = match normalize_word(word) {
+                Some(word) => word,
+                None => continue,
+            };
+            match groups.last_mut() {
+                _ if exclude => excluded.push(word),
+                Some(group) if or => group.push(word),
+                _ => groups.push(vec![word]),
+            }
+            or = false;
+        }
+    }
+    (groups, excluded)
+}
+
+/// alphanumeric words with their byte offsets
+fn words(s: &str) -> Vec<(usize, &str)> {
+    let mut words = vec![];
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        if c.is_alphanumeric() {
+            start.get_or_insert(i);
+        } else if let Some(start) = start.take() {
+            words.push((start, &s[start..i]));
+        }
+    }
+    if let Some(start) = start {
+        words.push((start, &s[start..]));
+    }
+    words
+}
+
+/// lowercase word without a plural 's', none for stop words
+fn normalize_word(word: &str) -> Option<String> {
+    let word = word.to_lowercase();
+    if STOP_WORDS.contains(&word.as_str()) {
+        return None;
+    }
+    match word.strip_suffix('s') {
+        Some(stem) if stem.len() > 2 && !stem.ends_with('s') => Some(stem.to_string()),
+        _ => Some(word),
+    }
+}
+
+impl Validator for ReservationSearch {
+    fn validate(&self) -> Result<(), Error> {
+        if self.text.trim().is_empty() {
+            return Err(Error::InvalidSearchText(self.text.clone()));
+        }
+
+        if self.page_size < 10 || self.page_size > 100 {
+            return Err(Error::InvalidPageSize(self.page_size));
+        }
+
+        if self.offset < 0 {
+            return Err(Error::InvalidOffset(self.offset));
+        }
+
+        validate_statuses(self.status, &self.statuses)?;
+
+        validate_tenant_id(&self.tenant_id)?;
+
+        for selector in &self.label_selectors {
+            selector.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Normalizer for ReservationSearch {
+    fn do_normalize(&mut self) {
+        normalize_statuses(self.status, &mut self.statuses);
+    }
+}
+
+impl ToSql for ReservationSearch {
+    fn push_sql<'a>(&'a self, builder: &mut QueryBuilder<'a, Postgres>) {
+        // one more row to know if there's a next page
+        let limit = self.page_size + 1;
+
+        builder
+            .push("SELECT *, ts_rank(note_tsv, query) AS rank, ts_headline('english', coalesce(note, ''), query, 'StartSel=<b>, StopSel=</b>, MaxFragments=2') AS snippet FROM rsvp.reservations, websearch_to_tsquery('english', ")
+            .push_bind(&self.text)
+            .push(") AS query WHERE tenant_id = ")
+            .push_bind(&self.tenant_id)
+            .push(" AND note_tsv @@ query");
+
+        push_statuses(builder, &self.statuses);
+        push_user_resource(builder, &self.user_id, &self.resource_id);
+        push_label_selectors(builder, &self.label_selectors);
+
+        builder
+            .push(" ORDER BY rank DESC, id ASC LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(self.offset);
+    }
+}
+
+impl FromRow<'_, PgRow> for SearchHit {
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            reservation: Some(Reservation::from_row(row)?),
+            rank: row.get("rank"),
+            snippet: row.get("snippet"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReservationStatus;
+
+    #[test]
+    fn search_should_generate_valid_sql() {
+        let search = ReservationSearchBuilder::default()
+            .text("vip upgrade")
+            .user_id("tyr")
+            .build()
+            .unwrap();
+
+        assert_eq!(search.to_sql().sql(), "SELECT *, ts_rank(note_tsv, query) AS rank, ts_headline('english', coalesce(note, ''), query, 'StartSel=<b>, StopSel=</b>, MaxFragments=2') AS snippet FROM rsvp.reservations, websearch_to_tsquery('english', $1) AS query WHERE tenant_id = $2 AND note_tsv @@ query AND user_id = $3 ORDER BY rank DESC, id ASC LIMIT $4 OFFSET $5");
+
+        let search = ReservationSearchBuilder::default()
+            .text("tyr's \"vip upgrade\"")
+            .resource_id("ocean-view-room-713")
+            .status(ReservationStatus::Confirmed as i32)
+            .offset(20)
+            .build()
+            .unwrap();
+
+        assert_eq!(search.to_sql().sql(), "SELECT *, ts_rank(note_tsv, query) AS rank, ts_headline('english', coalesce(note, ''), query, 'StartSel=<b>, StopSel=</b>, MaxFragments=2') AS snippet FROM rsvp.reservations, websearch_to_tsquery('english', $1) AS query WHERE tenant_id = $2 AND note_tsv @@ query AND status = ANY($3::rsvp.reservation_status[]) AND resource_id = $4 ORDER BY rank DESC, id ASC LIMIT $5 OFFSET $6");
+    }
+
+    #[test]
+    fn invalid_search_should_be_rejected() {
+        let err = ReservationSearchBuilder::default()
+            .text("  ")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, Error::InvalidSearchText("  ".into()));
+
+        let err = ReservationSearchBuilder::default()
+            .text("vip")
+            .offset(-1)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, Error::InvalidOffset(-1));
+    }
+
+    #[test]
+    fn next_offset_should_work() {
+        let search = ReservationSearchBuilder::default()
+            .text("vip")
+            .offset(10)
+            .build()
+            .unwrap();
+
+        let mut hits = vec![SearchHit::default(); 11];
+        assert_eq!(search.next_offset(&mut hits), Some(20));
+        assert_eq!(hits.len(), 10);
+
+        let mut hits = vec![SearchHit::default(); 3];
+        assert_eq!(search.next_offset(&mut hits), None);
+        assert_eq!(hits.len(), 3);
+    }
+
+    #[test]
+    fn search_should_rank_and_highlight_in_memory() {
+        let note = |id, note: &str| Reservation {
+            id,
+            note: note.into(),
+            ..Default::default()
+        };
+        let rsvps = vec![
+            note(1, "Please help to upgrade to executive room if possible."),
+            note(2, "I need to book this for xyz project."),
+            note(
+                3,
+                "VIP guest, upgrade to the suite. Upgrade is already paid.",
+            ),
+        ];
+
+        let search = ReservationSearchBuilder::default()
+            .text("upgrade rooms")
+            .build()
+            .unwrap();
+        let hits = search.hits(&rsvps);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            hits[0].snippet,
+            "Please help to <b>upgrade</b> to executive <b>room</b> if possible."
+        );
+
+        let search = ReservationSearchBuilder::default()
+            .text("upgrade")
+            .build()
+            .unwrap();
+        let ids: Vec<_> = search
+            .hits(&rsvps)
+            .iter()
+            .map(|h| h.reservation.as_ref().unwrap().id)
+            .collect();
+        assert_eq!(ids, vec![3, 1]);
+
+        let search = ReservationSearchBuilder::default()
+            .text("suite or project -vip")
+            .build()
+            .unwrap();
+        let hits = search.hits(&rsvps);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].reservation.as_ref().unwrap().id, 2);
+
+        let search = ReservationSearchBuilder::default()
+            .text("to the")
+            .build()
+            .unwrap();
+        assert!(search.hits(&rsvps).is_empty());
+    }
+}
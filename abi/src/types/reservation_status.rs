@@ -1,5 +1,6 @@
-use crate::{ReservationStatus, RsvpStatus};
-use std::fmt;
+use crate::{Error, ReservationStatus, RsvpStatus};
+use sqlx::{Postgres, QueryBuilder};
+use std::{fmt, str::FromStr};
 
 impl From<RsvpStatus> for ReservationStatus {
     fn from(status: RsvpStatus) -> Self {
@@ -22,3 +23,123 @@ impl fmt::Display for ReservationStatus {
         }
     }
 }
+
+impl FromStr for ReservationStatus {
+    type Err = Error;
+
+    /// the inverse of Display, unknown is not a status a reservation can be in
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "pending" => Ok(ReservationStatus::Pending),
+            "confirmed" => Ok(ReservationStatus::Confirmed),
+            "blocked" => Ok(ReservationStatus::Blocked),
+            _ => Err(Error::InvalidStatus(ReservationStatus::Unknown as i32)),
+        }
+    }
+}
+
+/// both the deprecated single status and the status list shall be valid
+pub(crate) fn validate_statuses(status: i32, statuses: &[i32]) -> Result<(), Error> {
+    for s in std::iter::once(&status).chain(statuses) {
+        ReservationStatus::from_i32(*s).ok_or(Error::InvalidStatus(*s))?;
+    }
+    Ok(())
+}
+
+/// fold the deprecated single status into the list. An empty list means all statuses
+pub(crate) fn normalize_statuses(status: i32, statuses: &mut Vec<i32>) {
+    if status != ReservationStatus::Unknown as i32 {
+        statuses.push(status);
+    }
+    if statuses.contains(&(ReservationStatus::Unknown as i32)) {
+        statuses.clear();
+    }
+    statuses.sort_unstable();
+    statuses.dedup();
+}
+
+pub(crate) fn get_statuses(statuses: &[i32]) -> Vec<ReservationStatus> {
+    statuses
+        .iter()
+        .filter_map(|s| ReservationStatus::from_i32(*s))
+        .collect()
+}
+
+/// check the status in memory, an empty list matches all
+pub(crate) fn matches_statuses(statuses: &[i32], status: i32) -> bool {
+    statuses.is_empty() || statuses.contains(&status)
+}
+
+/// push the condition on statuses, nothing if reservations in all statuses shall be returned
+pub(crate) fn push_statuses(builder: &mut QueryBuilder<'_, Postgres>, statuses: &[i32]) {
+    if statuses.is_empty() {
+        return;
+    }
+    let statuses: Vec<_> = get_statuses(statuses)
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    builder
+        .push(" AND status = ANY(")
+        .push_bind(statuses)
+        .push("::rsvp.reservation_status[])");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_statuses_should_work() {
+        let mut statuses = vec![ReservationStatus::Confirmed as i32];
+        normalize_statuses(ReservationStatus::Pending as i32, &mut statuses);
+        assert_eq!(
+            statuses,
+            vec![
+                ReservationStatus::Pending as i32,
+                ReservationStatus::Confirmed as i32
+            ]
+        );
+
+        // unknown means all
+        let mut statuses = vec![];
+        normalize_statuses(ReservationStatus::Unknown as i32, &mut statuses);
+        assert!(statuses.is_empty());
+
+        let mut statuses = vec![
+            ReservationStatus::Blocked as i32,
+            ReservationStatus::Unknown as i32,
+        ];
+        normalize_statuses(ReservationStatus::Unknown as i32, &mut statuses);
+        assert!(statuses.is_empty());
+    }
+
+    #[test]
+    fn push_statuses_should_work() {
+        let mut builder = QueryBuilder::new("");
+        push_statuses(&mut builder, &[]);
+        assert_eq!(builder.sql(), "");
+
+        push_statuses(
+            &mut builder,
+            &[
+                ReservationStatus::Pending as i32,
+                ReservationStatus::Confirmed as i32,
+            ],
+        );
+        assert_eq!(
+            builder.sql(),
+            " AND status = ANY($1::rsvp.reservation_status[])"
+        );
+    }
+
+    #[test]
+    fn validate_statuses_should_reject_unknown_values() {
+        assert!(validate_statuses(0, &[1, 2, 3]).is_ok());
+        assert_eq!(
+            validate_statuses(0, &[1, 42]),
+            Err(Error::InvalidStatus(42))
+        );
+        assert_eq!(validate_statuses(42, &[]), Err(Error::InvalidStatus(42)));
+    }
+}
@@ -14,3 +14,18 @@ pub fn convert_to_timestamp(dt: &DateTime<Utc>) -> Timestamp {
         nanos: dt.timestamp_subsec_nanos() as _,
     }
 }
+
+/// microseconds since epoch, same precision as postgres timestamptz
+pub fn convert_to_micros(ts: &Timestamp) -> i64 {
+    ts.seconds * 1_000_000 + ts.nanos as i64 / 1_000
+}
+
+pub fn convert_micros_to_utc_time(micros: i64) -> DateTime<Utc> {
+    DateTime::<Utc>::from_utc(
+        NaiveDateTime::from_timestamp(
+            micros.div_euclid(1_000_000),
+            (micros.rem_euclid(1_000_000) * 1_000) as _,
+        ),
+        Utc,
+    )
+}
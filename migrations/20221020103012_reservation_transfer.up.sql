@@ -0,0 +1,28 @@
+-- a reservation could be handed over to another user, optionally waiting for the new owner to accept it
+ALTER TYPE rsvp.reservation_update_type ADD VALUE 'transfer';
+ALTER TABLE rsvp.reservations ADD COLUMN pending_user_id VARCHAR(64);
+
+-- trigger for add/update/delete a reservation, ownership changes are recorded as 'transfer'
+CREATE OR REPLACE FUNCTION rsvp.reservations_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        -- update reservation_changes
+        INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op) VALUES (NEW.id, null, to_jsonb(NEW), 'create');
+    ELSIF TG_OP = 'UPDATE' THEN
+        -- if status changed, update reservation_changes
+        IF OLD.status <> NEW.status THEN
+            INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op) VALUES (NEW.id, to_jsonb(OLD), to_jsonb(NEW), 'update');
+        END IF;
+        -- if owner changed or a transfer is requested, old and new contain both owners
+        IF OLD.user_id <> NEW.user_id OR OLD.pending_user_id IS DISTINCT FROM NEW.pending_user_id THEN
+            INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op) VALUES (NEW.id, to_jsonb(OLD), to_jsonb(NEW), 'transfer');
+        END IF;
+    ELSIF TG_OP = 'DELETE' THEN
+        -- update reservation_changes
+        INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op) VALUES (OLD.id, to_jsonb(OLD), null, 'delete');
+    END IF;
+    -- notify a channel called reservation_update
+    NOTIFY reservation_update;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,9 @@
+DROP INDEX rsvp.reservations_tenant_id_resource_id_idx;
+DROP INDEX rsvp.reservations_tenant_id_user_id_idx;
+CREATE INDEX reservations_resource_id_idx ON rsvp.reservations (resource_id);
+CREATE INDEX reservations_user_id_idx ON rsvp.reservations (user_id);
+
+ALTER TABLE rsvp.reservations DROP CONSTRAINT reservations_conflict;
+ALTER TABLE rsvp.reservations ADD CONSTRAINT reservations_conflict EXCLUDE USING gist (resource_id WITH =, timespan WITH &&);
+
+ALTER TABLE rsvp.reservations DROP COLUMN tenant_id;
@@ -0,0 +1,10 @@
+-- tenant isolates resource_id and user_id spaces of different business units, '' is the default tenant
+ALTER TABLE rsvp.reservations ADD COLUMN tenant_id VARCHAR(64) NOT NULL DEFAULT '';
+
+ALTER TABLE rsvp.reservations DROP CONSTRAINT reservations_conflict;
+ALTER TABLE rsvp.reservations ADD CONSTRAINT reservations_conflict EXCLUDE USING gist (tenant_id WITH =, resource_id WITH =, timespan WITH &&);
+
+DROP INDEX rsvp.reservations_resource_id_idx;
+DROP INDEX rsvp.reservations_user_id_idx;
+CREATE INDEX reservations_tenant_id_resource_id_idx ON rsvp.reservations (tenant_id, resource_id);
+CREATE INDEX reservations_tenant_id_user_id_idx ON rsvp.reservations (tenant_id, user_id);
@@ -0,0 +1,2 @@
+DROP INDEX rsvp.reservations_labels_idx;
+ALTER TABLE rsvp.reservations DROP COLUMN labels;
@@ -0,0 +1,3 @@
+-- arbitrary labels (e.g. project code, cost center), GIN index supports @> and ? for label selectors
+ALTER TABLE rsvp.reservations ADD COLUMN labels JSONB NOT NULL DEFAULT '{}';
+CREATE INDEX reservations_labels_idx ON rsvp.reservations USING gin (labels);
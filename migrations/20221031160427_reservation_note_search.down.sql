@@ -0,0 +1,2 @@
+DROP INDEX rsvp.reservations_note_tsv_idx;
+ALTER TABLE rsvp.reservations DROP COLUMN note_tsv;
@@ -0,0 +1,3 @@
+-- full text search over notes
+ALTER TABLE rsvp.reservations ADD COLUMN note_tsv TSVECTOR GENERATED ALWAYS AS (to_tsvector('english', coalesce(note, ''))) STORED;
+CREATE INDEX reservations_note_tsv_idx ON rsvp.reservations USING gin (note_tsv);
@@ -0,0 +1,5 @@
+DROP INDEX rsvp.reservations_tenant_created_at_idx;
+DROP INDEX rsvp.reservations_tenant_end_idx;
+DROP INDEX rsvp.reservations_tenant_start_idx;
+
+ALTER TABLE rsvp.reservations DROP COLUMN created_at;
@@ -0,0 +1,7 @@
+-- existing reservations get the time of the migration as created_at
+ALTER TABLE rsvp.reservations ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- keyset pagination by (sort_key, id)
+CREATE INDEX reservations_tenant_start_idx ON rsvp.reservations (tenant_id, lower(timespan), id);
+CREATE INDEX reservations_tenant_end_idx ON rsvp.reservations (tenant_id, upper(timespan), id);
+CREATE INDEX reservations_tenant_created_at_idx ON rsvp.reservations (tenant_id, created_at, id);
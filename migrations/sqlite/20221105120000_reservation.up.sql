@@ -0,0 +1,19 @@
+-- sqlite has no exclusion constraints, overlaps are rejected by the manager inside the insert transaction
+CREATE TABLE reservations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tenant_id TEXT NOT NULL DEFAULT '',
+    user_id TEXT NOT NULL,
+    pending_user_id TEXT,
+    status INTEGER NOT NULL,
+
+    resource_id TEXT NOT NULL,
+    -- microseconds since epoch, start is inclusive and end is exclusive like tstzrange
+    start_at INTEGER NOT NULL,
+    end_at INTEGER NOT NULL,
+
+    note TEXT NOT NULL DEFAULT '',
+    labels TEXT NOT NULL DEFAULT '{}',
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX reservations_tenant_id_resource_id_idx ON reservations (tenant_id, resource_id, start_at);
+CREATE INDEX reservations_tenant_id_user_id_idx ON reservations (tenant_id, user_id);
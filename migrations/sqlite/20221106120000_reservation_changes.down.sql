@@ -0,0 +1,5 @@
+DROP TRIGGER reservations_delete_trigger;
+DROP TRIGGER reservations_transfer_trigger;
+DROP TRIGGER reservations_status_trigger;
+DROP TRIGGER reservations_insert_trigger;
+DROP TABLE reservation_changes;
@@ -0,0 +1,47 @@
+-- reservation change queue, sqlite has no notifications so listeners poll it
+CREATE TABLE reservation_changes (
+    change_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- same values as abi::ReservationUpdateType
+    op INTEGER NOT NULL,
+
+    -- snapshot of the reservation, the new one for create/update/transfer and the old one for delete
+    id INTEGER NOT NULL,
+    tenant_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    pending_user_id TEXT,
+    status INTEGER NOT NULL,
+    resource_id TEXT NOT NULL,
+    start_at INTEGER NOT NULL,
+    end_at INTEGER NOT NULL,
+    note TEXT NOT NULL,
+    labels TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX reservation_changes_tenant_id_idx ON reservation_changes (tenant_id, change_id);
+
+CREATE TRIGGER reservations_insert_trigger AFTER INSERT ON reservations
+BEGIN
+    INSERT INTO reservation_changes (op, id, tenant_id, user_id, pending_user_id, status, resource_id, start_at, end_at, note, labels, created_at)
+    VALUES (1, NEW.id, NEW.tenant_id, NEW.user_id, NEW.pending_user_id, NEW.status, NEW.resource_id, NEW.start_at, NEW.end_at, NEW.note, NEW.labels, NEW.created_at);
+END;
+
+CREATE TRIGGER reservations_status_trigger AFTER UPDATE OF status ON reservations
+WHEN OLD.status <> NEW.status
+BEGIN
+    INSERT INTO reservation_changes (op, id, tenant_id, user_id, pending_user_id, status, resource_id, start_at, end_at, note, labels, created_at)
+    VALUES (2, NEW.id, NEW.tenant_id, NEW.user_id, NEW.pending_user_id, NEW.status, NEW.resource_id, NEW.start_at, NEW.end_at, NEW.note, NEW.labels, NEW.created_at);
+END;
+
+-- owner changed or a transfer is requested
+CREATE TRIGGER reservations_transfer_trigger AFTER UPDATE OF user_id, pending_user_id ON reservations
+WHEN OLD.user_id <> NEW.user_id OR OLD.pending_user_id IS NOT NEW.pending_user_id
+BEGIN
+    INSERT INTO reservation_changes (op, id, tenant_id, user_id, pending_user_id, status, resource_id, start_at, end_at, note, labels, created_at)
+    VALUES (4, NEW.id, NEW.tenant_id, NEW.user_id, NEW.pending_user_id, NEW.status, NEW.resource_id, NEW.start_at, NEW.end_at, NEW.note, NEW.labels, NEW.created_at);
+END;
+
+CREATE TRIGGER reservations_delete_trigger AFTER DELETE ON reservations
+BEGIN
+    INSERT INTO reservation_changes (op, id, tenant_id, user_id, pending_user_id, status, resource_id, start_at, end_at, note, labels, created_at)
+    VALUES (3, OLD.id, OLD.tenant_id, OLD.user_id, OLD.pending_user_id, OLD.status, OLD.resource_id, OLD.start_at, OLD.end_at, OLD.note, OLD.labels, OLD.created_at);
+END;
@@ -0,0 +1,583 @@
+use crate::{check_owner, EmbeddedReservationManager, Rsvp};
+use abi::{
+    convert_to_micros, convert_to_timestamp, FilterPager, Normalizer, ReservationConflict,
+    ReservationId, ReservationUpdateType, UserId, Validator,
+};
+use async_trait::async_trait;
+use chrono::{DurationRound, Utc};
+use prost::Message;
+use redb::{backends::InMemoryBackend, Database, ReadableTable, TableDefinition, WriteTransaction};
+use std::{fmt::Display, path::Path, sync::Arc};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// id -> encoded reservation
+const RESERVATIONS: TableDefinition<i64, &[u8]> = TableDefinition::new("reservations");
+/// (tenant_id, resource_id, start, id) -> end. Reservations of a resource never overlap, so the ones
+/// conflicting with a new reservation are the last ones starting before its end
+const RESOURCE_INDEX: TableDefinition<(&str, &str, i64, i64), i64> =
+    TableDefinition::new("resource_index");
+/// change id -> encoded ListenResponse, like rsvp.reservation_changes
+const CHANGES: TableDefinition<i64, &[u8]> = TableDefinition::new("reservation_changes");
+/// table name -> last id
+const SEQUENCES: TableDefinition<&str, i64> = TableDefinition::new("sequences");
+
+#[async_trait]
+impl Rsvp for EmbeddedReservationManager {
+    fn with_tenant(&self, tenant_id: abi::TenantId) -> Self {
+        Self {
+            db: self.db.clone(),
+            changed: self.changed.clone(),
+            tenant_id,
+        }
+    }
+
+    async fn reserve(&self, mut rsvp: abi::Reservation) -> Result<abi::Reservation, abi::Error> {
+        rsvp.tenant_id = self.tenant_id.clone();
+        rsvp.validate()?;
+
+        let status = abi::ReservationStatus::from_i32(rsvp.status)
+            .unwrap_or(abi::ReservationStatus::Pending);
+        rsvp.status = status as i32;
+        // same precision as timestamptz
+        let now = Utc::now()
+            .duration_trunc(chrono::Duration::microseconds(1))
+            .unwrap();
+        rsvp.created_at = Some(convert_to_timestamp(&now));
+        rsvp.pending_user_id = String::new();
+
+        let rsvp = self
+            .run(move |manager| {
+                let mut rsvp = rsvp;
+                let start = convert_to_micros(rsvp.start.as_ref().unwrap());
+                let end = convert_to_micros(rsvp.end.as_ref().unwrap());
+                let tenant_id = rsvp.tenant_id.clone();
+                let resource_id = rsvp.resource_id.clone();
+                let (tenant_id, resource_id) = (tenant_id.as_str(), resource_id.as_str());
+
+                let txn = manager.db.begin_write().storage()?;
+                {
+                    let mut index = txn.open_table(RESOURCE_INDEX).storage()?;
+                    let conflicts = {
+                        let table = txn.open_table(RESERVATIONS).storage()?;
+                        conflicts(&index, &table, &rsvp)?
+                    };
+                    if !conflicts.is_empty() {
+                        let conflict = ReservationConflict::new(&rsvp, &conflicts);
+                        return Err(abi::Error::ConflictReservation(conflict));
+                    }
+
+                    rsvp.id = next_id(&txn, "reservations")?;
+                    index
+                        .insert((tenant_id, resource_id, start, rsvp.id), end)
+                        .storage()?;
+                }
+                write_reservation(&txn, &rsvp)?;
+                record(&txn, ReservationUpdateType::Create, &rsvp)?;
+                txn.commit().storage()?;
+                Ok(rsvp)
+            })
+            .await?;
+
+        self.changed.notify_waiters();
+        Ok(rsvp)
+    }
+
+    async fn check(&self, mut rsvp: abi::Reservation) -> Result<abi::CheckResponse, abi::Error> {
+        rsvp.tenant_id = self.tenant_id.clone();
+        if rsvp.validate().is_err() {
+            return Ok(abi::CheckResponse::new(&rsvp, &[]));
+        }
+
+        self.run(move |manager| {
+            let txn = manager.db.begin_read().storage()?;
+            let index = txn.open_table(RESOURCE_INDEX).storage()?;
+            let table = txn.open_table(RESERVATIONS).storage()?;
+            let conflicts = conflicts(&index, &table, &rsvp)?;
+            Ok(abi::CheckResponse::new(&rsvp, &conflicts))
+        })
+        .await
+    }
+
+    async fn change_status(
+        &self,
+        id: ReservationId,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        // if current status is pending, change it to confirmed, otherwise do nothing
+        id.validate()?;
+        self.update(id, Some(ReservationUpdateType::Update), owner, |rsvp| {
+            if rsvp.status != abi::ReservationStatus::Pending as i32 {
+                return Err(abi::Error::NotFound);
+            }
+            rsvp.status = abi::ReservationStatus::Confirmed as i32;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_note(
+        &self,
+        id: ReservationId,
+        note: String,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        self.update(id, None, owner, |rsvp| {
+            rsvp.note = note;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn transfer(
+        &self,
+        id: ReservationId,
+        user_id: UserId,
+        require_acceptance: bool,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        if user_id.is_empty() {
+            return Err(abi::Error::InvalidUserId(user_id));
+        }
+
+        self.update(
+            id,
+            Some(ReservationUpdateType::Transfer),
+            owner,
+            move |rsvp| {
+                if rsvp.user_id == user_id {
+                    return Err(abi::Error::InvalidUserId(user_id));
+                }
+                if require_acceptance {
+                    rsvp.pending_user_id = user_id;
+                } else {
+                    rsvp.user_id = user_id;
+                    rsvp.pending_user_id = String::new();
+                }
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    async fn accept_transfer(
+        &self,
+        id: ReservationId,
+        user_id: UserId,
+    ) -> Result<abi::Reservation, abi::Error> {
+        // if the reservation is pending transfer to the user, change the owner, otherwise not found
+        id.validate()?;
+        self.update(
+            id,
+            Some(ReservationUpdateType::Transfer),
+            None,
+            move |rsvp| {
+                if rsvp.pending_user_id.is_empty() || rsvp.pending_user_id != user_id {
+                    return Err(abi::Error::NotFound);
+                }
+                rsvp.user_id = std::mem::take(&mut rsvp.pending_user_id);
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    async fn get(&self, id: ReservationId) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        self.run(move |manager| {
+            let txn = manager.db.begin_read().storage()?;
+            let table = txn.open_table(RESERVATIONS).storage()?;
+            read_reservation(&table, id)?
+                .filter(|rsvp| rsvp.tenant_id == manager.tenant_id)
+                .ok_or(abi::Error::NotFound)
+        })
+        .await
+    }
+
+    async fn delete(
+        &self,
+        id: ReservationId,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        let rsvp = self
+            .run(move |manager| {
+                let txn = manager.db.begin_write().storage()?;
+                let rsvp = {
+                    let mut table = txn.open_table(RESERVATIONS).storage()?;
+                    let rsvp = read_reservation(&table, id)?
+                        .filter(|rsvp| rsvp.tenant_id == manager.tenant_id)
+                        .ok_or(abi::Error::NotFound)?;
+                    check_owner(&rsvp, owner.as_deref())?;
+                    table.remove(id).storage()?;
+                    rsvp
+                };
+                {
+                    let mut index = txn.open_table(RESOURCE_INDEX).storage()?;
+                    let start = convert_to_micros(rsvp.start.as_ref().unwrap());
+                    index
+                        .remove((
+                            rsvp.tenant_id.as_str(),
+                            rsvp.resource_id.as_str(),
+                            start,
+                            id,
+                        ))
+                        .storage()?;
+                }
+                record(&txn, ReservationUpdateType::Delete, &rsvp)?;
+                txn.commit().storage()?;
+                Ok(rsvp)
+            })
+            .await?;
+
+        self.changed.notify_waiters();
+        Ok(rsvp)
+    }
+
+    async fn query(
+        &self,
+        mut query: abi::ReservationQuery,
+    ) -> mpsc::Receiver<Result<abi::Reservation, abi::Error>> {
+        let (tx, rx) = mpsc::channel(128);
+        query.tenant_id = self.tenant_id.clone();
+
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let rsvps = match query.normalize() {
+                Ok(_) => manager.reservations().await,
+                Err(e) => Err(e),
+            };
+            match rsvps {
+                Ok(rsvps) => {
+                    let mut rsvps: Vec<_> = rsvps
+                        .into_iter()
+                        .filter(|rsvp| query.matches(rsvp))
+                        .collect();
+                    rsvps.sort_by_key(|rsvp| {
+                        (rsvp.start.as_ref().map(abi::convert_to_micros), rsvp.id)
+                    });
+                    if query.desc {
+                        rsvps.reverse();
+                    }
+                    for rsvp in rsvps {
+                        if tx.send(Ok(rsvp)).await.is_err() {
+                            // rx is dropped, so client disconnected
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                }
+            }
+        });
+
+        rx
+    }
+
+    async fn filter(
+        &self,
+        mut filter: abi::ReservationFilter,
+    ) -> Result<(FilterPager, Vec<abi::Reservation>), abi::Error> {
+        filter.tenant_id = self.tenant_id.clone();
+        filter.normalize()?;
+
+        let rsvps = self.reservations().await?;
+        let mut page = filter.fetch_page(&rsvps);
+        let mut pager = filter.get_pager(&mut page);
+        if filter.with_total {
+            let total = rsvps.iter().filter(|rsvp| filter.matches(rsvp)).count();
+            pager.total = Some(total as i64);
+        }
+        Ok((pager, page.into_iter().collect()))
+    }
+
+    async fn search(
+        &self,
+        mut search: abi::ReservationSearch,
+    ) -> Result<abi::SearchResponse, abi::Error> {
+        search.tenant_id = self.tenant_id.clone();
+        search.normalize()?;
+
+        let rsvps = self.reservations().await?;
+        let mut hits = search.hits(&rsvps);
+        let next_offset = search.next_offset(&mut hits);
+        Ok(abi::SearchResponse { hits, next_offset })
+    }
+
+    async fn listen(&self) -> mpsc::Receiver<Result<abi::ListenResponse, abi::Error>> {
+        let (tx, rx) = mpsc::channel(128);
+
+        // find out where the changes start before returning, so the changes made right after are not missed
+        let last_id = self
+            .run(|manager| {
+                let txn = manager.db.begin_read().storage()?;
+                let table = txn.open_table(SEQUENCES).storage()?;
+                let last_id = table.get("reservation_changes").storage()?;
+                Ok(last_id.map(|id| id.value()).unwrap_or_default())
+            })
+            .await;
+        let mut last_id = match last_id {
+            Ok(last_id) => last_id,
+            Err(e) => {
+                let _ = tx.send(Err(e)).await;
+                return rx;
+            }
+        };
+
+        let manager = self.clone();
+        tokio::spawn(async move {
+            loop {
+                // register before reading, so a change made in between still wakes us up
+                let changed = manager.changed.notified();
+                let changes = manager
+                    .run(move |manager| {
+                        let txn = manager.db.begin_read().storage()?;
+                        let table = txn.open_table(CHANGES).storage()?;
+                        let mut changes = vec![];
+                        for entry in table.range(last_id + 1..).storage()? {
+                            let (id, value) = entry.storage()?;
+                            let change = abi::ListenResponse::decode(value.value()).storage()?;
+                            changes.push((id.value(), change));
+                        }
+                        Ok(changes)
+                    })
+                    .await;
+                let changes = match changes {
+                    Ok(changes) => changes,
+                    Err(e) => {
+                        warn!("Listen error: {:?}", e);
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+
+                for (id, change) in changes {
+                    last_id = id;
+                    let tenant_id = change.reservation.as_ref().map(|rsvp| &rsvp.tenant_id);
+                    if tenant_id != Some(&manager.tenant_id) {
+                        continue;
+                    }
+                    if tx.send(Ok(change)).await.is_err() {
+                        // rx is dropped, so client disconnected
+                        return;
+                    }
+                }
+
+                tokio::select! {
+                    _ = changed => {}
+                    _ = tx.closed() => return,
+                }
+            }
+        });
+
+        rx
+    }
+}
+
+impl EmbeddedReservationManager {
+    /// open (or create) the database file, for the default tenant
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, abi::Error> {
+        let db = Database::create(path).storage()?;
+        Self::new(db)
+    }
+
+    /// keep the database in memory, nothing is persisted
+    pub fn in_memory() -> Result<Self, abi::Error> {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .storage()?;
+        Self::new(db)
+    }
+
+    fn new(db: Database) -> Result<Self, abi::Error> {
+        // create the tables, so read transactions could always open them
+        let txn = db.begin_write().storage()?;
+        txn.open_table(RESERVATIONS).storage()?;
+        txn.open_table(RESOURCE_INDEX).storage()?;
+        txn.open_table(CHANGES).storage()?;
+        txn.open_table(SEQUENCES).storage()?;
+        txn.commit().storage()?;
+
+        Ok(Self {
+            db: Arc::new(db),
+            changed: Default::default(),
+            tenant_id: abi::TenantId::default(),
+        })
+    }
+
+    // redb blocks on file io, so run transactions on the blocking threads
+    async fn run<T, F>(&self, f: F) -> Result<T, abi::Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Self) -> Result<T, abi::Error> + Send + 'static,
+    {
+        let manager = self.clone();
+        tokio::task::spawn_blocking(move || f(&manager))
+            .await
+            .storage()?
+    }
+
+    // apply the change to the reservation of the tenant (and of the owner if given) unless it fails,
+    // like an UPDATE ... WHERE. The change is recorded as `op` for listeners, like the trigger does
+    async fn update(
+        &self,
+        id: ReservationId,
+        op: Option<ReservationUpdateType>,
+        owner: Option<UserId>,
+        f: impl FnOnce(&mut abi::Reservation) -> Result<(), abi::Error> + Send + 'static,
+    ) -> Result<abi::Reservation, abi::Error> {
+        let rsvp = self
+            .run(move |manager| {
+                let txn = manager.db.begin_write().storage()?;
+                let rsvp = {
+                    let table = txn.open_table(RESERVATIONS).storage()?;
+                    read_reservation(&table, id)?
+                };
+                let mut rsvp = rsvp
+                    .filter(|rsvp| rsvp.tenant_id == manager.tenant_id)
+                    .ok_or(abi::Error::NotFound)?;
+                check_owner(&rsvp, owner.as_deref())?;
+                f(&mut rsvp)?;
+
+                write_reservation(&txn, &rsvp)?;
+                if let Some(op) = op {
+                    record(&txn, op, &rsvp)?;
+                }
+                txn.commit().storage()?;
+                Ok(rsvp)
+            })
+            .await?;
+
+        if op.is_some() {
+            self.changed.notify_waiters();
+        }
+        Ok(rsvp)
+    }
+
+    // all the reservations of the tenant, the rest conditions are checked in memory
+    async fn reservations(&self) -> Result<Vec<abi::Reservation>, abi::Error> {
+        self.run(|manager| {
+            let txn = manager.db.begin_read().storage()?;
+            let table = txn.open_table(RESERVATIONS).storage()?;
+            let mut rsvps = vec![];
+            for entry in table.iter().storage()? {
+                let (_, value) = entry.storage()?;
+                let rsvp = abi::Reservation::decode(value.value()).storage()?;
+                if rsvp.tenant_id == manager.tenant_id {
+                    rsvps.push(rsvp);
+                }
+            }
+            Ok(rsvps)
+        })
+        .await
+    }
+}
+
+// reservations of the same resource overlapping the window of the reservation. Walk back from the
+// last one starting before its end, till one ends before its start
+fn conflicts(
+    index: &impl ReadableTable<(&'static str, &'static str, i64, i64), i64>,
+    table: &impl ReadableTable<i64, &'static [u8]>,
+    rsvp: &abi::Reservation,
+) -> Result<Vec<abi::Reservation>, abi::Error> {
+    let start = convert_to_micros(rsvp.start.as_ref().unwrap());
+    let end = convert_to_micros(rsvp.end.as_ref().unwrap());
+    let (tenant_id, resource_id) = (rsvp.tenant_id.as_str(), rsvp.resource_id.as_str());
+
+    let mut conflicts = vec![];
+    let range =
+        (tenant_id, resource_id, i64::MIN, i64::MIN)..(tenant_id, resource_id, end, i64::MIN);
+    for entry in index.range(range).storage()?.rev() {
+        let (key, value) = entry.storage()?;
+        if value.value() <= start {
+            break;
+        }
+        conflicts.extend(read_reservation(table, key.value().3)?);
+    }
+    Ok(conflicts)
+}
+
+fn read_reservation(
+    table: &impl ReadableTable<i64, &'static [u8]>,
+    id: ReservationId,
+) -> Result<Option<abi::Reservation>, abi::Error> {
+    match table.get(id).storage()? {
+        Some(value) => Ok(Some(abi::Reservation::decode(value.value()).storage()?)),
+        None => Ok(None),
+    }
+}
+
+fn write_reservation(txn: &WriteTransaction, rsvp: &abi::Reservation) -> Result<(), abi::Error> {
+    let mut table = txn.open_table(RESERVATIONS).storage()?;
+    table
+        .insert(rsvp.id, rsvp.encode_to_vec().as_slice())
+        .storage()?;
+    Ok(())
+}
+
+fn record(
+    txn: &WriteTransaction,
+    op: ReservationUpdateType,
+    rsvp: &abi::Reservation,
+) -> Result<(), abi::Error> {
+    let id = next_id(txn, "reservation_changes")?;
+    let change = abi::ListenResponse::new(op, rsvp.clone());
+    let mut table = txn.open_table(CHANGES).storage()?;
+    table
+        .insert(id, change.encode_to_vec().as_slice())
+        .storage()?;
+    Ok(())
+}
+
+fn next_id(txn: &WriteTransaction, name: &str) -> Result<i64, abi::Error> {
+    let mut table = txn.open_table(SEQUENCES).storage()?;
+    let last_id = table.get(name).storage()?.map(|id| id.value());
+    let id = last_id.unwrap_or_default() + 1;
+    table.insert(name, id).storage()?;
+    Ok(id)
+}
+
+/// errors of redb and the encoding of the values are storage errors
+trait StorageResult<T> {
+    fn storage(self) -> Result<T, abi::Error>;
+}
+
+impl<T, E: Display> StorageResult<T> for Result<T, E> {
+    fn storage(self) -> Result<T, abi::Error> {
+        self.map_err(|e| abi::Error::StorageError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    rsvp_test_suite!(((), EmbeddedReservationManager::in_memory().unwrap()));
+
+    #[tokio::test]
+    async fn reservations_should_persist_after_reopen() {
+        let path = std::env::temp_dir().join(format!("rsvp-{}.redb", std::process::id()));
+        let rsvp = abi::Reservation::new_pending(
+            "tyrid",
+            "ocean-view-room-713",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let rsvp = {
+            let manager = EmbeddedReservationManager::open(&path).unwrap();
+            manager.reserve(rsvp).await.unwrap()
+        };
+
+        let manager = EmbeddedReservationManager::open(&path).unwrap();
+        assert_eq!(manager.get(rsvp.id).await.unwrap(), rsvp);
+        // the index is persisted as well
+        let err = manager.reserve(rsvp.clone()).await.unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+        drop(manager);
+        std::fs::remove_file(path).unwrap();
+    }
+}
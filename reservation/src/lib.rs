@@ -1,29 +1,118 @@
+#[cfg(test)]
+#[macro_use]
+mod test_suite;
+
+mod embedded;
 mod manager;
+mod memory;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
-use abi::FilterPager;
+use abi::{FilterPager, Validator};
 use async_trait::async_trait;
+use chrono::Utc;
+use memory::MemoryStore;
 use sqlx::PgPool;
-use tokio::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Notify};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ReservationManager {
     pool: PgPool,
+    tenant_id: abi::TenantId,
+}
+
+/// stores reservations in a sqlite database, for deployments without postgres
+#[cfg(feature = "sqlite")]
+#[derive(Debug, Clone)]
+pub struct SqliteReservationManager {
+    pool: sqlx::SqlitePool,
+    tenant_id: abi::TenantId,
+}
+
+/// stores reservations in an embedded key-value database file, so a single binary is all it takes.
+/// The file is locked by the process, managers cloned or scoped by `with_tenant` share it
+#[derive(Debug, Clone)]
+pub struct EmbeddedReservationManager {
+    db: Arc<redb::Database>,
+    changed: Arc<Notify>,
+    tenant_id: abi::TenantId,
+}
+
+/// keeps reservations in memory with the same semantics as `ReservationManager`, for tests and
+/// embedded use. Managers cloned or scoped by `with_tenant` share the same reservations
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryReservationManager {
+    store: Arc<Mutex<MemoryStore>>,
+    tenant_id: abi::TenantId,
 }
 
 #[async_trait]
 pub trait Rsvp {
+    /// scope the manager to a tenant, every operation of the returned manager only sees that tenant's reservations
+    fn with_tenant(&self, tenant_id: abi::TenantId) -> Self
+    where
+        Self: Sized;
     /// make a reservation
     async fn reserve(&self, rsvp: abi::Reservation) -> Result<abi::Reservation, abi::Error>;
-    /// change reservation status (if current status is pending, change it to confirmed)
-    async fn change_status(&self, id: abi::ReservationId) -> Result<abi::Reservation, abi::Error>;
-    /// update note
+    /// reserve the reservations as a batch, postgres commits them in one transaction. An invalid or
+    /// conflicting reservation gets its error in place and doesn't stop the others, a storage
+    /// error stops the batch
+    async fn reserve_batch(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+    ) -> Result<Vec<Result<abi::Reservation, abi::Error>>, abi::Error>
+    where
+        Self: Sync,
+    {
+        let mut results = Vec::with_capacity(rsvps.len());
+        for rsvp in rsvps {
+            match self.reserve(rsvp).await {
+                Err(e @ (abi::Error::DbError(_) | abi::Error::StorageError(_))) => return Err(e),
+                result => results.push(result),
+            }
+        }
+        Ok(results)
+    }
+    /// run the validation and conflict checks of `reserve` without writing anything
+    async fn check(&self, rsvp: abi::Reservation) -> Result<abi::CheckResponse, abi::Error>;
+    /// change reservation status (if current status is pending, change it to confirmed). With an
+    /// owner, only a reservation of the owner is changed, permission denied otherwise. It's checked
+    /// by the same write, so the reservation can't change hands in between
+    async fn change_status(
+        &self,
+        id: abi::ReservationId,
+        owner: Option<abi::UserId>,
+    ) -> Result<abi::Reservation, abi::Error>;
+    /// update note, only of a reservation of the owner if given
     async fn update_note(
         &self,
         id: abi::ReservationId,
         note: String,
+        owner: Option<abi::UserId>,
+    ) -> Result<abi::Reservation, abi::Error>;
+    /// transfer reservation to another user, only a reservation of the owner if given. If
+    /// require_acceptance is set, the transfer stays pending until the new owner accepts it
+    async fn transfer(
+        &self,
+        id: abi::ReservationId,
+        user_id: abi::UserId,
+        require_acceptance: bool,
+        owner: Option<abi::UserId>,
+    ) -> Result<abi::Reservation, abi::Error>;
+    /// accept a pending transfer (only the user the reservation is transferred to could accept it),
+    /// not found if there's no pending transfer to the user
+    async fn accept_transfer(
+        &self,
+        id: abi::ReservationId,
+        user_id: abi::UserId,
+    ) -> Result<abi::Reservation, abi::Error>;
+    /// delete reservation, only a reservation of the owner if given
+    async fn delete(
+        &self,
+        id: abi::ReservationId,
+        owner: Option<abi::UserId>,
     ) -> Result<abi::Reservation, abi::Error>;
-    /// delete reservation
-    async fn delete(&self, id: abi::ReservationId) -> Result<abi::Reservation, abi::Error>;
     /// get reservation by id
     async fn get(&self, id: abi::ReservationId) -> Result<abi::Reservation, abi::Error>;
     /// query reservations
@@ -36,4 +125,162 @@ pub trait Rsvp {
         &self,
         query: abi::ReservationFilter,
     ) -> Result<(FilterPager, Vec<abi::Reservation>), abi::Error>;
+    /// full text search over reservation notes, hits are ordered by relevance
+    async fn search(
+        &self,
+        search: abi::ReservationSearch,
+    ) -> Result<abi::SearchResponse, abi::Error>;
+    /// the `limit` free windows of the same duration nearest to the reservation on its resource, and the
+    /// resources of the pool free at its time. Both are only hints, a later `reserve` may still conflict
+    async fn suggest(
+        &self,
+        rsvp: abi::Reservation,
+        limit: usize,
+        resource_pool: Vec<String>,
+    ) -> Result<abi::SuggestResponse, abi::Error>
+    where
+        Self: Sync,
+    {
+        rsvp.validate()?;
+        if resource_pool.len() > abi::MAX_RESOURCE_POOL {
+            return Err(abi::Error::InvalidResourcePool(resource_pool.len() as i64));
+        }
+
+        // only the reservations around the requested window, free windows further away aren't
+        // suggested anyway
+        let (start, end) = abi::suggest_window(&rsvp).ok_or(abi::Error::InvalidTime)?;
+        let query = abi::ReservationQueryBuilder::default()
+            .resource_id(rsvp.resource_id.clone())
+            .start(start)
+            .end(end)
+            .build()?;
+        let busy = collect(self.query(query).await).await?;
+        let slots = abi::nearest_free_slots(&rsvp, &busy, limit, Utc::now());
+
+        // a resource of the pool is free if nothing of it overlaps the requested window, the pool
+        // is capped so a query for each of them is fine
+        let mut free_resources = vec![];
+        for resource_id in resource_pool {
+            if resource_id == rsvp.resource_id || free_resources.contains(&resource_id) {
+                continue;
+            }
+            let query = abi::ReservationQueryBuilder::default()
+                .resource_id(resource_id.clone())
+                .start(rsvp.start.clone().unwrap())
+                .end(rsvp.end.clone().unwrap())
+                .build()?;
+            // the first reservation is enough to know it's taken
+            match self.query(query).await.recv().await {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e),
+                None => free_resources.push(resource_id),
+            }
+        }
+
+        Ok(abi::SuggestResponse {
+            slots,
+            free_resources,
+        })
+    }
+    /// reserve the events of an imported calendar one by one, with a result for each. In a dry run
+    /// nothing is written: the events are checked against the reservations and the events before
+    /// them. Only a storage error stops the import, the events before it are created already
+    async fn import(
+        &self,
+        events: Vec<abi::ImportedEvent>,
+        dry_run: bool,
+    ) -> Result<Vec<abi::ImportResult>, abi::Error>
+    where
+        Self: Sync,
+    {
+        let mut results = Vec::with_capacity(events.len());
+        // events accepted so far in a dry run
+        let mut accepted: Vec<abi::Reservation> = vec![];
+        for event in events {
+            let uid = event.uid;
+            let rsvp = match (event.cancelled, event.reservation) {
+                (true, _) => {
+                    results.push(abi::ImportResult::skipped(uid));
+                    continue;
+                }
+                (false, Err(e)) => {
+                    results.push(abi::ImportResult::invalid(uid, &e));
+                    continue;
+                }
+                (false, Ok(rsvp)) => rsvp,
+            };
+
+            let result = if dry_run {
+                let checked = self.check(rsvp.clone()).await?;
+                let mut conflicts: Vec<_> = checked
+                    .problems
+                    .into_iter()
+                    .filter_map(|p| p.conflict)
+                    .collect();
+                conflicts.extend(accepted.iter().filter(|r| r.conflicts_with(&rsvp)).cloned());
+                if conflicts.is_empty() {
+                    accepted.push(rsvp.clone());
+                    abi::ImportResult::created(uid, rsvp)
+                } else {
+                    let conflict = abi::ReservationConflict::new(&rsvp, &conflicts);
+                    abi::ImportResult::conflict(uid, rsvp, conflict)
+                }
+            } else {
+                match self.reserve(rsvp.clone()).await {
+                    Ok(rsvp) => abi::ImportResult::created(uid, rsvp),
+                    Err(abi::Error::ConflictReservation(conflict)) => {
+                        abi::ImportResult::conflict(uid, rsvp, conflict)
+                    }
+                    Err(e @ (abi::Error::DbError(_) | abi::Error::StorageError(_))) => {
+                        return Err(e)
+                    }
+                    Err(e) => abi::ImportResult::invalid(uid, &e),
+                }
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+    /// changes of the tenant's reservations from now on: created, status changed, transferred and deleted
+    async fn listen(&self) -> mpsc::Receiver<Result<abi::ListenResponse, abi::Error>>;
+    /// check the storage is reachable, for health checks. Storages in the process always are
+    async fn ping(&self) -> Result<(), abi::Error> {
+        Ok(())
+    }
+}
+
+/// fail unless the reservation is the owner's, any reservation goes without an owner
+fn check_owner(rsvp: &abi::Reservation, owner: Option<&str>) -> Result<(), abi::Error> {
+    match owner {
+        Some(owner) if owner != rsvp.user_id => Err(abi::Error::PermissionDenied(format!(
+            "{} doesn't own reservation {}",
+            owner, rsvp.id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// why a write conditioned on the owner changed no row: the reservation is gone or someone else's,
+/// otherwise `err`
+async fn unchanged(
+    manager: &(impl Rsvp + Sync),
+    id: abi::ReservationId,
+    owner: Option<&str>,
+    err: abi::Error,
+) -> abi::Error {
+    match manager.get(id).await {
+        Ok(rsvp) => check_owner(&rsvp, owner).err().unwrap_or(err),
+        Err(e) => e,
+    }
+}
+
+/// every reservation of a query, or its first error
+async fn collect(
+    mut rx: mpsc::Receiver<Result<abi::Reservation, abi::Error>>,
+) -> Result<Vec<abi::Reservation>, abi::Error> {
+    let mut rsvps = vec![];
+    while let Some(result) = rx.recv().await {
+        rsvps.push(result?);
+    }
+    Ok(rsvps)
 }
@@ -1,66 +1,180 @@
-use crate::{ReservationManager, Rsvp};
-use abi::{DbConfig, FilterPager, Normalizer, ReservationId, ToSql, Validator};
+use crate::{unchanged, ReservationManager, Rsvp};
+use abi::{
+    convert_to_timestamp, DbConfig, FilterPager, Normalizer, ReservationConflict, ReservationId,
+    ToSql, UserId, Validator,
+};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use futures::StreamExt;
 use sqlx::{
-    postgres::{types::PgRange, PgPoolOptions},
-    Either, PgPool, Row,
+    postgres::{types::PgRange, PgListener, PgPoolOptions},
+    types::Json,
+    Acquire, Either, FromRow, PgExecutor, PgPool, Row,
 };
 use tokio::sync::mpsc;
 use tracing::{info, warn};
 
+/// sqlstate of exclusion_violation
+const EXCLUSION_VIOLATION: &str = "23P01";
+
 #[async_trait]
 impl Rsvp for ReservationManager {
+    fn with_tenant(&self, tenant_id: abi::TenantId) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            tenant_id,
+        }
+    }
+
     async fn reserve(&self, mut rsvp: abi::Reservation) -> Result<abi::Reservation, abi::Error> {
+        rsvp.tenant_id = self.tenant_id.clone();
         rsvp.validate()?;
 
-        let status = abi::ReservationStatus::from_i32(rsvp.status)
-            .unwrap_or(abi::ReservationStatus::Pending);
-
-        let timespan: PgRange<DateTime<Utc>> = rsvp.get_timespan();
+        match insert(&self.pool, &mut rsvp).await {
+            Err(e) if is_conflict(&e) => {
+                // the constraint only reports one of the conflicts, and in a message meant for humans
+                let conflicts = conflicts(&self.pool, &rsvp).await?;
+                let conflict = ReservationConflict::new(&rsvp, &conflicts);
+                Err(abi::Error::ConflictReservation(conflict))
+            }
+            result => Ok(result.map(|_| rsvp)?),
+        }
+    }
 
-        // generate a insert sql for the reservation
-        // execute the sql
-        let id = sqlx::query(
-            "INSERT INTO rsvp.reservations (user_id, resource_id, timespan, note, status) VALUES ($1, $2, $3, $4, $5::rsvp.reservation_status) RETURNING id"
-        )
-        .bind(rsvp.user_id.clone())
-        .bind(rsvp.resource_id.clone())
-        .bind(timespan)
-        .bind(rsvp.note.clone())
-        .bind(status.to_string())
-        .fetch_one(&self.pool)
-        .await?.get(0);
+    async fn reserve_batch(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+    ) -> Result<Vec<Result<abi::Reservation, abi::Error>>, abi::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(rsvps.len());
+        for mut rsvp in rsvps {
+            rsvp.tenant_id = self.tenant_id.clone();
+            if let Err(e) = rsvp.validate() {
+                results.push(Err(e));
+                continue;
+            }
 
-        rsvp.id = id;
+            // a conflict aborts the transaction, so only roll back to the savepoint of this one
+            let mut savepoint = tx.begin().await?;
+            match insert(&mut *savepoint, &mut rsvp).await {
+                Ok(_) => {
+                    savepoint.commit().await?;
+                    results.push(Ok(rsvp));
+                }
+                Err(e) if is_conflict(&e) => {
+                    savepoint.rollback().await?;
+                    let conflicts = conflicts(&mut *tx, &rsvp).await?;
+                    let conflict = ReservationConflict::new(&rsvp, &conflicts);
+                    results.push(Err(abi::Error::ConflictReservation(conflict)));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        tx.commit().await?;
+        Ok(results)
+    }
 
-        Ok(rsvp)
+    async fn check(&self, mut rsvp: abi::Reservation) -> Result<abi::CheckResponse, abi::Error> {
+        rsvp.tenant_id = self.tenant_id.clone();
+        let conflicts = match rsvp.validate() {
+            Ok(_) => conflicts(&self.pool, &rsvp).await?,
+            Err(_) => vec![],
+        };
+        Ok(abi::CheckResponse::new(&rsvp, &conflicts))
     }
 
-    async fn change_status(&self, id: ReservationId) -> Result<abi::Reservation, abi::Error> {
+    async fn change_status(
+        &self,
+        id: ReservationId,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
         // if current status is pending, change it to confirmed, otherwise do nothing
         id.validate()?;
-        let rsvp: abi::Reservation = sqlx::query_as(
-            "UPDATE rsvp.reservations SET status = 'confirmed' WHERE id = $1 AND status = 'pending' RETURNING *"
-        ).bind(id).fetch_one(&self.pool).await?;
+        let rsvp: Option<abi::Reservation> = sqlx::query_as(
+            "UPDATE rsvp.reservations SET status = 'confirmed' WHERE id = $1 AND tenant_id = $2 AND status = 'pending' AND ($3::text IS NULL OR user_id = $3) RETURNING *"
+        ).bind(id).bind(&self.tenant_id).bind(&owner).fetch_optional(&self.pool).await?;
 
-        Ok(rsvp)
+        match rsvp {
+            Some(rsvp) => Ok(rsvp),
+            None => Err(unchanged(self, id, owner.as_deref(), abi::Error::NotFound).await),
+        }
     }
 
     async fn update_note(
         &self,
         id: ReservationId,
         note: String,
+        owner: Option<UserId>,
     ) -> Result<abi::Reservation, abi::Error> {
         // update the note of the reservation
         id.validate()?;
-        let rsvp: abi::Reservation =
-            sqlx::query_as("UPDATE rsvp.reservations SET note = $1 WHERE id = $2 RETURNING *")
-                .bind(note)
-                .bind(id)
-                .fetch_one(&self.pool)
-                .await?;
+        let rsvp: Option<abi::Reservation> = sqlx::query_as(
+            "UPDATE rsvp.reservations SET note = $1 WHERE id = $2 AND tenant_id = $3 AND ($4::text IS NULL OR user_id = $4) RETURNING *",
+        )
+        .bind(note)
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(&owner)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match rsvp {
+            Some(rsvp) => Ok(rsvp),
+            None => Err(unchanged(self, id, owner.as_deref(), abi::Error::NotFound).await),
+        }
+    }
+
+    async fn transfer(
+        &self,
+        id: ReservationId,
+        user_id: UserId,
+        require_acceptance: bool,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        // move the reservation to the new owner, or record the pending transfer
+        id.validate()?;
+        if user_id.is_empty() {
+            return Err(abi::Error::InvalidUserId(user_id));
+        }
+
+        // the owner can't be the new owner, a reservation left alone is only found by its id
+        let sql = if require_acceptance {
+            "UPDATE rsvp.reservations SET pending_user_id = $1 WHERE id = $2 AND tenant_id = $3 AND user_id <> $1 AND ($4::text IS NULL OR user_id = $4) RETURNING *"
+        } else {
+            "UPDATE rsvp.reservations SET user_id = $1, pending_user_id = NULL WHERE id = $2 AND tenant_id = $3 AND user_id <> $1 AND ($4::text IS NULL OR user_id = $4) RETURNING *"
+        };
+        let rsvp: Option<abi::Reservation> = sqlx::query_as(sql)
+            .bind(&user_id)
+            .bind(id)
+            .bind(&self.tenant_id)
+            .bind(&owner)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match rsvp {
+            Some(rsvp) => Ok(rsvp),
+            None => {
+                let err = abi::Error::InvalidUserId(user_id);
+                Err(unchanged(self, id, owner.as_deref(), err).await)
+            }
+        }
+    }
+
+    async fn accept_transfer(
+        &self,
+        id: ReservationId,
+        user_id: UserId,
+    ) -> Result<abi::Reservation, abi::Error> {
+        // if the reservation is pending transfer to the user, change the owner, otherwise not found
+        id.validate()?;
+        let rsvp: abi::Reservation = sqlx::query_as(
+            "UPDATE rsvp.reservations SET user_id = pending_user_id, pending_user_id = NULL WHERE id = $1 AND tenant_id = $2 AND pending_user_id = $3 RETURNING *"
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
 
         Ok(rsvp)
     }
@@ -69,35 +183,52 @@ impl Rsvp for ReservationManager {
         // get the reservation by id
         id.validate()?;
         let rsvp: abi::Reservation =
-            sqlx::query_as("SELECT * FROM rsvp.reservations WHERE id = $1")
+            sqlx::query_as("SELECT * FROM rsvp.reservations WHERE id = $1 AND tenant_id = $2")
                 .bind(id)
+                .bind(&self.tenant_id)
                 .fetch_one(&self.pool)
                 .await?;
 
         Ok(rsvp)
     }
 
-    async fn delete(&self, id: ReservationId) -> Result<abi::Reservation, abi::Error> {
+    async fn delete(
+        &self,
+        id: ReservationId,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
         // delete the reservation by id
         id.validate()?;
-        let rsvp: abi::Reservation =
-            sqlx::query_as("DELETE FROM rsvp.reservations WHERE id = $1 RETURNING *")
-                .bind(id)
-                .fetch_one(&self.pool)
-                .await?;
-        Ok(rsvp)
+        let rsvp: Option<abi::Reservation> = sqlx::query_as(
+            "DELETE FROM rsvp.reservations WHERE id = $1 AND tenant_id = $2 AND ($3::text IS NULL OR user_id = $3) RETURNING *",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(&owner)
+        .fetch_optional(&self.pool)
+        .await?;
+        match rsvp {
+            Some(rsvp) => Ok(rsvp),
+            None => Err(unchanged(self, id, owner.as_deref(), abi::Error::NotFound).await),
+        }
     }
 
     async fn query(
         &self,
-        query: abi::ReservationQuery,
+        mut query: abi::ReservationQuery,
     ) -> mpsc::Receiver<Result<abi::Reservation, abi::Error>> {
         let pool = self.pool.clone();
         let (tx, rx) = mpsc::channel(128);
+        query.tenant_id = self.tenant_id.clone();
 
         tokio::spawn(async move {
-            let sql = query.to_sql();
-            let mut rsvps = sqlx::query_as(&sql).fetch_many(&pool);
+            if let Err(e) = query.normalize() {
+                // nothing else to send, rx will be closed when tx is dropped
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+            let mut builder = query.to_sql();
+            let mut rsvps = builder.build_query_as().fetch_many(&pool);
             while let Some(ret) = rsvps.next().await {
                 match ret {
                     Ok(Either::Left(r)) => {
@@ -127,21 +258,80 @@ impl Rsvp for ReservationManager {
         &self,
         mut filter: abi::ReservationFilter,
     ) -> Result<(FilterPager, Vec<abi::Reservation>), abi::Error> {
+        filter.tenant_id = self.tenant_id.clone();
         filter.normalize()?;
 
-        let sql = filter.to_sql();
-
-        let rsvps: Vec<abi::Reservation> = sqlx::query_as(&sql).fetch_all(&self.pool).await?;
+        let rsvps: Vec<abi::Reservation> = filter
+            .to_sql()
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await?;
         let mut rsvps = rsvps.into_iter().collect();
 
-        let pager = filter.get_pager(&mut rsvps);
+        let mut pager = filter.get_pager(&mut rsvps);
+        if filter.with_total {
+            let (total,): (i64,) = filter
+                .to_count_sql()
+                .build_query_as()
+                .fetch_one(&self.pool)
+                .await?;
+            pager.total = Some(total);
+        }
         Ok((pager, rsvps.into_iter().collect()))
     }
+
+    async fn search(
+        &self,
+        mut search: abi::ReservationSearch,
+    ) -> Result<abi::SearchResponse, abi::Error> {
+        search.tenant_id = self.tenant_id.clone();
+        search.normalize()?;
+
+        let mut hits: Vec<abi::SearchHit> = search
+            .to_sql()
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await?;
+        let next_offset = search.next_offset(&mut hits);
+        Ok(abi::SearchResponse { hits, next_offset })
+    }
+
+    async fn listen(&self) -> mpsc::Receiver<Result<abi::ListenResponse, abi::Error>> {
+        let (tx, rx) = mpsc::channel(128);
+
+        // subscribe before returning, so the changes made right after are not missed
+        let listener = match self.subscribe().await {
+            Ok(listener) => listener,
+            Err(e) => {
+                let _ = tx.send(Err(e)).await;
+                return rx;
+            }
+        };
+
+        let manager = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = manager.send_changes(listener, &tx).await {
+                warn!("Listen error: {:?}", e);
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+
+        rx
+    }
+
+    async fn ping(&self) -> Result<(), abi::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
 }
 
 impl ReservationManager {
+    /// create a manager for the default tenant, use `with_tenant` to scope it to another tenant
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            tenant_id: abi::TenantId::default(),
+        }
     }
 
     pub async fn from_config(config: &DbConfig) -> Result<Self, abi::Error> {
@@ -152,18 +342,129 @@ impl ReservationManager {
             .await?;
         Ok(Self::new(pool))
     }
+
+    // listen to the notifications of the reservation trigger, and find out where the changes start
+    async fn subscribe(&self) -> Result<(PgListener, i32), abi::Error> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen("reservation_update").await?;
+        let (last_id,): (i32,) =
+            sqlx::query_as("SELECT COALESCE(MAX(id), 0) FROM rsvp.reservation_changes")
+                .fetch_one(&self.pool)
+                .await?;
+        Ok((listener, last_id))
+    }
+
+    // send the tenant's changes recorded by the trigger every time it notifies, until rx is dropped
+    async fn send_changes(
+        &self,
+        (mut listener, mut last_id): (PgListener, i32),
+        tx: &mpsc::Sender<Result<abi::ListenResponse, abi::Error>>,
+    ) -> Result<(), abi::Error> {
+        loop {
+            let rows = sqlx::query(
+                "SELECT c.id AS change_id, c.op::text AS op, r.* FROM rsvp.reservation_changes c, jsonb_populate_record(NULL::rsvp.reservations, COALESCE(c.new, c.old)) r WHERE c.id > $1 AND r.tenant_id = $2 ORDER BY c.id",
+            )
+            .bind(last_id)
+            .bind(&self.tenant_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+            for row in rows {
+                last_id = row.get("change_id");
+                let op = match row.get::<&str, _>("op") {
+                    "create" => abi::ReservationUpdateType::Create,
+                    "update" => abi::ReservationUpdateType::Update,
+                    "delete" => abi::ReservationUpdateType::Delete,
+                    "transfer" => abi::ReservationUpdateType::Transfer,
+                    _ => abi::ReservationUpdateType::Unknown,
+                };
+                let rsvp = abi::Reservation::from_row(&row)?;
+                if tx
+                    .send(Ok(abi::ListenResponse::new(op, rsvp)))
+                    .await
+                    .is_err()
+                {
+                    // rx is dropped, so client disconnected
+                    return Ok(());
+                }
+            }
+
+            tokio::select! {
+                notification = listener.recv() => {
+                    notification?;
+                }
+                _ = tx.closed() => return Ok(()),
+            }
+        }
+    }
+}
+
+/// insert the validated reservation, set its id and creation time
+async fn insert<'e>(
+    executor: impl PgExecutor<'e>,
+    rsvp: &mut abi::Reservation,
+) -> Result<(), sqlx::Error> {
+    let status =
+        abi::ReservationStatus::from_i32(rsvp.status).unwrap_or(abi::ReservationStatus::Pending);
+
+    let timespan: PgRange<DateTime<Utc>> = rsvp.get_timespan();
+
+    let row = sqlx::query(
+        "INSERT INTO rsvp.reservations (tenant_id, user_id, resource_id, timespan, note, status, labels) VALUES ($1, $2, $3, $4, $5, $6::rsvp.reservation_status, $7) RETURNING id, created_at"
+    )
+    .bind(rsvp.tenant_id.clone())
+    .bind(rsvp.user_id.clone())
+    .bind(rsvp.resource_id.clone())
+    .bind(timespan)
+    .bind(rsvp.note.clone())
+    .bind(status.to_string())
+    .bind(Json(&rsvp.labels))
+    .fetch_one(executor)
+    .await?;
+
+    rsvp.id = row.get(0);
+    rsvp.created_at = Some(convert_to_timestamp(&row.get::<DateTime<Utc>, _>(1)));
+    Ok(())
+}
+
+/// the insert failed on the exclusion constraint
+fn is_conflict(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(e) if e.code().as_deref() == Some(EXCLUSION_VIOLATION))
+}
+
+// reservations of the same resource overlapping the window of the reservation
+async fn conflicts<'e>(
+    executor: impl PgExecutor<'e>,
+    rsvp: &abi::Reservation,
+) -> Result<Vec<abi::Reservation>, abi::Error> {
+    let conflicts = sqlx::query_as(
+        "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND resource_id = $2 AND timespan && $3 ORDER BY lower(timespan)",
+    )
+    .bind(&rsvp.tenant_id)
+    .bind(&rsvp.resource_id)
+    .bind(rsvp.get_timespan())
+    .fetch_all(executor)
+    .await?;
+    Ok(conflicts)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use abi::{
-        Reservation, ReservationConflict, ReservationConflictInfo, ReservationFilterBuilder,
-        ReservationQueryBuilder, ReservationWindow,
+        ConflictingReservation, LabelOperator, LabelSelector, Reservation,
+        ReservationFilterBuilder, ReservationQueryBuilder, ReservationQueryMode,
+        ReservationSearchBuilder, ReservationWindow,
     };
     use prost_types::Timestamp;
     use sqlx_db_tester::TestDb;
 
+    rsvp_test_suite!({
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        (tdb, ReservationManager::new(pool))
+    });
+
     #[tokio::test]
     async fn reserve_should_work_for_valid_window() {
         let tdb = get_tdb();
@@ -176,7 +477,7 @@ mod tests {
     async fn reserve_conflict_reservation_should_reject() {
         let tdb = get_tdb();
         let pool = tdb.get_pool().await;
-        let (_rsvp1, manager) = make_tyr_reservation(pool).await;
+        let (rsvp1, manager) = make_tyr_reservation(pool).await;
         let rsvp2 = abi::Reservation::new_pending(
             "aliceid",
             "ocean-view-room-713",
@@ -187,20 +488,25 @@ mod tests {
 
         let err = manager.reserve(rsvp2).await.unwrap_err();
 
-        let info = ReservationConflictInfo::Parsed(ReservationConflict {
+        let conflict = ReservationConflict {
             new: ReservationWindow {
                 rid: "ocean-view-room-713".to_string(),
                 start: "2022-12-26T15:00:00-0700".parse().unwrap(),
                 end: "2022-12-30T12:00:00-0700".parse().unwrap(),
             },
-            old: ReservationWindow {
-                rid: "ocean-view-room-713".to_string(),
-                start: "2022-12-25T15:00:00-0700".parse().unwrap(),
-                end: "2022-12-28T12:00:00-0700".parse().unwrap(),
-            },
-        });
+            conflicts: vec![ConflictingReservation {
+                id: rsvp1.id,
+                user_id: "tyrid".to_string(),
+                window: ReservationWindow {
+                    rid: "ocean-view-room-713".to_string(),
+                    start: "2022-12-25T15:00:00-0700".parse().unwrap(),
+                    end: "2022-12-28T12:00:00-0700".parse().unwrap(),
+                },
+                status: abi::ReservationStatus::Pending,
+            }],
+        };
 
-        assert_eq!(err, abi::Error::ConflictReservation(info));
+        assert_eq!(err, abi::Error::ConflictReservation(conflict));
     }
 
     #[tokio::test]
@@ -209,7 +515,7 @@ mod tests {
         let pool = tdb.get_pool().await;
         let (rsvp, manager) = make_alice_reservation(pool).await;
 
-        let rsvp = manager.change_status(rsvp.id).await.unwrap();
+        let rsvp = manager.change_status(rsvp.id, None).await.unwrap();
         assert_eq!(rsvp.status, abi::ReservationStatus::Confirmed as i32);
     }
 
@@ -219,10 +525,10 @@ mod tests {
         let pool = tdb.get_pool().await;
         let (rsvp, manager) = make_alice_reservation(pool).await;
 
-        let rsvp = manager.change_status(rsvp.id).await.unwrap();
+        let rsvp = manager.change_status(rsvp.id, None).await.unwrap();
 
         // change status again should do nothing
-        let ret = manager.change_status(rsvp.id).await.unwrap_err();
+        let ret = manager.change_status(rsvp.id, None).await.unwrap_err();
         assert_eq!(ret, abi::Error::NotFound);
     }
 
@@ -232,12 +538,81 @@ mod tests {
         let pool = tdb.get_pool().await;
         let (rsvp, manager) = make_alice_reservation(pool).await;
         let rsvp = manager
-            .update_note(rsvp.id, "hello world".into())
+            .update_note(rsvp.id, "hello world".into(), None)
             .await
             .unwrap();
         assert_eq!(rsvp.note, "hello world");
     }
 
+    #[tokio::test]
+    async fn transfer_should_change_owner_and_record_both_owners() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let (rsvp, manager) = make_alice_reservation(pool.clone()).await;
+        let rsvp = manager
+            .transfer(rsvp.id, "bobid".into(), false, None)
+            .await
+            .unwrap();
+        assert_eq!(rsvp.user_id, "bobid");
+        assert_eq!(rsvp.pending_user_id, "");
+
+        let (old, new): (String, String) = sqlx::query_as(
+            "SELECT old->>'user_id', new->>'user_id' FROM rsvp.reservation_changes WHERE reservation_id = $1 AND op = 'transfer'",
+        )
+        .bind(rsvp.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(old, "aliceid");
+        assert_eq!(new, "bobid");
+    }
+
+    #[tokio::test]
+    async fn transfer_with_acceptance_should_wait_for_new_owner() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let (rsvp, manager) = make_alice_reservation(pool).await;
+        let rsvp = manager
+            .transfer(rsvp.id, "bobid".into(), true, None)
+            .await
+            .unwrap();
+        assert_eq!(rsvp.user_id, "aliceid");
+        assert_eq!(rsvp.pending_user_id, "bobid");
+
+        // only the pending user could accept the transfer
+        let err = manager
+            .accept_transfer(rsvp.id, "eveid".into())
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::NotFound);
+
+        let rsvp = manager
+            .accept_transfer(rsvp.id, "bobid".into())
+            .await
+            .unwrap();
+        assert_eq!(rsvp.user_id, "bobid");
+        assert_eq!(rsvp.pending_user_id, "");
+
+        // accept again should do nothing
+        let err = manager
+            .accept_transfer(rsvp.id, "bobid".into())
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::NotFound);
+    }
+
+    #[tokio::test]
+    async fn transfer_to_empty_user_should_reject() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let (rsvp, manager) = make_alice_reservation(pool).await;
+        let err = manager
+            .transfer(rsvp.id, "".into(), false, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::InvalidUserId("".into()));
+    }
+
     #[tokio::test]
     async fn get_reservation_should_work() {
         let tdb = get_tdb();
@@ -252,7 +627,7 @@ mod tests {
         let tdb = get_tdb();
         let pool = tdb.get_pool().await;
         let (rsvp, manager) = make_alice_reservation(pool).await;
-        manager.delete(rsvp.id).await.unwrap();
+        manager.delete(rsvp.id, None).await.unwrap();
         let rsvp1 = manager.get(rsvp.id).await.unwrap_err();
         assert_eq!(rsvp1, abi::Error::NotFound);
     }
@@ -280,6 +655,7 @@ mod tests {
             .start("2023-01-01T15:00:00-0700".parse::<Timestamp>().unwrap())
             .end("2023-02-01T12:00:00-0700".parse::<Timestamp>().unwrap())
             .status(abi::ReservationStatus::Confirmed as i32)
+            .mode(ReservationQueryMode::ContainedIn as i32)
             .build()
             .unwrap();
         let mut rx = manager.query(query).await;
@@ -297,11 +673,87 @@ mod tests {
         assert_eq!(rx.recv().await, None);
 
         // change state to confirmed, query should get result
-        let rsvp = manager.change_status(rsvp.id).await.unwrap();
+        let rsvp = manager.change_status(rsvp.id, None).await.unwrap();
         let mut rx = manager.query(query).await;
         assert_eq!(rx.recv().await, Some(Ok(rsvp)));
     }
 
+    #[tokio::test]
+    async fn query_modes_should_work() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        // alice reserved 2023-01-25 ~ 2023-02-25
+        let (rsvp, manager) = make_alice_reservation(pool).await;
+
+        let cases = [
+            (
+                "2023-02-01",
+                "2023-03-01",
+                ReservationQueryMode::Overlaps,
+                true,
+            ),
+            (
+                "2023-02-01",
+                "2023-03-01",
+                ReservationQueryMode::ContainedIn,
+                false,
+            ),
+            (
+                "2023-01-01",
+                "2023-03-01",
+                ReservationQueryMode::ContainedIn,
+                true,
+            ),
+            (
+                "2023-02-01",
+                "2023-02-10",
+                ReservationQueryMode::Contains,
+                true,
+            ),
+            (
+                "2023-01-01",
+                "2023-02-10",
+                ReservationQueryMode::Contains,
+                false,
+            ),
+            (
+                "2023-01-01",
+                "2023-02-01",
+                ReservationQueryMode::StartsWithin,
+                true,
+            ),
+            (
+                "2023-02-01",
+                "2023-03-01",
+                ReservationQueryMode::StartsWithin,
+                false,
+            ),
+        ];
+
+        for (start, end, mode, found) in cases {
+            let query = ReservationQueryBuilder::default()
+                .user_id("aliceid")
+                .start(
+                    format!("{}T00:00:00-0700", start)
+                        .parse::<Timestamp>()
+                        .unwrap(),
+                )
+                .end(
+                    format!("{}T00:00:00-0700", end)
+                        .parse::<Timestamp>()
+                        .unwrap(),
+                )
+                .mode(mode as i32)
+                .build()
+                .unwrap();
+            let mut rx = manager.query(query).await;
+            if found {
+                assert_eq!(rx.recv().await, Some(Ok(rsvp.clone())), "{:?}", mode);
+            }
+            assert_eq!(rx.recv().await, None, "{:?}", mode);
+        }
+    }
+
     #[tokio::test]
     async fn filter_reservations_should_work() {
         let tdb = get_tdb();
@@ -320,6 +772,375 @@ mod tests {
         assert_eq!(rsvps[0], rsvp);
     }
 
+    #[tokio::test]
+    async fn filter_reservations_with_multiple_statuses_should_work() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let (alice, manager) = make_alice_reservation(pool.clone()).await;
+        let (tyr, _) = make_tyr_reservation(pool).await;
+        let tyr = manager.change_status(tyr.id, None).await.unwrap();
+
+        let filter = ReservationFilterBuilder::default()
+            .statuses(vec![
+                abi::ReservationStatus::Pending as i32,
+                abi::ReservationStatus::Confirmed as i32,
+            ])
+            .build()
+            .unwrap();
+        let (_, rsvps) = manager.filter(filter).await.unwrap();
+        assert_eq!(rsvps, vec![alice.clone(), tyr.clone()]);
+
+        // no status means all statuses
+        let filter = ReservationFilterBuilder::default().build().unwrap();
+        let (_, rsvps) = manager.filter(filter).await.unwrap();
+        assert_eq!(rsvps, vec![alice.clone(), tyr.clone()]);
+
+        let query = ReservationQueryBuilder::default()
+            .statuses(vec![abi::ReservationStatus::Pending as i32])
+            .build()
+            .unwrap();
+        let mut rx = manager.query(query).await;
+        assert_eq!(rx.recv().await, Some(Ok(alice)));
+        assert_eq!(rx.recv().await, None);
+
+        // old clients set the deprecated status without normalizing the query
+        let query = abi::ReservationQuery {
+            status: abi::ReservationStatus::Confirmed as i32,
+            mode: ReservationQueryMode::Overlaps as i32,
+            ..Default::default()
+        };
+        let mut rx = manager.query(query).await;
+        assert_eq!(rx.recv().await, Some(Ok(tyr)));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn filter_reservations_by_window_sorted_by_start_should_work() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let manager = ReservationManager::new(pool);
+
+        // 25 daily bookings inserted in reverse order, so id order differs from start order
+        let mut rsvps = vec![];
+        for day in (1..=25).rev() {
+            let rsvp = abi::Reservation::new_pending(
+                "tyrid",
+                format!("room-{}", day % 2),
+                format!("2022-12-{:02}T10:00:00-0700", day).parse().unwrap(),
+                format!("2022-12-{:02}T12:00:00-0700", day).parse().unwrap(),
+                "",
+            );
+            rsvps.push(manager.reserve(rsvp).await.unwrap());
+        }
+        rsvps.reverse();
+
+        let filter = ReservationFilterBuilder::default()
+            .user_id("tyrid")
+            .start("2022-12-03T11:00:00-0700".parse::<Timestamp>().unwrap())
+            .sort_by(abi::ReservationSortBy::Start as i32)
+            .build()
+            .unwrap();
+        let (pager, page1) = manager.filter(filter.clone()).await.unwrap();
+        assert_eq!(page1, rsvps[2..12]);
+        assert!(pager.next_key.is_some());
+
+        // a booking inserted before the cursor doesn't shift the next page
+        let rsvp = abi::Reservation::new_pending(
+            "tyrid",
+            "room-2",
+            "2022-12-04T10:00:00-0700".parse().unwrap(),
+            "2022-12-04T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        manager.reserve(rsvp).await.unwrap();
+
+        let filter = filter.next_page(&pager).unwrap();
+        let (pager, page2) = manager.filter(filter.clone()).await.unwrap();
+        assert_eq!(page2, rsvps[12..22]);
+
+        let filter = filter.next_page(&pager).unwrap();
+        let (pager, page3) = manager.filter(filter).await.unwrap();
+        assert_eq!(page3, rsvps[22..]);
+        assert_eq!(pager.next, None);
+    }
+
+    #[tokio::test]
+    async fn filter_reservations_should_page_back_and_forth() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let manager = ReservationManager::new(pool);
+
+        let mut rsvps = vec![];
+        for i in 0..25 {
+            let rsvp = abi::Reservation::new_pending(
+                "tyrid",
+                format!("router-{}", i),
+                "2022-12-26T15:00:00-0700".parse().unwrap(),
+                "2022-12-30T12:00:00-0700".parse().unwrap(),
+                "",
+            );
+            rsvps.push(manager.reserve(rsvp).await.unwrap());
+        }
+
+        for desc in [false, true] {
+            let mut expected = rsvps.clone();
+            if desc {
+                expected.reverse();
+            }
+            let pages: Vec<_> = expected.chunks(10).collect();
+
+            let filter = ReservationFilterBuilder::default()
+                .desc(desc)
+                .with_total(true)
+                .build()
+                .unwrap();
+            let (pager, page) = manager.filter(filter.clone()).await.unwrap();
+            assert_eq!(page, pages[0]);
+            assert_eq!(pager.total, Some(25));
+
+            let filter = filter.next_page(&pager).unwrap();
+            let (pager, page) = manager.filter(filter.clone()).await.unwrap();
+            assert_eq!(page, pages[1]);
+
+            let filter = filter.next_page(&pager).unwrap();
+            let (pager, page) = manager.filter(filter.clone()).await.unwrap();
+            assert_eq!(page, pages[2]);
+            assert_eq!(pager.next, None);
+            assert_eq!(pager.total, Some(25));
+
+            let filter = filter.prev_page(&pager).unwrap();
+            let (pager, page) = manager.filter(filter.clone()).await.unwrap();
+            assert_eq!(page, pages[1]);
+
+            let filter = filter.prev_page(&pager).unwrap();
+            let (pager, page) = manager.filter(filter.clone()).await.unwrap();
+            assert_eq!(page, pages[0]);
+            assert_eq!(pager.prev, None);
+            assert!(filter.prev_page(&pager).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn ids_with_quotes_should_be_bound_not_injected() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let (rsvp, manager) = make_reservation(
+            pool,
+            "o'neil",
+            "room-1",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-28T12:00:00-0700",
+            "it's a VIP",
+        )
+        .await;
+        make_alice_reservation(manager.pool.clone()).await;
+
+        let filter = ReservationFilterBuilder::default()
+            .user_id("o'neil")
+            .build()
+            .unwrap();
+        let (_, rsvps) = manager.filter(filter).await.unwrap();
+        assert_eq!(rsvps, vec![rsvp.clone()]);
+
+        let filter = ReservationFilterBuilder::default()
+            .user_id("x' OR '1'='1")
+            .build()
+            .unwrap();
+        let (_, rsvps) = manager.filter(filter).await.unwrap();
+        assert!(rsvps.is_empty());
+
+        let query = ReservationQueryBuilder::default()
+            .resource_id("room-1' OR TRUE --")
+            .build()
+            .unwrap();
+        let mut rx = manager.query(query).await;
+        assert_eq!(rx.recv().await, None);
+
+        let query = ReservationQueryBuilder::default()
+            .user_id("o'neil")
+            .label_selectors(vec![LabelSelector::new(
+                "team",
+                LabelOperator::NotEquals,
+                ["it's"],
+            )])
+            .build()
+            .unwrap();
+        let mut rx = manager.query(query).await;
+        assert_eq!(rx.recv().await, Some(Ok(rsvp)));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn tenants_should_not_see_each_other() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let (rsvp, manager) = make_tyr_reservation(pool).await;
+
+        // same resource and window in another tenant is not a conflict
+        let acme = manager.with_tenant("acme".into());
+        let acme_rsvp = acme
+            .reserve(abi::Reservation::new_pending(
+                "tyrid",
+                "ocean-view-room-713",
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "same room, different business unit",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(acme_rsvp.tenant_id, "acme");
+
+        // id based operations can't reach reservations of other tenants
+        assert_eq!(acme.get(rsvp.id).await.unwrap_err(), abi::Error::NotFound);
+        assert_eq!(
+            acme.delete(rsvp.id, None).await.unwrap_err(),
+            abi::Error::NotFound
+        );
+        assert_eq!(
+            manager.change_status(acme_rsvp.id, None).await.unwrap_err(),
+            abi::Error::NotFound
+        );
+
+        // a crafted tenant id in query or filter is ignored
+        let mut query = ReservationQueryBuilder::default()
+            .user_id("tyrid")
+            .status(abi::ReservationStatus::Pending as i32)
+            .build()
+            .unwrap();
+        query.tenant_id = "".into();
+        let mut rx = acme.query(query).await;
+        assert_eq!(rx.recv().await, Some(Ok(acme_rsvp.clone())));
+        assert_eq!(rx.recv().await, None);
+
+        let mut filter = ReservationFilterBuilder::default()
+            .user_id("tyrid")
+            .status(abi::ReservationStatus::Pending as i32)
+            .build()
+            .unwrap();
+        filter.tenant_id = "acme".into();
+        let (_, rsvps) = manager.filter(filter).await.unwrap();
+        assert_eq!(rsvps, vec![rsvp]);
+    }
+
+    #[tokio::test]
+    async fn query_and_filter_by_labels_should_work() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let manager = ReservationManager::new(pool);
+        let apollo = manager
+            .reserve(
+                abi::Reservation::new_pending(
+                    "aliceid",
+                    "ixia-test-1",
+                    "2023-01-25T15:00:00-0700".parse().unwrap(),
+                    "2023-02-25T12:00:00-0700".parse().unwrap(),
+                    "apollo testbed",
+                )
+                .with_label("project", "apollo")
+                .with_label("cost-center", "42"),
+            )
+            .await
+            .unwrap();
+        let gemini = manager
+            .reserve(
+                abi::Reservation::new_pending(
+                    "aliceid",
+                    "ixia-test-2",
+                    "2023-01-25T15:00:00-0700".parse().unwrap(),
+                    "2023-02-25T12:00:00-0700".parse().unwrap(),
+                    "gemini testbed",
+                )
+                .with_label("project", "gemini"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(manager.get(apollo.id).await.unwrap(), apollo);
+
+        let query = ReservationQueryBuilder::default()
+            .user_id("aliceid")
+            .label_selectors(
+                LabelSelector::parse_all("project in (apollo, gemini),cost-center").unwrap(),
+            )
+            .build()
+            .unwrap();
+        let mut rx = manager.query(query).await;
+        assert_eq!(rx.recv().await, Some(Ok(apollo)));
+        assert_eq!(rx.recv().await, None);
+
+        let filter = ReservationFilterBuilder::default()
+            .label_selectors(LabelSelector::parse_all("project!=apollo").unwrap())
+            .build()
+            .unwrap();
+        let (_, rsvps) = manager.filter(filter).await.unwrap();
+        assert_eq!(rsvps, vec![gemini]);
+    }
+
+    #[tokio::test]
+    async fn search_reservations_should_rank_and_highlight() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let (tyr, manager) = make_tyr_reservation(pool.clone()).await;
+        let (alice, _) = make_alice_reservation(pool).await;
+        let vip = manager
+            .reserve(abi::Reservation::new_pending(
+                "bobid",
+                "ocean-view-room-714",
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "VIP guest, upgrade to the presidential suite. Upgrade is already paid.",
+            ))
+            .await
+            .unwrap();
+
+        let search = ReservationSearchBuilder::default()
+            .text("upgrade room")
+            .build()
+            .unwrap();
+        let ret = manager.search(search).await.unwrap();
+        assert_eq!(ret.next_offset, None);
+        assert_eq!(ret.hits.len(), 1);
+        assert_eq!(ret.hits[0].reservation, Some(tyr.clone()));
+        assert!(ret.hits[0].snippet.contains("<b>upgrade</b>"));
+
+        let search = ReservationSearchBuilder::default()
+            .text("upgrade")
+            .build()
+            .unwrap();
+        let ret = manager.search(search).await.unwrap();
+        let ids: Vec<_> = ret
+            .hits
+            .iter()
+            .map(|h| h.reservation.as_ref().unwrap().id)
+            .collect();
+        // the note mentioning upgrade twice ranks first
+        assert_eq!(ids, vec![vip.id, tyr.id]);
+        assert!(ret.hits[0].rank > ret.hits[1].rank);
+
+        // existing filters still apply
+        let search = ReservationSearchBuilder::default()
+            .text("upgrade")
+            .user_id("tyrid")
+            .build()
+            .unwrap();
+        let ret = manager.search(search).await.unwrap();
+        assert_eq!(ret.hits.len(), 1);
+        assert_eq!(ret.hits[0].reservation, Some(tyr));
+
+        let search = ReservationSearchBuilder::default()
+            .text("xyz project")
+            .status(abi::ReservationStatus::Confirmed as i32)
+            .build()
+            .unwrap();
+        assert!(manager.search(search).await.unwrap().hits.is_empty());
+        manager.change_status(alice.id, None).await.unwrap();
+        let search = ReservationSearchBuilder::default()
+            .text("xyz project")
+            .status(abi::ReservationStatus::Confirmed as i32)
+            .build()
+            .unwrap();
+        assert_eq!(manager.search(search).await.unwrap().hits.len(), 1);
+    }
+
     // private none test functions
     fn get_tdb() -> TestDb {
         TestDb::new("localhost", 5432, "postgres", "postgres", "../migrations")
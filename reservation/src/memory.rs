@@ -0,0 +1,368 @@
+use crate::{check_owner, InMemoryReservationManager, Rsvp};
+use abi::{
+    convert_to_timestamp, FilterPager, Normalizer, ReservationConflict, ReservationId,
+    ReservationUpdateType, UserId, Validator,
+};
+use async_trait::async_trait;
+use chrono::{DurationRound, Utc};
+use std::collections::BTreeMap;
+use tokio::sync::{broadcast, mpsc};
+
+/// how many changes a listener may fall behind before it's dropped
+const CHANGES_CAPACITY: usize = 1024;
+
+/// reservations of all the tenants, ids are shared like a BIGSERIAL
+#[derive(Debug)]
+pub(crate) struct MemoryStore {
+    last_id: ReservationId,
+    reservations: BTreeMap<ReservationId, abi::Reservation>,
+    /// changes like rsvp.reservation_changes, only kept until the listeners got them
+    changes: broadcast::Sender<abi::ListenResponse>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self {
+            last_id: 0,
+            reservations: BTreeMap::new(),
+            changes: broadcast::channel(CHANGES_CAPACITY).0,
+        }
+    }
+}
+
+impl MemoryStore {
+    fn conflicts(&self, rsvp: &abi::Reservation) -> Vec<abi::Reservation> {
+        self.reservations
+            .values()
+            .filter(|old| old.conflicts_with(rsvp))
+            .cloned()
+            .collect()
+    }
+
+    fn record(&mut self, op: ReservationUpdateType, rsvp: &abi::Reservation) {
+        // no listener, nothing to keep
+        let _ = self
+            .changes
+            .send(abi::ListenResponse::new(op, rsvp.clone()));
+    }
+}
+
+#[async_trait]
+impl Rsvp for InMemoryReservationManager {
+    fn with_tenant(&self, tenant_id: abi::TenantId) -> Self {
+        Self {
+            store: self.store.clone(),
+            tenant_id,
+        }
+    }
+
+    async fn reserve(&self, mut rsvp: abi::Reservation) -> Result<abi::Reservation, abi::Error> {
+        rsvp.tenant_id = self.tenant_id.clone();
+        rsvp.validate()?;
+
+        let status = abi::ReservationStatus::from_i32(rsvp.status)
+            .unwrap_or(abi::ReservationStatus::Pending);
+        rsvp.status = status as i32;
+
+        let mut store = self.store.lock().unwrap();
+        let conflicts = store.conflicts(&rsvp);
+        if !conflicts.is_empty() {
+            let conflict = ReservationConflict::new(&rsvp, &conflicts);
+            return Err(abi::Error::ConflictReservation(conflict));
+        }
+
+        store.last_id += 1;
+        rsvp.id = store.last_id;
+        // same precision as timestamptz
+        let now = Utc::now()
+            .duration_trunc(chrono::Duration::microseconds(1))
+            .unwrap();
+        rsvp.created_at = Some(convert_to_timestamp(&now));
+        let rsvp = abi::Reservation {
+            pending_user_id: String::new(),
+            ..rsvp
+        };
+        store.record(ReservationUpdateType::Create, &rsvp);
+        store.reservations.insert(rsvp.id, rsvp.clone());
+
+        Ok(rsvp)
+    }
+
+    async fn check(&self, mut rsvp: abi::Reservation) -> Result<abi::CheckResponse, abi::Error> {
+        rsvp.tenant_id = self.tenant_id.clone();
+        let conflicts = match rsvp.validate() {
+            Ok(_) => self.store.lock().unwrap().conflicts(&rsvp),
+            Err(_) => vec![],
+        };
+        Ok(abi::CheckResponse::new(&rsvp, &conflicts))
+    }
+
+    async fn change_status(
+        &self,
+        id: ReservationId,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        // if current status is pending, change it to confirmed, otherwise do nothing
+        id.validate()?;
+        self.update(id, Some(ReservationUpdateType::Update), owner, |rsvp| {
+            if rsvp.status != abi::ReservationStatus::Pending as i32 {
+                return Err(abi::Error::NotFound);
+            }
+            rsvp.status = abi::ReservationStatus::Confirmed as i32;
+            Ok(())
+        })
+    }
+
+    async fn update_note(
+        &self,
+        id: ReservationId,
+        note: String,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        self.update(id, None, owner, |rsvp| {
+            rsvp.note = note;
+            Ok(())
+        })
+    }
+
+    async fn transfer(
+        &self,
+        id: ReservationId,
+        user_id: UserId,
+        require_acceptance: bool,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        if user_id.is_empty() {
+            return Err(abi::Error::InvalidUserId(user_id));
+        }
+
+        self.update(id, Some(ReservationUpdateType::Transfer), owner, |rsvp| {
+            if rsvp.user_id == user_id {
+                return Err(abi::Error::InvalidUserId(user_id));
+            }
+            if require_acceptance {
+                rsvp.pending_user_id = user_id;
+            } else {
+                rsvp.user_id = user_id;
+                rsvp.pending_user_id = String::new();
+            }
+            Ok(())
+        })
+    }
+
+    async fn accept_transfer(
+        &self,
+        id: ReservationId,
+        user_id: UserId,
+    ) -> Result<abi::Reservation, abi::Error> {
+        // if the reservation is pending transfer to the user, change the owner, otherwise not found
+        id.validate()?;
+        self.update(id, Some(ReservationUpdateType::Transfer), None, |rsvp| {
+            if rsvp.pending_user_id.is_empty() || rsvp.pending_user_id != user_id {
+                return Err(abi::Error::NotFound);
+            }
+            rsvp.user_id = std::mem::take(&mut rsvp.pending_user_id);
+            Ok(())
+        })
+    }
+
+    async fn get(&self, id: ReservationId) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        let store = self.store.lock().unwrap();
+        store
+            .reservations
+            .get(&id)
+            .filter(|rsvp| rsvp.tenant_id == self.tenant_id)
+            .cloned()
+            .ok_or(abi::Error::NotFound)
+    }
+
+    async fn delete(
+        &self,
+        id: ReservationId,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        let mut store = self.store.lock().unwrap();
+        let rsvp = match store.reservations.get(&id) {
+            Some(rsvp) if rsvp.tenant_id == self.tenant_id => {
+                check_owner(rsvp, owner.as_deref())?;
+                store.reservations.remove(&id).unwrap()
+            }
+            _ => return Err(abi::Error::NotFound),
+        };
+        store.record(ReservationUpdateType::Delete, &rsvp);
+        Ok(rsvp)
+    }
+
+    async fn query(
+        &self,
+        mut query: abi::ReservationQuery,
+    ) -> mpsc::Receiver<Result<abi::Reservation, abi::Error>> {
+        let (tx, rx) = mpsc::channel(128);
+        query.tenant_id = self.tenant_id.clone();
+
+        let rsvps = query.normalize().map(|_| {
+            let store = self.store.lock().unwrap();
+            let mut rsvps: Vec<_> = store
+                .reservations
+                .values()
+                .filter(|rsvp| query.matches(rsvp))
+                .cloned()
+                .collect();
+            rsvps.sort_by_key(|rsvp| (rsvp.start.as_ref().map(abi::convert_to_micros), rsvp.id));
+            if query.desc {
+                rsvps.reverse();
+            }
+            rsvps
+        });
+
+        tokio::spawn(async move {
+            match rsvps {
+                Ok(rsvps) => {
+                    for rsvp in rsvps {
+                        if tx.send(Ok(rsvp)).await.is_err() {
+                            // rx is dropped, so client disconnected
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                }
+            }
+        });
+
+        rx
+    }
+
+    async fn filter(
+        &self,
+        mut filter: abi::ReservationFilter,
+    ) -> Result<(FilterPager, Vec<abi::Reservation>), abi::Error> {
+        filter.tenant_id = self.tenant_id.clone();
+        filter.normalize()?;
+
+        let store = self.store.lock().unwrap();
+        let mut rsvps = filter.fetch_page(store.reservations.values());
+        let mut pager = filter.get_pager(&mut rsvps);
+        if filter.with_total {
+            let total = store
+                .reservations
+                .values()
+                .filter(|rsvp| filter.matches(rsvp))
+                .count();
+            pager.total = Some(total as i64);
+        }
+        Ok((pager, rsvps.into_iter().collect()))
+    }
+
+    async fn search(
+        &self,
+        mut search: abi::ReservationSearch,
+    ) -> Result<abi::SearchResponse, abi::Error> {
+        search.tenant_id = self.tenant_id.clone();
+        search.normalize()?;
+
+        let store = self.store.lock().unwrap();
+        let mut hits = search.hits(store.reservations.values());
+        let next_offset = search.next_offset(&mut hits);
+        Ok(abi::SearchResponse { hits, next_offset })
+    }
+
+    async fn listen(&self) -> mpsc::Receiver<Result<abi::ListenResponse, abi::Error>> {
+        let (tx, rx) = mpsc::channel(128);
+        // subscribe before returning, so the changes made right after are not missed
+        let mut changes = self.store.lock().unwrap().changes.subscribe();
+
+        let tenant_id = self.tenant_id.clone();
+        tokio::spawn(async move {
+            loop {
+                let change = tokio::select! {
+                    change = changes.recv() => change,
+                    _ = tx.closed() => return,
+                };
+                let change = match change {
+                    Ok(change) => change,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        let msg = format!("listener fell behind, {} changes were dropped", n);
+                        let _ = tx.send(Err(abi::Error::StorageError(msg))).await;
+                        return;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                let of_tenant = change
+                    .reservation
+                    .as_ref()
+                    .is_some_and(|rsvp| rsvp.tenant_id == tenant_id);
+                if of_tenant && tx.send(Ok(change)).await.is_err() {
+                    // rx is dropped, so client disconnected
+                    return;
+                }
+            }
+        });
+
+        rx
+    }
+}
+
+impl InMemoryReservationManager {
+    /// create an empty manager for the default tenant, use `with_tenant` to scope it to another tenant
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // apply the change to the reservation of the tenant (and of the owner if given) unless it fails,
+    // like an UPDATE ... WHERE. The change is sent as `op` to the listeners, like the trigger does
+    fn update(
+        &self,
+        id: ReservationId,
+        op: Option<ReservationUpdateType>,
+        owner: Option<UserId>,
+        f: impl FnOnce(&mut abi::Reservation) -> Result<(), abi::Error>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        let mut store = self.store.lock().unwrap();
+        let rsvp = match store.reservations.get_mut(&id) {
+            Some(rsvp) if rsvp.tenant_id == self.tenant_id => {
+                check_owner(rsvp, owner.as_deref())?;
+                f(rsvp)?;
+                rsvp.clone()
+            }
+            _ => return Err(abi::Error::NotFound),
+        };
+        if let Some(op) = op {
+            store.record(op, &rsvp);
+        }
+        Ok(rsvp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    rsvp_test_suite!(((), InMemoryReservationManager::new()));
+
+    #[tokio::test]
+    async fn listen_should_fail_once_too_far_behind() {
+        let manager = InMemoryReservationManager::new();
+        let mut rx = manager.listen().await;
+        let start = "2022-12-25T15:00:00-0700".parse::<chrono::DateTime<chrono::FixedOffset>>();
+        let start = start.unwrap();
+        for i in 0..CHANGES_CAPACITY + 1 {
+            let rsvp = abi::Reservation::new_pending(
+                "alice",
+                format!("room-{}", i),
+                start,
+                start + chrono::Duration::hours(1),
+                "",
+            );
+            manager.reserve(rsvp).await.unwrap();
+        }
+        let err = rx.recv().await.unwrap().unwrap_err();
+        assert!(matches!(err, abi::Error::StorageError(_)));
+        assert!(rx.recv().await.is_none());
+    }
+}
@@ -0,0 +1,706 @@
+use crate::{unchanged, Rsvp, SqliteReservationManager};
+use abi::{
+    convert_micros_to_utc_time, convert_to_micros, convert_to_timestamp, DbConfig, FilterPager,
+    Normalizer, ReservationConflict, ReservationId, ReservationQueryMode, ReservationSortBy,
+    UserId, Validator,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::StreamExt;
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    types::Json,
+    Executor, FromRow, QueryBuilder, Sqlite, SqlitePool,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    str::FromStr,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+static MIGRATOR: Migrator = sqlx::migrate!("../migrations/sqlite");
+/// how often listeners look for new changes
+const LISTEN_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, FromRow)]
+struct ReservationRow {
+    id: i64,
+    tenant_id: String,
+    user_id: String,
+    pending_user_id: Option<String>,
+    status: i32,
+    resource_id: String,
+    start_at: i64,
+    end_at: i64,
+    note: String,
+    labels: Json<HashMap<String, String>>,
+    created_at: i64,
+}
+
+#[derive(Debug, FromRow)]
+struct ChangeRow {
+    change_id: i64,
+    op: i32,
+    #[sqlx(flatten)]
+    reservation: ReservationRow,
+}
+
+#[async_trait]
+impl Rsvp for SqliteReservationManager {
+    fn with_tenant(&self, tenant_id: abi::TenantId) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            tenant_id,
+        }
+    }
+
+    async fn reserve(&self, mut rsvp: abi::Reservation) -> Result<abi::Reservation, abi::Error> {
+        rsvp.tenant_id = self.tenant_id.clone();
+        rsvp.validate()?;
+
+        let status = abi::ReservationStatus::from_i32(rsvp.status)
+            .unwrap_or(abi::ReservationStatus::Pending);
+        rsvp.status = status as i32;
+        let start = convert_to_micros(rsvp.start.as_ref().unwrap());
+        let end = convert_to_micros(rsvp.end.as_ref().unwrap());
+        let created_at = convert_to_micros(&convert_to_timestamp(&Utc::now()));
+
+        // insert first to take the write lock, so concurrent reservations are checked one by one
+        let mut tx = self.pool.begin().await?;
+        let (id,): (i64,) = sqlx::query_as(
+            "INSERT INTO reservations (tenant_id, user_id, status, resource_id, start_at, end_at, note, labels, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
+        )
+        .bind(&rsvp.tenant_id)
+        .bind(&rsvp.user_id)
+        .bind(rsvp.status)
+        .bind(&rsvp.resource_id)
+        .bind(start)
+        .bind(end)
+        .bind(&rsvp.note)
+        .bind(Json(&rsvp.labels))
+        .bind(created_at)
+        .fetch_one(&mut tx)
+        .await?;
+
+        let conflicts = conflicts(&mut tx, &rsvp, id).await?;
+        if !conflicts.is_empty() {
+            tx.rollback().await?;
+            let conflict = ReservationConflict::new(&rsvp, &conflicts);
+            return Err(abi::Error::ConflictReservation(conflict));
+        }
+        tx.commit().await?;
+
+        rsvp.id = id;
+        rsvp.created_at = Some(convert_to_timestamp(&convert_micros_to_utc_time(
+            created_at,
+        )));
+        Ok(rsvp)
+    }
+
+    async fn check(&self, mut rsvp: abi::Reservation) -> Result<abi::CheckResponse, abi::Error> {
+        rsvp.tenant_id = self.tenant_id.clone();
+        let conflicts = match rsvp.validate() {
+            Ok(_) => conflicts(&self.pool, &rsvp, 0).await?,
+            Err(_) => vec![],
+        };
+        Ok(abi::CheckResponse::new(&rsvp, &conflicts))
+    }
+
+    async fn change_status(
+        &self,
+        id: ReservationId,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        // if current status is pending, change it to confirmed, otherwise do nothing
+        id.validate()?;
+        let row: Option<ReservationRow> = sqlx::query_as(
+            "UPDATE reservations SET status = ?1 WHERE id = ?2 AND tenant_id = ?3 AND status = ?4 AND (?5 IS NULL OR user_id = ?5) RETURNING *",
+        )
+        .bind(abi::ReservationStatus::Confirmed as i32)
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(abi::ReservationStatus::Pending as i32)
+        .bind(&owner)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(row.into()),
+            None => Err(unchanged(self, id, owner.as_deref(), abi::Error::NotFound).await),
+        }
+    }
+
+    async fn update_note(
+        &self,
+        id: ReservationId,
+        note: String,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        let row: Option<ReservationRow> = sqlx::query_as(
+            "UPDATE reservations SET note = ?1 WHERE id = ?2 AND tenant_id = ?3 AND (?4 IS NULL OR user_id = ?4) RETURNING *",
+        )
+        .bind(note)
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(&owner)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(row.into()),
+            None => Err(unchanged(self, id, owner.as_deref(), abi::Error::NotFound).await),
+        }
+    }
+
+    async fn transfer(
+        &self,
+        id: ReservationId,
+        user_id: UserId,
+        require_acceptance: bool,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        if user_id.is_empty() {
+            return Err(abi::Error::InvalidUserId(user_id));
+        }
+
+        // the owner can't be the new owner, a reservation left alone is only found by its id
+        let sql = if require_acceptance {
+            "UPDATE reservations SET pending_user_id = ?1 WHERE id = ?2 AND tenant_id = ?3 AND user_id <> ?1 AND (?4 IS NULL OR user_id = ?4) RETURNING *"
+        } else {
+            "UPDATE reservations SET user_id = ?1, pending_user_id = NULL WHERE id = ?2 AND tenant_id = ?3 AND user_id <> ?1 AND (?4 IS NULL OR user_id = ?4) RETURNING *"
+        };
+        let row: Option<ReservationRow> = sqlx::query_as(sql)
+            .bind(&user_id)
+            .bind(id)
+            .bind(&self.tenant_id)
+            .bind(&owner)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(row.into()),
+            None => {
+                let err = abi::Error::InvalidUserId(user_id);
+                Err(unchanged(self, id, owner.as_deref(), err).await)
+            }
+        }
+    }
+
+    async fn accept_transfer(
+        &self,
+        id: ReservationId,
+        user_id: UserId,
+    ) -> Result<abi::Reservation, abi::Error> {
+        // if the reservation is pending transfer to the user, change the owner, otherwise not found
+        id.validate()?;
+        let row: ReservationRow = sqlx::query_as(
+            "UPDATE reservations SET user_id = pending_user_id, pending_user_id = NULL WHERE id = ? AND tenant_id = ? AND pending_user_id = ? RETURNING *",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    async fn get(&self, id: ReservationId) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        let row: ReservationRow =
+            sqlx::query_as("SELECT * FROM reservations WHERE id = ? AND tenant_id = ?")
+                .bind(id)
+                .bind(&self.tenant_id)
+                .fetch_one(&self.pool)
+                .await?;
+
+        Ok(row.into())
+    }
+
+    async fn delete(
+        &self,
+        id: ReservationId,
+        owner: Option<UserId>,
+    ) -> Result<abi::Reservation, abi::Error> {
+        id.validate()?;
+        let row: Option<ReservationRow> = sqlx::query_as(
+            "DELETE FROM reservations WHERE id = ?1 AND tenant_id = ?2 AND (?3 IS NULL OR user_id = ?3) RETURNING *",
+        )
+        .bind(id)
+        .bind(&self.tenant_id)
+        .bind(&owner)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(row.into()),
+            None => Err(unchanged(self, id, owner.as_deref(), abi::Error::NotFound).await),
+        }
+    }
+
+    async fn query(
+        &self,
+        mut query: abi::ReservationQuery,
+    ) -> mpsc::Receiver<Result<abi::Reservation, abi::Error>> {
+        let pool = self.pool.clone();
+        let (tx, rx) = mpsc::channel(128);
+        query.tenant_id = self.tenant_id.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = query.normalize() {
+                // nothing else to send, rx will be closed when tx is dropped
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+            let direction = if query.desc { "DESC" } else { "ASC" };
+            let mut builder = select_candidates(
+                "*",
+                &query.tenant_id,
+                &query.statuses,
+                &query.user_id,
+                &query.resource_id,
+            );
+            push_query_window(&mut builder, &query);
+            builder.push(format!(
+                " ORDER BY start_at {}, id {}",
+                direction, direction
+            ));
+
+            let mut rows = builder.build_query_as::<ReservationRow>().fetch(&pool);
+            while let Some(ret) = rows.next().await {
+                let ret = match ret {
+                    Ok(row) => {
+                        let rsvp: abi::Reservation = row.into();
+                        if !matches_labels(&query.label_selectors, &rsvp) {
+                            continue;
+                        }
+                        Ok(rsvp)
+                    }
+                    Err(e) => {
+                        warn!("Query error: {:?}", e);
+                        Err(e.into())
+                    }
+                };
+                if tx.send(ret).await.is_err() {
+                    // rx is dropped, so client disconnected
+                    break;
+                }
+            }
+        });
+
+        rx
+    }
+
+    async fn filter(
+        &self,
+        mut filter: abi::ReservationFilter,
+    ) -> Result<(FilterPager, Vec<abi::Reservation>), abi::Error> {
+        filter.tenant_id = self.tenant_id.clone();
+        filter.normalize()?;
+
+        let limit = filter.fetch_limit();
+        let mut builder = select_filtered("*", &filter);
+        push_cursor(&mut builder, &filter);
+        let direction = if filter.fetch_desc() { "DESC" } else { "ASC" };
+        match sort_column(filter.get_sort_by()) {
+            Some(column) => builder.push(format!(
+                " ORDER BY {} {}, id {}",
+                column, direction, direction
+            )),
+            None => builder.push(format!(" ORDER BY id {}", direction)),
+        };
+        // labels are checked in memory, so the rows can only be limited in sql without selectors
+        if filter.label_selectors.is_empty() {
+            builder.push(" LIMIT ").push_bind(limit);
+        }
+        let mut page = VecDeque::new();
+        let mut rows = builder.build_query_as::<ReservationRow>().fetch(&self.pool);
+        while let Some(row) = rows.next().await {
+            let rsvp: abi::Reservation = row?.into();
+            if matches_labels(&filter.label_selectors, &rsvp) {
+                page.push_back(rsvp);
+                if page.len() as i64 == limit {
+                    break;
+                }
+            }
+        }
+        // give the connection back before counting
+        drop(rows);
+
+        let mut pager = filter.get_pager(&mut page);
+        if filter.with_total {
+            pager.total = Some(self.count(&filter).await?);
+        }
+        Ok((pager, page.into_iter().collect()))
+    }
+
+    async fn search(
+        &self,
+        mut search: abi::ReservationSearch,
+    ) -> Result<abi::SearchResponse, abi::Error> {
+        search.tenant_id = self.tenant_id.clone();
+        search.normalize()?;
+
+        // the text is matched and ranked in memory
+        let rows: Vec<ReservationRow> = select_candidates(
+            "*",
+            &search.tenant_id,
+            &search.statuses,
+            &search.user_id,
+            &search.resource_id,
+        )
+        .build_query_as()
+        .fetch_all(&self.pool)
+        .await?;
+        let rsvps: Vec<abi::Reservation> = rows.into_iter().map(|row| row.into()).collect();
+        let mut hits = search.hits(&rsvps);
+        let next_offset = search.next_offset(&mut hits);
+        Ok(abi::SearchResponse { hits, next_offset })
+    }
+
+    async fn listen(&self) -> mpsc::Receiver<Result<abi::ListenResponse, abi::Error>> {
+        let (tx, rx) = mpsc::channel(128);
+
+        // find out where the changes start before returning, so the changes made right after are not missed
+        let last_id: Result<(i64,), _> =
+            sqlx::query_as("SELECT COALESCE(MAX(change_id), 0) FROM reservation_changes")
+                .fetch_one(&self.pool)
+                .await;
+        let (mut last_id,) = match last_id {
+            Ok(last_id) => last_id,
+            Err(e) => {
+                let _ = tx.send(Err(e.into())).await;
+                return rx;
+            }
+        };
+
+        let manager = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let rows: Result<Vec<ChangeRow>, _> = sqlx::query_as(
+                    "SELECT * FROM reservation_changes WHERE tenant_id = ? AND change_id > ? ORDER BY change_id",
+                )
+                .bind(&manager.tenant_id)
+                .bind(last_id)
+                .fetch_all(&manager.pool)
+                .await;
+                let rows = match rows {
+                    Ok(rows) => rows,
+                    Err(e) => {
+                        warn!("Listen error: {:?}", e);
+                        let _ = tx.send(Err(e.into())).await;
+                        return;
+                    }
+                };
+
+                for row in rows {
+                    last_id = row.change_id;
+                    let op = abi::ReservationUpdateType::from_i32(row.op)
+                        .unwrap_or(abi::ReservationUpdateType::Unknown);
+                    let change = abi::ListenResponse::new(op, row.reservation.into());
+                    if tx.send(Ok(change)).await.is_err() {
+                        // rx is dropped, so client disconnected
+                        return;
+                    }
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(LISTEN_INTERVAL) => {}
+                    _ = tx.closed() => return,
+                }
+            }
+        });
+
+        rx
+    }
+
+    async fn ping(&self) -> Result<(), abi::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+}
+
+impl SqliteReservationManager {
+    /// create a manager for the default tenant, the database shall be migrated already
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            tenant_id: abi::TenantId::default(),
+        }
+    }
+
+    /// open (or create) the database file and migrate it
+    pub async fn from_config(config: &DbConfig) -> Result<Self, abi::Error> {
+        let options = SqliteConnectOptions::from_str(&config.url())?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal);
+        let pool = SqlitePoolOptions::default()
+            .max_connections(config.max_connections)
+            .connect_with(options)
+            .await?;
+        Self::migrate(&pool).await?;
+        Ok(Self::new(pool))
+    }
+
+    pub async fn migrate(pool: &SqlitePool) -> Result<(), abi::Error> {
+        MIGRATOR.run(pool).await.map_err(sqlx::Error::from)?;
+        Ok(())
+    }
+
+    // all the reservations matched by the filter regardless of the page
+    async fn count(&self, filter: &abi::ReservationFilter) -> Result<i64, abi::Error> {
+        if filter.label_selectors.is_empty() {
+            let (total,): (i64,) = select_filtered("COUNT(*)", filter)
+                .build_query_as()
+                .fetch_one(&self.pool)
+                .await?;
+            return Ok(total);
+        }
+
+        let mut total = 0;
+        let mut builder = select_filtered("*", filter);
+        let mut rows = builder.build_query_as::<ReservationRow>().fetch(&self.pool);
+        while let Some(row) = rows.next().await {
+            if matches_labels(&filter.label_selectors, &row?.into()) {
+                total += 1;
+            }
+        }
+        Ok(total)
+    }
+}
+
+// reservations of the same resource overlapping the window of the reservation, except the one of `id`
+async fn conflicts<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    rsvp: &abi::Reservation,
+    id: ReservationId,
+) -> Result<Vec<abi::Reservation>, abi::Error> {
+    let rows: Vec<ReservationRow> = sqlx::query_as(
+        "SELECT * FROM reservations WHERE tenant_id = ? AND resource_id = ? AND start_at < ? AND ? < end_at AND id <> ? ORDER BY start_at",
+    )
+    .bind(&rsvp.tenant_id)
+    .bind(&rsvp.resource_id)
+    .bind(convert_to_micros(rsvp.end.as_ref().unwrap()))
+    .bind(convert_to_micros(rsvp.start.as_ref().unwrap()))
+    .bind(id)
+    .fetch_all(executor)
+    .await?;
+    Ok(rows.into_iter().map(|row| row.into()).collect())
+}
+
+// select the rows of the tenant narrowed by status, user and resource, an empty one matches all
+fn select_candidates<'a>(
+    columns: &str,
+    tenant_id: &'a str,
+    statuses: &'a [i32],
+    user_id: &'a str,
+    resource_id: &'a str,
+) -> QueryBuilder<'a, Sqlite> {
+    let mut builder = QueryBuilder::new(format!(
+        "SELECT {} FROM reservations WHERE tenant_id = ",
+        columns
+    ));
+    builder.push_bind(tenant_id);
+    if !statuses.is_empty() {
+        builder.push(" AND status IN (");
+        let mut separated = builder.separated(", ");
+        for status in statuses {
+            separated.push_bind(status);
+        }
+        builder.push(")");
+    }
+    if !user_id.is_empty() {
+        builder.push(" AND user_id = ").push_bind(user_id);
+    }
+    if !resource_id.is_empty() {
+        builder.push(" AND resource_id = ").push_bind(resource_id);
+    }
+    builder
+}
+
+// same as abi::ReservationQuery::to_sql, a missing bound is unbounded
+fn push_query_window(builder: &mut QueryBuilder<'_, Sqlite>, query: &abi::ReservationQuery) {
+    let start = query.start.as_ref().map(convert_to_micros);
+    let end = query.end.as_ref().map(convert_to_micros);
+    let (start_cond, end_cond) = match query.get_mode() {
+        ReservationQueryMode::Overlaps => ("end_at > ", "start_at < "),
+        ReservationQueryMode::Unspecified | ReservationQueryMode::ContainedIn => {
+            ("start_at >= ", "end_at <= ")
+        }
+        // a reservation never covers an unbounded window
+        ReservationQueryMode::Contains if start.is_none() || end.is_none() => {
+            builder.push(" AND FALSE");
+            return;
+        }
+        ReservationQueryMode::Contains => ("start_at <= ", "end_at >= "),
+        ReservationQueryMode::StartsWithin => ("start_at >= ", "start_at < "),
+    };
+    if let Some(start) = start {
+        builder
+            .push(format!(" AND {}", start_cond))
+            .push_bind(start);
+    }
+    if let Some(end) = end {
+        builder.push(format!(" AND {}", end_cond)).push_bind(end);
+    }
+}
+
+// same conditions as abi::ReservationFilter::to_count_sql, except the labels
+fn select_filtered<'a>(
+    columns: &str,
+    filter: &'a abi::ReservationFilter,
+) -> QueryBuilder<'a, Sqlite> {
+    let mut builder = select_candidates(
+        columns,
+        &filter.tenant_id,
+        &filter.statuses,
+        &filter.user_id,
+        &filter.resource_id,
+    );
+    if let Some(start) = filter.start.as_ref() {
+        builder
+            .push(" AND end_at > ")
+            .push_bind(convert_to_micros(start));
+    }
+    if let Some(end) = filter.end.as_ref() {
+        builder
+            .push(" AND start_at < ")
+            .push_bind(convert_to_micros(end));
+    }
+    builder
+}
+
+// the keyset of the page, (sort key, id) or id alone when sorted by id
+fn push_cursor(builder: &mut QueryBuilder<'_, Sqlite>, filter: &abi::ReservationFilter) {
+    let op = if filter.fetch_desc() { "<=" } else { ">=" };
+    match (
+        sort_column(filter.get_sort_by()),
+        filter.cursor,
+        filter.cursor_key,
+    ) {
+        (None, _, _) => {
+            builder
+                .push(format!(" AND id {} ", op))
+                .push_bind(filter.get_cursor());
+        }
+        (Some(column), Some(cursor), Some(cursor_key)) => {
+            builder
+                .push(format!(" AND ({}, id) {} (", column, op))
+                .push_bind(cursor_key)
+                .push(", ")
+                .push_bind(cursor)
+                .push(")");
+        }
+        // first page
+        _ => {}
+    }
+}
+
+// the column of the sort key, all in microseconds like abi::SortKey, none when sorted by id
+fn sort_column(sort_by: ReservationSortBy) -> Option<&'static str> {
+    match sort_by {
+        ReservationSortBy::Id => None,
+        ReservationSortBy::Start => Some("start_at"),
+        ReservationSortBy::End => Some("end_at"),
+        ReservationSortBy::CreatedAt => Some("created_at"),
+    }
+}
+
+// labels are stored as json, so they're checked in memory
+fn matches_labels(selectors: &[abi::LabelSelector], rsvp: &abi::Reservation) -> bool {
+    selectors
+        .iter()
+        .all(|selector| selector.matches(&rsvp.labels))
+}
+
+impl From<ReservationRow> for abi::Reservation {
+    fn from(row: ReservationRow) -> Self {
+        let timestamp = |micros| convert_to_timestamp(&convert_micros_to_utc_time(micros));
+        Self {
+            id: row.id,
+            user_id: row.user_id,
+            status: row.status,
+            resource_id: row.resource_id,
+            start: Some(timestamp(row.start_at)),
+            end: Some(timestamp(row.end_at)),
+            note: row.note,
+            pending_user_id: row.pending_user_id.unwrap_or_default(),
+            tenant_id: row.tenant_id,
+            labels: row.labels.0,
+            created_at: Some(timestamp(row.created_at)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::{Reservation, ReservationFilterBuilder};
+
+    rsvp_test_suite!({
+        // every connection of sqlite::memory: is a new database, so keep exactly one
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        SqliteReservationManager::migrate(&pool).await.unwrap();
+        ((), SqliteReservationManager::new(pool))
+    });
+
+    #[tokio::test]
+    async fn concurrent_reservations_should_not_overlap() {
+        let path = std::env::temp_dir().join(format!("rsvp-{}.db", std::process::id()));
+        let config = DbConfig {
+            driver: abi::DbDriver::Sqlite,
+            dbname: path.to_string_lossy().into(),
+            ..Default::default()
+        };
+        let manager = SqliteReservationManager::from_config(&config)
+            .await
+            .unwrap();
+
+        let tasks: Vec<_> = (0..10)
+            .map(|i| {
+                let manager = manager.clone();
+                tokio::spawn(async move {
+                    let rsvp = Reservation::new_pending(
+                        format!("user-{}", i),
+                        "ocean-view-room-713",
+                        "2022-12-25T15:00:00-0700".parse().unwrap(),
+                        "2022-12-28T12:00:00-0700".parse().unwrap(),
+                        "",
+                    );
+                    manager.reserve(rsvp).await
+                })
+            })
+            .collect();
+        let mut reserved = 0;
+        for task in tasks {
+            match task.await.unwrap() {
+                Ok(_) => reserved += 1,
+                Err(e) => assert!(matches!(e, abi::Error::ConflictReservation(_))),
+            }
+        }
+        assert_eq!(reserved, 1);
+
+        let filter = ReservationFilterBuilder::default()
+            .with_total(true)
+            .build()
+            .unwrap();
+        let (pager, _) = manager.filter(filter).await.unwrap();
+        assert_eq!(pager.total, Some(1));
+
+        manager.pool.close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.to_string_lossy(), suffix));
+        }
+    }
+}
@@ -0,0 +1,734 @@
+//! behaviors every `Rsvp` implementation shall have, run them with `rsvp_test_suite!`
+
+use crate::Rsvp;
+use abi::{
+    ConflictingReservation, LabelSelector, Reservation, ReservationConflict,
+    ReservationFilterBuilder, ReservationQuery, ReservationQueryBuilder, ReservationQueryMode,
+    ReservationSearchBuilder, ReservationSortBy, ReservationStatus, ReservationUpdateType,
+    ReservationWindow,
+};
+use prost_types::Timestamp;
+
+/// generate a test for each behavior. `$setup` is evaluated in every test and returns a guard to
+/// keep alive (e.g. the test db) and an empty manager
+macro_rules! rsvp_test_suite {
+    ($setup:expr) => {
+        rsvp_test_suite!(
+            $setup;
+            reserve_conflict_reservation_should_reject,
+            reserve_adjacent_window_should_work,
+            reserve_should_report_every_conflict,
+            check_should_report_problems_without_writing,
+            reserve_batch_should_report_each_reservation,
+            suggest_should_find_nearest_free_windows_and_resources,
+            import_should_report_each_event,
+            change_status_and_update_note_should_work,
+            changes_should_be_limited_to_owner,
+            transfer_with_acceptance_should_wait_for_new_owner,
+            tenants_should_not_see_each_other,
+            query_reservations_should_work,
+            filter_reservations_should_page_back_and_forth,
+            search_reservations_should_rank_and_highlight,
+            listen_should_receive_changes_of_the_tenant,
+            ping_should_reach_the_storage,
+        );
+    };
+    ($setup:expr; $($name:ident),* $(,)?) => {
+        mod rsvp_suite {
+            use super::*;
+
+            $(
+                #[tokio::test]
+                async fn $name() {
+                    let (_guard, manager) = $setup;
+                    $crate::test_suite::$name(manager).await;
+                }
+            )*
+        }
+    };
+}
+
+pub async fn reserve_conflict_reservation_should_reject(manager: impl Rsvp) {
+    let tyr = make_tyr_reservation(&manager).await;
+    let rsvp2 = Reservation::new_pending(
+        "aliceid",
+        "ocean-view-room-713",
+        "2022-12-26T15:00:00-0700".parse().unwrap(),
+        "2022-12-30T12:00:00-0700".parse().unwrap(),
+        "hello.",
+    );
+
+    let err = manager.reserve(rsvp2.clone()).await.unwrap_err();
+
+    let conflict = ReservationConflict {
+        new: ReservationWindow {
+            rid: "ocean-view-room-713".to_string(),
+            start: "2022-12-26T15:00:00-0700".parse().unwrap(),
+            end: "2022-12-30T12:00:00-0700".parse().unwrap(),
+        },
+        conflicts: vec![ConflictingReservation {
+            id: tyr.id,
+            user_id: "tyrid".to_string(),
+            window: ReservationWindow {
+                rid: "ocean-view-room-713".to_string(),
+                start: "2022-12-25T15:00:00-0700".parse().unwrap(),
+                end: "2022-12-28T12:00:00-0700".parse().unwrap(),
+            },
+            status: ReservationStatus::Pending,
+        }],
+    };
+    assert_eq!(err, abi::Error::ConflictReservation(conflict));
+
+    // other tenants have their own resources
+    let rsvp = manager.with_tenant("acme".into()).reserve(rsvp2).await;
+    assert!(rsvp.is_ok());
+}
+
+pub async fn reserve_adjacent_window_should_work(manager: impl Rsvp) {
+    let rsvp1 = make_tyr_reservation(&manager).await;
+    let rsvp2 = Reservation::new_pending(
+        "aliceid",
+        "ocean-view-room-713",
+        "2022-12-28T12:00:00-0700".parse().unwrap(),
+        "2022-12-30T12:00:00-0700".parse().unwrap(),
+        "hello.",
+    );
+
+    let rsvp2 = manager.reserve(rsvp2).await.unwrap();
+    assert!(rsvp2.id > rsvp1.id);
+    assert!(rsvp2.created_at.is_some());
+}
+
+pub async fn reserve_should_report_every_conflict(manager: impl Rsvp) {
+    let mut rsvps = vec![];
+    for (start, end) in [
+        ("2022-12-28T12:00:00-0700", "2022-12-29T12:00:00-0700"),
+        ("2022-12-25T12:00:00-0700", "2022-12-26T12:00:00-0700"),
+        // not overlapped, it ends right at the start
+        ("2022-12-24T12:00:00-0700", "2022-12-25T12:00:00-0700"),
+    ] {
+        let rsvp = make_reservation(&manager, "tyrid", "ixia-test-1", start, end, "").await;
+        rsvps.push(rsvp);
+    }
+    manager.change_status(rsvps[0].id, None).await.unwrap();
+
+    let rsvp = Reservation::new_pending(
+        "aliceid",
+        "ixia-test-1",
+        "2022-12-25T12:00:00-0700".parse().unwrap(),
+        "2022-12-31T12:00:00-0700".parse().unwrap(),
+        "",
+    );
+    let conflict = match manager.reserve(rsvp).await {
+        Err(abi::Error::ConflictReservation(conflict)) => conflict,
+        ret => panic!("expect conflict, got {:?}", ret),
+    };
+    let conflicts: Vec<_> = conflict
+        .conflicts
+        .iter()
+        .map(|c| (c.id, c.status))
+        .collect();
+    assert_eq!(
+        conflicts,
+        vec![
+            (rsvps[1].id, ReservationStatus::Pending),
+            (rsvps[0].id, ReservationStatus::Confirmed),
+        ]
+    );
+}
+
+pub async fn reserve_batch_should_report_each_reservation(manager: impl Rsvp + Sync) {
+    let rsvp = |uid: &str, start: &str, end: &str| {
+        Reservation::new_pending(
+            uid,
+            "ocean-view-room-713",
+            start.parse().unwrap(),
+            end.parse().unwrap(),
+            "",
+        )
+    };
+    let rsvps = vec![
+        rsvp(
+            "tyrid",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-28T12:00:00-0700",
+        ),
+        // conflicts with the one before it in the batch
+        rsvp(
+            "aliceid",
+            "2022-12-26T15:00:00-0700",
+            "2022-12-30T12:00:00-0700",
+        ),
+        rsvp("", "2023-01-25T15:00:00-0700", "2023-01-28T12:00:00-0700"),
+        rsvp(
+            "aliceid",
+            "2022-12-28T12:00:00-0700",
+            "2022-12-30T12:00:00-0700",
+        ),
+    ];
+
+    let results = manager.reserve_batch(rsvps).await.unwrap();
+    assert_eq!(results.len(), 4);
+    let tyr = results[0].as_ref().unwrap();
+    match &results[1] {
+        Err(abi::Error::ConflictReservation(conflict)) => {
+            assert_eq!(conflict.conflicts[0].id, tyr.id)
+        }
+        result => panic!("expect conflict, got {:?}", result),
+    }
+    assert_eq!(results[2], Err(abi::Error::InvalidUserId(String::new())));
+    let alice = results[3].as_ref().unwrap();
+    assert_eq!(manager.get(tyr.id).await.as_ref(), Ok(tyr));
+    assert_eq!(manager.get(alice.id).await.as_ref(), Ok(alice));
+}
+
+pub async fn check_should_report_problems_without_writing(manager: impl Rsvp) {
+    let tyr = make_tyr_reservation(&manager).await;
+    let mut rx = manager.listen().await;
+
+    let rsvp = Reservation::new_pending(
+        "aliceid",
+        "ocean-view-room-713",
+        "2022-12-28T12:00:00-0700".parse().unwrap(),
+        "2022-12-30T12:00:00-0700".parse().unwrap(),
+        "",
+    );
+    let ret = manager.check(rsvp.clone()).await.unwrap();
+    assert!(ret.ok);
+    assert!(ret.problems.is_empty());
+
+    let conflicting = Reservation {
+        start: tyr.start.clone(),
+        ..rsvp.clone()
+    };
+    let ret = manager.check(conflicting).await.unwrap();
+    assert!(!ret.ok);
+    assert_eq!(ret.problems.len(), 1);
+    assert_eq!(ret.problems[0].reason, "CONFLICT_RESERVATION");
+    assert_eq!(ret.problems[0].conflict.as_ref().unwrap().id, tyr.id);
+
+    // all the validation failures are reported, conflicts aren't looked up for them
+    let invalid = Reservation {
+        user_id: String::new(),
+        end: tyr.start.clone(),
+        ..rsvp.clone()
+    };
+    let ret = manager.check(invalid).await.unwrap();
+    let reasons: Vec<_> = ret.problems.iter().map(|p| p.reason.as_str()).collect();
+    assert_eq!(reasons, vec!["INVALID_USER_ID", "INVALID_TIME"]);
+    assert_eq!(ret.problems[0].field, "user_id");
+
+    // nothing is written, the first change is the reservation made afterwards
+    let rsvp = manager.reserve(rsvp).await.unwrap();
+    let change = rx.recv().await.unwrap().unwrap();
+    assert_eq!(change.reservation, Some(rsvp));
+}
+
+pub async fn suggest_should_find_nearest_free_windows_and_resources(manager: impl Rsvp + Sync) {
+    // far enough in the future to be suggested
+    make_reservation(
+        &manager,
+        "tyrid",
+        "ocean-view-room-713",
+        "2099-12-25T15:00:00-0700",
+        "2099-12-28T12:00:00-0700",
+        "",
+    )
+    .await;
+    make_reservation(
+        &manager,
+        "tyrid",
+        "ixia-test-1",
+        "2099-12-26T06:00:00-0700",
+        "2099-12-26T18:00:00-0700",
+        "",
+    )
+    .await;
+
+    let rsvp = Reservation::new_pending(
+        "aliceid",
+        "ocean-view-room-713",
+        "2099-12-26T12:00:00-0700".parse().unwrap(),
+        "2099-12-27T12:00:00-0700".parse().unwrap(),
+        "",
+    );
+    let pool = vec![
+        "ixia-test-1".to_string(),
+        "ocean-view-room-713".to_string(),
+        "ocean-view-room-714".to_string(),
+    ];
+    let ret = manager.suggest(rsvp.clone(), 2, pool).await.unwrap();
+
+    let windows: Vec<_> = ret
+        .slots
+        .iter()
+        .map(|s| (s.start.clone().unwrap(), s.end.clone().unwrap()))
+        .collect();
+    let ts = |s: &str| s.parse::<Timestamp>().unwrap();
+    assert_eq!(
+        windows,
+        vec![
+            // right before tyr's reservation, then right after it
+            (
+                ts("2099-12-24T15:00:00-0700"),
+                ts("2099-12-25T15:00:00-0700")
+            ),
+            (
+                ts("2099-12-28T12:00:00-0700"),
+                ts("2099-12-29T12:00:00-0700")
+            ),
+        ]
+    );
+    assert!(ret
+        .slots
+        .iter()
+        .all(|s| s.resource_id == "ocean-view-room-713"));
+    // ixia-test-1 is taken at the requested window
+    assert_eq!(ret.free_resources, vec!["ocean-view-room-714"]);
+
+    // nothing is suggested in the past
+    let past = Reservation::new_pending(
+        "aliceid",
+        "ocean-view-room-713",
+        "2022-12-26T12:00:00-0700".parse().unwrap(),
+        "2022-12-27T12:00:00-0700".parse().unwrap(),
+        "",
+    );
+    let ret = manager.suggest(past, 2, vec![]).await.unwrap();
+    assert!(ret.slots.is_empty());
+
+    let pool = (0..=abi::MAX_RESOURCE_POOL)
+        .map(|i| format!("room-{}", i))
+        .collect();
+    let err = manager.suggest(rsvp, 2, pool).await.unwrap_err();
+    assert_eq!(
+        err,
+        abi::Error::InvalidResourcePool(abi::MAX_RESOURCE_POOL as i64 + 1)
+    );
+}
+
+pub async fn import_should_report_each_event(manager: impl Rsvp + Sync) {
+    make_tyr_reservation(&manager).await;
+
+    let event = |uid: &str, organizer: &str, location: &str, start: &str, end: &str| {
+        format!(
+            "BEGIN:VEVENT\r\nUID:{uid}\r\nORGANIZER:mailto:{organizer}\r\nLOCATION:{location}\r\n\
+             DTSTART:{start}\r\nDTEND:{end}\r\nEND:VEVENT\r\n"
+        )
+    };
+    let calendar = [
+        "BEGIN:VCALENDAR\r\n".to_string(),
+        // overlaps the reservation of tyr
+        event(
+            "1",
+            "alice",
+            "ocean-view-room-713",
+            "20221226T000000Z",
+            "20221227T000000Z",
+        ),
+        event(
+            "2",
+            "alice",
+            "ixia-test-1",
+            "20221226T000000Z",
+            "20221227T000000Z",
+        ),
+        // overlaps the event before it
+        event(
+            "3",
+            "bob",
+            "ixia-test-1",
+            "20221226T120000Z",
+            "20221228T000000Z",
+        ),
+        event(
+            "4",
+            "",
+            "ixia-test-1",
+            "20221228T000000Z",
+            "20221229T000000Z",
+        ),
+        event(
+            "5",
+            "bob",
+            "ixia-test-1",
+            "20221228T000000Z",
+            "20221229T000000Z",
+        )
+        .replace("END:VEVENT", "STATUS:CANCELLED\r\nEND:VEVENT"),
+        "END:VCALENDAR\r\n".to_string(),
+    ]
+    .concat();
+    let mapping = abi::CalendarMapping {
+        users: [("alice".to_string(), "aliceid".to_string())].into(),
+        ..Default::default()
+    };
+    let outcomes = |results: &[abi::ImportResult]| -> Vec<abi::ImportOutcome> {
+        results.iter().map(|r| r.outcome()).collect()
+    };
+    let expected = vec![
+        abi::ImportOutcome::Conflict,
+        abi::ImportOutcome::Created,
+        abi::ImportOutcome::Conflict,
+        abi::ImportOutcome::Invalid,
+        abi::ImportOutcome::Skipped,
+    ];
+
+    let events = mapping.parse(&calendar).unwrap();
+    let results = manager.import(events, true).await.unwrap();
+    assert_eq!(outcomes(&results), expected);
+    assert_eq!(results[3].reason, "INVALID_USER_ID");
+    // the dry run writes nothing, so the conflicting event has no id
+    assert_eq!(results[2].conflicts[0].id, 0);
+    let query = ReservationQueryBuilder::default()
+        .resource_id("ixia-test-1")
+        .build()
+        .unwrap();
+    let mut rx = manager.query(query.clone()).await;
+    assert_eq!(rx.recv().await, None);
+
+    let events = mapping.parse(&calendar).unwrap();
+    let results = manager.import(events, false).await.unwrap();
+    assert_eq!(outcomes(&results), expected);
+    let created = results[1].reservation.clone().unwrap();
+    assert_eq!(created.user_id, "aliceid");
+    assert_eq!(created.labels[abi::ICS_UID_LABEL], "2");
+    assert_eq!(results[2].conflicts[0].id, created.id);
+    let mut rx = manager.query(query).await;
+    assert_eq!(rx.recv().await, Some(Ok(created)));
+    assert_eq!(rx.recv().await, None);
+}
+
+pub async fn change_status_and_update_note_should_work(manager: impl Rsvp) {
+    let rsvp = make_alice_reservation(&manager).await;
+
+    let rsvp = manager.change_status(rsvp.id, None).await.unwrap();
+    assert_eq!(rsvp.status, ReservationStatus::Confirmed as i32);
+    // change status again should do nothing
+    let err = manager.change_status(rsvp.id, None).await.unwrap_err();
+    assert_eq!(err, abi::Error::NotFound);
+
+    let rsvp = manager
+        .update_note(rsvp.id, "hello world".into(), None)
+        .await
+        .unwrap();
+    assert_eq!(rsvp.note, "hello world");
+    assert_eq!(manager.get(rsvp.id).await.unwrap(), rsvp);
+
+    assert_eq!(manager.delete(rsvp.id, None).await.unwrap(), rsvp);
+    assert_eq!(manager.get(rsvp.id).await, Err(abi::Error::NotFound));
+    assert_eq!(
+        manager.get(0).await,
+        Err(abi::Error::InvalidReservationId(0))
+    );
+}
+
+pub async fn changes_should_be_limited_to_owner(manager: impl Rsvp) {
+    let rsvp = make_alice_reservation(&manager).await;
+    let bob = || Some("bobid".to_string());
+    let denied = |ret: Result<Reservation, abi::Error>| {
+        assert!(matches!(ret, Err(abi::Error::PermissionDenied(_))));
+    };
+
+    denied(manager.change_status(rsvp.id, bob()).await);
+    denied(manager.update_note(rsvp.id, "mine".into(), bob()).await);
+    denied(
+        manager
+            .transfer(rsvp.id, "bobid".into(), false, bob())
+            .await,
+    );
+    denied(manager.delete(rsvp.id, bob()).await);
+    assert_eq!(manager.get(rsvp.id).await.unwrap(), rsvp);
+    // a missing reservation is still not found
+    let ret = manager.delete(rsvp.id + 100, bob()).await;
+    assert_eq!(ret, Err(abi::Error::NotFound));
+
+    let alice = || Some("aliceid".to_string());
+    let rsvp = manager.change_status(rsvp.id, alice()).await.unwrap();
+    assert_eq!(rsvp.status, ReservationStatus::Confirmed as i32);
+    let ret = manager.change_status(rsvp.id, alice()).await;
+    assert_eq!(ret, Err(abi::Error::NotFound));
+    let ret = manager
+        .transfer(rsvp.id, "aliceid".into(), true, alice())
+        .await;
+    assert_eq!(ret, Err(abi::Error::InvalidUserId("aliceid".into())));
+    let rsvp = manager
+        .transfer(rsvp.id, "bobid".into(), false, alice())
+        .await
+        .unwrap();
+
+    // once transferred, it's bob's to change
+    denied(manager.update_note(rsvp.id, "mine".into(), alice()).await);
+    manager
+        .update_note(rsvp.id, "mine".into(), bob())
+        .await
+        .unwrap();
+    manager.delete(rsvp.id, bob()).await.unwrap();
+}
+
+pub async fn transfer_with_acceptance_should_wait_for_new_owner(manager: impl Rsvp) {
+    let rsvp = make_alice_reservation(&manager).await;
+
+    let rsvp = manager
+        .transfer(rsvp.id, "bobid".into(), true, None)
+        .await
+        .unwrap();
+    assert_eq!(rsvp.user_id, "aliceid");
+    assert_eq!(rsvp.pending_user_id, "bobid");
+
+    let err = manager
+        .accept_transfer(rsvp.id, "tyrid".into())
+        .await
+        .unwrap_err();
+    assert_eq!(err, abi::Error::NotFound);
+
+    let rsvp = manager
+        .accept_transfer(rsvp.id, "bobid".into())
+        .await
+        .unwrap();
+    assert_eq!(rsvp.user_id, "bobid");
+    assert!(rsvp.pending_user_id.is_empty());
+
+    let rsvp = manager
+        .transfer(rsvp.id, "tyrid".into(), false, None)
+        .await
+        .unwrap();
+    assert_eq!(rsvp.user_id, "tyrid");
+
+    let err = manager
+        .transfer(rsvp.id, "".into(), false, None)
+        .await
+        .unwrap_err();
+    assert_eq!(err, abi::Error::InvalidUserId("".into()));
+
+    // not to the owner itself
+    for require_acceptance in [true, false] {
+        let err = manager
+            .transfer(rsvp.id, "tyrid".into(), require_acceptance, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::InvalidUserId("tyrid".into()));
+    }
+    assert_eq!(manager.get(rsvp.id).await.unwrap().pending_user_id, "");
+    let err = manager
+        .transfer(rsvp.id + 100, "aliceid".into(), false, None)
+        .await
+        .unwrap_err();
+    assert_eq!(err, abi::Error::NotFound);
+}
+
+pub async fn tenants_should_not_see_each_other(manager: impl Rsvp) {
+    let acme = manager.with_tenant("acme".into());
+    let rsvp = make_tyr_reservation(&acme).await;
+    assert_eq!(rsvp.tenant_id, "acme");
+
+    assert_eq!(manager.get(rsvp.id).await, Err(abi::Error::NotFound));
+    assert_eq!(
+        manager.delete(rsvp.id, None).await,
+        Err(abi::Error::NotFound)
+    );
+    let (_, rsvps) = manager
+        .filter(ReservationFilterBuilder::default().build().unwrap())
+        .await
+        .unwrap();
+    assert!(rsvps.is_empty());
+
+    let (_, rsvps) = acme
+        .filter(ReservationFilterBuilder::default().build().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(rsvps, vec![rsvp]);
+}
+
+pub async fn query_reservations_should_work(manager: impl Rsvp) {
+    let rsvp = make_alice_reservation(&manager).await;
+    let query = ReservationQueryBuilder::default()
+        .user_id("aliceid")
+        .start("2021-11-01T15:00:00-0700".parse::<Timestamp>().unwrap())
+        .end("2023-12-31T12:00:00-0700".parse::<Timestamp>().unwrap())
+        .status(ReservationStatus::Pending as i32)
+        .build()
+        .unwrap();
+    let mut rx = manager.query(query).await;
+    assert_eq!(rx.recv().await, Some(Ok(rsvp.clone())));
+    assert_eq!(rx.recv().await, None);
+
+    // the window starts after the reservation, so it's only overlapped
+    let query = ReservationQueryBuilder::default()
+        .start("2023-02-01T15:00:00-0700".parse::<Timestamp>().unwrap())
+        .mode(ReservationQueryMode::ContainedIn as i32)
+        .build()
+        .unwrap();
+    let mut rx = manager.query(query.clone()).await;
+    assert_eq!(rx.recv().await, None);
+    let query = ReservationQuery {
+        mode: ReservationQueryMode::Overlaps as i32,
+        ..query
+    };
+    let mut rx = manager.query(query).await;
+    assert_eq!(rx.recv().await, Some(Ok(rsvp)));
+
+    let query = ReservationQuery {
+        mode: 42,
+        ..Default::default()
+    };
+    let mut rx = manager.query(query).await;
+    assert_eq!(rx.recv().await, Some(Err(abi::Error::InvalidQueryMode(42))));
+}
+
+pub async fn filter_reservations_should_page_back_and_forth(manager: impl Rsvp) {
+    let mut rsvps = vec![];
+    for i in 0..25 {
+        let rsvp = Reservation::new_pending(
+            "tyr",
+            format!("router-{}", i),
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "",
+        )
+        .with_label("tier", if i % 2 == 0 { "db" } else { "cache" });
+        rsvps.push(manager.reserve(rsvp).await.unwrap());
+    }
+
+    let filter = ReservationFilterBuilder::default()
+        .user_id("tyr")
+        .sort_by(ReservationSortBy::CreatedAt as i32)
+        .desc(true)
+        .with_total(true)
+        .build()
+        .unwrap();
+    let (pager, page1) = manager.filter(filter.clone()).await.unwrap();
+    assert_eq!(pager.total, Some(25));
+    assert_eq!(page1.first(), rsvps.last());
+    assert_eq!(page1.len(), 10);
+
+    let filter = filter.next_page(&pager).unwrap();
+    let (pager, page2) = manager.filter(filter.clone()).await.unwrap();
+    assert_eq!(page2.first(), rsvps.get(14));
+
+    let filter = filter.prev_page(&pager).unwrap();
+    let (pager, page) = manager.filter(filter).await.unwrap();
+    assert_eq!(page, page1);
+    assert_eq!(pager.prev, None);
+
+    let filter = ReservationFilterBuilder::default()
+        .label_selectors(LabelSelector::parse_all("tier=cache").unwrap())
+        .with_total(true)
+        .build()
+        .unwrap();
+    let (pager, page) = manager.filter(filter.clone()).await.unwrap();
+    assert_eq!(pager.total, Some(12));
+    assert_eq!(page.len(), 10);
+    assert!(page.iter().all(|r| r.labels["tier"] == "cache"));
+    let filter = filter.next_page(&pager).unwrap();
+    let (pager, page) = manager.filter(filter).await.unwrap();
+    assert_eq!(page, vec![rsvps[21].clone(), rsvps[23].clone()]);
+    assert_eq!(pager.next, None);
+
+    // the window ends before the reservations
+    let filter = ReservationFilterBuilder::default()
+        .end("2022-12-26T15:00:00-0700".parse::<Timestamp>().unwrap())
+        .with_total(true)
+        .build()
+        .unwrap();
+    let (pager, page) = manager.filter(filter).await.unwrap();
+    assert_eq!(pager.total, Some(0));
+    assert!(page.is_empty());
+}
+
+pub async fn search_reservations_should_rank_and_highlight(manager: impl Rsvp) {
+    let tyr = make_tyr_reservation(&manager).await;
+    let alice = make_alice_reservation(&manager).await;
+
+    let search = ReservationSearchBuilder::default()
+        .text("upgrade room")
+        .build()
+        .unwrap();
+    let ret = manager.search(search).await.unwrap();
+    assert_eq!(ret.next_offset, None);
+    assert_eq!(ret.hits.len(), 1);
+    assert_eq!(ret.hits[0].reservation, Some(tyr));
+    assert!(ret.hits[0].snippet.contains("<b>upgrade</b>"));
+
+    let search = ReservationSearchBuilder::default()
+        .text("xyz project")
+        .status(ReservationStatus::Confirmed as i32)
+        .build()
+        .unwrap();
+    assert!(manager
+        .search(search.clone())
+        .await
+        .unwrap()
+        .hits
+        .is_empty());
+    manager.change_status(alice.id, None).await.unwrap();
+    assert_eq!(manager.search(search).await.unwrap().hits.len(), 1);
+}
+
+pub async fn listen_should_receive_changes_of_the_tenant(manager: impl Rsvp) {
+    let mut rx = manager.listen().await;
+
+    make_tyr_reservation(&manager.with_tenant("acme".into())).await;
+    let rsvp = make_alice_reservation(&manager).await;
+    manager
+        .update_note(rsvp.id, "hello".into(), None)
+        .await
+        .unwrap();
+    let confirmed = manager.change_status(rsvp.id, None).await.unwrap();
+    let deleted = manager.delete(rsvp.id, None).await.unwrap();
+
+    let mut changes = vec![];
+    for _ in 0..3 {
+        let change = rx.recv().await.unwrap().unwrap();
+        changes.push((change.op, change.reservation.unwrap()));
+    }
+    assert_eq!(
+        changes,
+        vec![
+            (ReservationUpdateType::Create as i32, rsvp),
+            (ReservationUpdateType::Update as i32, confirmed),
+            (ReservationUpdateType::Delete as i32, deleted),
+        ]
+    );
+}
+
+pub async fn ping_should_reach_the_storage(manager: impl Rsvp + Sync) {
+    manager.ping().await.unwrap();
+}
+
+async fn make_tyr_reservation(manager: &impl Rsvp) -> Reservation {
+    make_reservation(
+        manager,
+        "tyrid",
+        "ocean-view-room-713",
+        "2022-12-25T15:00:00-0700",
+        "2022-12-28T12:00:00-0700",
+        "I'll arrive at 3pm. Please help to upgrade to execuitive room if possible.",
+    )
+    .await
+}
+
+async fn make_alice_reservation(manager: &impl Rsvp) -> Reservation {
+    make_reservation(
+        manager,
+        "aliceid",
+        "ixia-test-1",
+        "2023-01-25T15:00:00-0700",
+        "2023-02-25T12:00:00-0700",
+        "I need to book this for xyz project for a month.",
+    )
+    .await
+}
+
+async fn make_reservation(
+    manager: &impl Rsvp,
+    uid: &str,
+    rid: &str,
+    start: &str,
+    end: &str,
+    note: &str,
+) -> Reservation {
+    let rsvp =
+        Reservation::new_pending(uid, rid, start.parse().unwrap(), end.parse().unwrap(), note);
+    manager.reserve(rsvp).await.unwrap()
+}
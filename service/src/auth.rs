@@ -0,0 +1,442 @@
+//! bearer JWTs of an OIDC issuer on the reservation rpcs. The `Authenticator` verifies the token
+//! with the issuer's JWKS, checks its issuer and audience, and puts the `Principal` of the caller
+//! into the request extensions. It's a tonic interceptor for grpc, and a middleware for the
+//! gateway. The JWKS is loaded again every `JWKS_REFRESH_INTERVAL`, and sooner when a token is
+//! signed by a key it doesn't have yet, so the issuer can rotate its keys
+
+use std::{
+    collections::HashSet,
+    fs,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use abi::{validate_tenant_id, AuthConfig};
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    extract::State,
+    http::{header, Request as HttpRequest},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hyper::{body, Client, Uri};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, Jwk, JwkSet},
+    DecodingKey, Validation,
+};
+use serde_json::{Map, Value};
+use tokio::sync::Notify;
+use tonic::{service::Interceptor, Request, Status};
+use tracing::{info, warn};
+
+use crate::gateway::ApiError;
+
+/// how often the JWKS is loaded again
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// the least time between two loads, however many tokens of unknown keys come
+const JWKS_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// a JWKS URL not answering by then fails the load, rather than holding the startup or the refresh
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// the authenticated caller
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub user_id: String,
+    /// the tenant the caller belongs to, the only one it can access
+    pub tenant_id: abi::TenantId,
+    /// service accounts may act on behalf of other users
+    pub service_account: bool,
+}
+
+#[derive(Clone)]
+pub struct Authenticator {
+    config: AuthConfig,
+    keys: Arc<RwLock<JwkSet>>,
+    refresh: Arc<Notify>,
+}
+
+impl Principal {
+    /// the user the call acts on behalf of: the caller if not given, others only for service
+    /// accounts
+    pub fn act_as(&self, user_id: &mut String) -> Result<(), abi::Error> {
+        if user_id.is_empty() {
+            *user_id = self.user_id.clone();
+        }
+        if *user_id == self.user_id || self.service_account {
+            return Ok(());
+        }
+        Err(abi::Error::PermissionDenied(format!(
+            "{} can't act on behalf of {}",
+            self.user_id, user_id
+        )))
+    }
+
+    /// the user whose reservations a bulk read returns: only the caller's own, a service account
+    /// reads everyone's if not given
+    pub fn read_as(&self, user_id: &mut String) -> Result<(), abi::Error> {
+        match self.service_account {
+            true => Ok(()),
+            false => self.act_as(user_id),
+        }
+    }
+
+    /// fail unless the caller may change the reservation: its owner, or a service account
+    pub fn check_owner(&self, rsvp: &abi::Reservation) -> Result<(), abi::Error> {
+        if rsvp.user_id == self.user_id || self.service_account {
+            return Ok(());
+        }
+        Err(abi::Error::PermissionDenied(format!(
+            "{} doesn't own reservation {}",
+            self.user_id, rsvp.id
+        )))
+    }
+
+    /// fail unless the caller may read the reservation: its owner, the user it's being transferred
+    /// to, or a service account
+    pub fn check_reader(&self, rsvp: &abi::Reservation) -> Result<(), abi::Error> {
+        match rsvp.pending_user_id == self.user_id && !self.user_id.is_empty() {
+            true => Ok(()),
+            false => self.check_owner(rsvp),
+        }
+    }
+}
+
+/// `act_as` of the principal of the request, anything goes if it has none (no auth configured)
+pub(crate) fn act_as<T>(request: &Request<T>, user_id: &mut String) -> Result<(), abi::Error> {
+    match request.extensions().get::<Principal>() {
+        Some(principal) => principal.act_as(user_id),
+        None => Ok(()),
+    }
+}
+
+/// `read_as` of the principal of the request, anything goes if it has none (no auth configured)
+pub(crate) fn read_as<T>(request: &Request<T>, user_id: &mut String) -> Result<(), abi::Error> {
+    match request.extensions().get::<Principal>() {
+        Some(principal) => principal.read_as(user_id),
+        None => Ok(()),
+    }
+}
+
+/// the owner a change of the request is limited to: the caller, none for a service account or if
+/// there's no principal (no auth configured)
+pub(crate) fn owner<T>(request: &Request<T>) -> Option<abi::UserId> {
+    request
+        .extensions()
+        .get::<Principal>()
+        .filter(|principal| !principal.service_account)
+        .map(|principal| principal.user_id.clone())
+}
+
+impl Authenticator {
+    /// load the JWKS, failing if it can't be loaded
+    pub async fn new(config: &AuthConfig) -> Result<Self> {
+        let keys = load_jwks(&config.jwks).await?;
+        Ok(Self::with_keys(config, keys))
+    }
+
+    pub fn with_keys(config: &AuthConfig, keys: JwkSet) -> Self {
+        Self {
+            config: config.clone(),
+            keys: Arc::new(RwLock::new(keys)),
+            refresh: Arc::new(Notify::new()),
+        }
+    }
+
+    /// load the JWKS again every `JWKS_REFRESH_INTERVAL`, or when asked for an unknown key. If it
+    /// fails, the current keys stay in use
+    pub fn watch(&self) {
+        let auth = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(JWKS_REFRESH_INTERVAL) => {},
+                    _ = auth.refresh.notified() => {},
+                }
+                match load_jwks(&auth.config.jwks).await {
+                    Ok(keys) => {
+                        *auth.keys.write().unwrap() = keys;
+                        info!("JWKS reloaded from {}", auth.config.jwks);
+                    }
+                    Err(e) => warn!("Failed to reload JWKS, keeping the current keys: {:#}", e),
+                }
+                tokio::time::sleep(JWKS_MIN_REFRESH_INTERVAL).await;
+            }
+        });
+    }
+
+    /// the caller of an `authorization` header value, `Bearer <jwt>`
+    pub fn principal(&self, authorization: Option<&str>) -> Result<Principal, abi::Error> {
+        let token = authorization
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or_else(|| unauthenticated("missing bearer token"))?;
+        let header = decode_header(token).map_err(|e| unauthenticated(e.to_string()))?;
+        let key = self.key(header.kid.as_deref())?;
+        if matches!(key.algorithm, AlgorithmParameters::OctetKey(_))
+            || key.common.algorithm.is_some_and(|alg| alg != header.alg)
+        {
+            return Err(unauthenticated("token algorithm doesn't match the key"));
+        }
+        let key = DecodingKey::from_jwk(&key).map_err(|e| unauthenticated(e.to_string()))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.audience]);
+        let claims = decode::<Map<String, Value>>(token, &key, &validation)
+            .map_err(|e| unauthenticated(e.to_string()))?
+            .claims;
+
+        let user_id = match claims.get(&self.config.user_claim) {
+            Some(Value::String(user_id)) if !user_id.is_empty() => user_id.clone(),
+            _ => {
+                let msg = format!("missing {} claim", self.config.user_claim);
+                return Err(unauthenticated(msg));
+            }
+        };
+        let tenant_id = match claims.get(&self.config.tenant_claim) {
+            Some(Value::String(tenant_id)) => tenant_id.clone(),
+            None => abi::TenantId::default(),
+            Some(_) => {
+                let msg = format!("invalid {} claim", self.config.tenant_claim);
+                return Err(unauthenticated(msg));
+            }
+        };
+        validate_tenant_id(&tenant_id).map_err(|e| unauthenticated(e.to_string()))?;
+        Ok(Principal {
+            user_id,
+            tenant_id,
+            service_account: scopes(&claims).contains(self.config.service_scope.as_str()),
+        })
+    }
+
+    /// the key of the id, the only key if the token doesn't name one
+    fn key(&self, kid: Option<&str>) -> Result<Jwk, abi::Error> {
+        let keys = self.keys.read().unwrap();
+        let key = match kid {
+            Some(kid) => keys.find(kid),
+            None if keys.keys.len() == 1 => keys.keys.first(),
+            None => return Err(unauthenticated("missing key id")),
+        };
+        match key {
+            Some(key) => Ok(key.clone()),
+            None => {
+                // the issuer may have rotated its keys
+                self.refresh.notify_one();
+                Err(unauthenticated("unknown signing key"))
+            }
+        }
+    }
+}
+
+impl Interceptor for Authenticator {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let authorization = request
+            .metadata()
+            .get(header::AUTHORIZATION.as_str())
+            .and_then(|v| v.to_str().ok());
+        let principal = self.principal(authorization)?;
+        request.extensions_mut().insert(principal);
+        Ok(request)
+    }
+}
+
+/// authenticate the gateway calls like the grpc ones
+pub(crate) async fn http_middleware<B>(
+    State(auth): State<Authenticator>,
+    mut request: HttpRequest<B>,
+    next: Next<B>,
+) -> Response {
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    match auth.principal(authorization) {
+        Ok(principal) => {
+            request.extensions_mut().insert(principal);
+            next.run(request).await
+        }
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+/// the scopes of the `scope` claim (space separated) or the `scp` claim (a list)
+fn scopes(claims: &Map<String, Value>) -> HashSet<&str> {
+    let mut scopes = HashSet::new();
+    if let Some(Value::String(scope)) = claims.get("scope") {
+        scopes.extend(scope.split_whitespace());
+    }
+    match claims.get("scp") {
+        Some(Value::Array(scp)) => scopes.extend(scp.iter().filter_map(|v| v.as_str())),
+        Some(Value::String(scp)) => scopes.extend(scp.split_whitespace()),
+        _ => {}
+    }
+    scopes
+}
+
+fn unauthenticated(msg: impl Into<String>) -> abi::Error {
+    abi::Error::Unauthenticated(msg.into())
+}
+
+/// the JWKS of a file or an http(s) URL
+async fn load_jwks(source: &str) -> Result<JwkSet> {
+    let content = if source.starts_with("https://") || source.starts_with("http://") {
+        fetch(source).await?
+    } else {
+        fs::read(source).with_context(|| format!("failed to read {}", source))?
+    };
+    serde_json::from_slice(&content).with_context(|| format!("invalid JWKS in {}", source))
+}
+
+async fn fetch(url: &str) -> Result<Vec<u8>> {
+    tokio::time::timeout(JWKS_FETCH_TIMEOUT, fetch_once(url))
+        .await
+        .map_err(|_| anyhow!("failed to fetch {}: timed out", url))?
+}
+
+async fn fetch_once(url: &str) -> Result<Vec<u8>> {
+    let uri: Uri = url.parse()?;
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let res = Client::builder()
+        .build::<_, hyper::Body>(connector)
+        .get(uri)
+        .await
+        .with_context(|| format!("failed to fetch {}", url))?;
+    if !res.status().is_success() {
+        return Err(anyhow!("failed to fetch {}: {}", url, res.status()));
+    }
+    Ok(body::to_bytes(res.into_body()).await?.to_vec())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use serde_json::json;
+
+    pub const ISSUER: &str = "https://issuer.example.com";
+    pub const AUDIENCE: &str = "reservation";
+
+    pub fn auth_config() -> AuthConfig {
+        AuthConfig {
+            jwks: "fixtures/auth/jwks.json".into(),
+            issuer: ISSUER.into(),
+            audience: AUDIENCE.into(),
+            user_claim: "sub".into(),
+            tenant_claim: "tenant_id".into(),
+            service_scope: "reservation:on_behalf".into(),
+        }
+    }
+
+    /// a token signed by the key of the fixture JWKS, with the claims over the default ones
+    pub fn token(claims: Value) -> String {
+        let mut all = json!({
+            "iss": ISSUER,
+            "aud": AUDIENCE,
+            "sub": "tyr",
+            "exp": chrono::Utc::now().timestamp() + 600,
+        });
+        all.as_object_mut()
+            .unwrap()
+            .extend(claims.as_object().unwrap().clone());
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("test-key".into());
+        let key = EncodingKey::from_rsa_pem(&fs::read("fixtures/auth/jwt.key").unwrap()).unwrap();
+        format!("Bearer {}", encode(&header, &all, &key).unwrap())
+    }
+
+    #[tokio::test]
+    async fn authenticator_should_verify_tokens() {
+        let auth = Authenticator::new(&auth_config()).await.unwrap();
+
+        let principal = auth.principal(Some(&token(json!({})))).unwrap();
+        assert_eq!(principal.user_id, "tyr");
+        assert_eq!(principal.tenant_id, "");
+        assert!(!principal.service_account);
+
+        let principal = auth
+            .principal(Some(&token(json!({ "tenant_id": "acme" }))))
+            .unwrap();
+        assert_eq!(principal.tenant_id, "acme");
+
+        let claims = json!({ "sub": "booking-bot", "scope": "openid reservation:on_behalf" });
+        let principal = auth.principal(Some(&token(claims))).unwrap();
+        assert_eq!(principal.user_id, "booking-bot");
+        assert!(principal.service_account);
+
+        let rejected = [
+            None,
+            Some("Basic dHlyOg==".to_string()),
+            Some(token(json!({ "iss": "https://evil.example.com" }))),
+            Some(token(json!({ "aud": "other" }))),
+            Some(token(
+                json!({ "exp": chrono::Utc::now().timestamp() - 600 }),
+            )),
+            Some(token(json!({ "sub": "" }))),
+            Some(token(json!({ "tenant_id": 42 }))),
+            Some(token(json!({ "tenant_id": "a b" }))),
+            Some(format!("{}x", token(json!({})))),
+        ];
+        for authorization in rejected {
+            let ret = auth.principal(authorization.as_deref());
+            assert!(
+                matches!(ret, Err(abi::Error::Unauthenticated(_))),
+                "{:?} is accepted",
+                authorization
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn interceptor_should_put_principal_into_extensions() {
+        let mut auth = Authenticator::new(&auth_config()).await.unwrap();
+        let status = auth.call(Request::new(())).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let mut request = Request::new(());
+        let token = token(json!({})).parse().unwrap();
+        request.metadata_mut().insert("authorization", token);
+        let request = auth.call(request).unwrap();
+        let mut user_id = String::new();
+        act_as(&request, &mut user_id).unwrap();
+        assert_eq!(user_id, "tyr");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn jwks_fetch_should_time_out() {
+        // accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/jwks.json", listener.local_addr().unwrap());
+        let err = load_jwks(&url).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[test]
+    fn principal_should_act_as_itself_unless_service_account() {
+        let user = Principal {
+            user_id: "tyr".into(),
+            tenant_id: Default::default(),
+            service_account: false,
+        };
+        let mut user_id = String::new();
+        user.act_as(&mut user_id).unwrap();
+        assert_eq!(user_id, "tyr");
+        let mut user_id = "alice".to_string();
+        assert!(matches!(
+            user.act_as(&mut user_id),
+            Err(abi::Error::PermissionDenied(_))
+        ));
+
+        let service = Principal {
+            user_id: "booking-bot".into(),
+            tenant_id: Default::default(),
+            service_account: true,
+        };
+        service.act_as(&mut user_id).unwrap();
+        assert_eq!(user_id, "alice");
+    }
+}
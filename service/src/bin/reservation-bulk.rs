@@ -0,0 +1,197 @@
+//! export reservations as CSV or JSON Lines, and import them back in chunks, e.g.
+//!
+//! ```bash
+//! reservation-bulk --tenant acme export --format jsonl --resource-id room-1 -o room-1.jsonl
+//! reservation-bulk --tenant acme import --format jsonl -i room-1.jsonl --rejected rejected.jsonl
+//! ```
+
+use abi::{
+    convert_to_timestamp, validate_tenant_id, Config, DbDriver, LabelSelector, ReservationFilter,
+    ReservationQuery, ReservationStatus, StorageBackend, TenantId,
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use reservation::{EmbeddedReservationManager, ReservationManager, Rsvp};
+use reservation_service::{
+    bulk::{self, Format, RecordWriter},
+    find_config_file,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+#[derive(Debug, Parser)]
+#[command(about = "export and import reservations in bulk")]
+struct Cli {
+    /// config file, found like the server does if not given
+    #[arg(short, long, env = "RESERVATION_CONFIG")]
+    config: Option<String>,
+    /// tenant of the reservations
+    #[arg(short, long, default_value = "")]
+    tenant: TenantId,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// write the reservations of a query to a file, or stdout
+    Export(ExportArgs),
+    /// reserve every valid row of a file, rows that fail are written to the rejected file
+    Import(ImportArgs),
+}
+
+#[derive(Debug, Args)]
+struct ExportArgs {
+    /// csv or jsonl
+    #[arg(short, long, default_value = "csv")]
+    format: Format,
+    /// stdout if not given
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    #[arg(long, default_value = "")]
+    user_id: String,
+    #[arg(long, default_value = "")]
+    resource_id: String,
+    /// pending, confirmed or blocked, all if not given
+    #[arg(long)]
+    status: Vec<ReservationStatus>,
+    /// RFC 3339, only the reservations overlapping with start..end are exported
+    #[arg(long)]
+    start: Option<DateTime<Utc>>,
+    #[arg(long)]
+    end: Option<DateTime<Utc>>,
+    /// label selector like `env=prod` or `team in (a, b)`
+    #[arg(short, long)]
+    label: Vec<LabelSelector>,
+    /// page through a filter ordered by id, rather than streaming a query ordered by start
+    #[arg(long)]
+    paged: bool,
+    #[arg(long, default_value_t = 100)]
+    page_size: i64,
+}
+
+#[derive(Debug, Args)]
+struct ImportArgs {
+    /// csv or jsonl
+    #[arg(short, long, default_value = "csv")]
+    format: Format,
+    /// stdin if not given
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// rejected rows in the format of the input, with their line and error
+    #[arg(long)]
+    rejected: PathBuf,
+    /// rows reserved and committed together
+    #[arg(long, default_value_t = 500)]
+    chunk_size: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let filename = cli
+        .config
+        .clone()
+        .or_else(find_config_file)
+        .ok_or_else(|| anyhow!("no config file found"))?;
+    let config = Config::load(filename)?;
+    validate_tenant_id(&cli.tenant)?;
+
+    match config.storage.backend {
+        StorageBackend::Db => match config.db.driver {
+            DbDriver::Postgres => {
+                let manager = ReservationManager::from_config(&config.db).await?;
+                run(manager, cli).await
+            }
+            #[cfg(feature = "sqlite")]
+            DbDriver::Sqlite => {
+                let manager =
+                    reservation::SqliteReservationManager::from_config(&config.db).await?;
+                run(manager, cli).await
+            }
+            #[cfg(not(feature = "sqlite"))]
+            DbDriver::Sqlite => Err(anyhow!(
+                "sqlite is not supported, please build with the sqlite feature"
+            )),
+        },
+        StorageBackend::Embedded => {
+            let path = shellexpand::tilde(&config.storage.path);
+            let manager = EmbeddedReservationManager::open(path.as_ref())?;
+            run(manager, cli).await
+        }
+        StorageBackend::Memory => Err(anyhow!(
+            "the memory backend keeps nothing to export or import into"
+        )),
+    }
+}
+
+async fn run<R: Rsvp + Sync>(manager: R, cli: Cli) -> Result<()> {
+    let manager = manager.with_tenant(cli.tenant);
+    match cli.command {
+        Command::Export(args) => export(&manager, args).await,
+        Command::Import(args) => import(&manager, args).await,
+    }
+}
+
+async fn export<R: Rsvp>(manager: &R, args: ExportArgs) -> Result<()> {
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut writer = RecordWriter::new(args.format, output);
+    let statuses = args.status.iter().map(|s| *s as i32).collect();
+    let start = args.start.as_ref().map(convert_to_timestamp);
+    let end = args.end.as_ref().map(convert_to_timestamp);
+
+    let count = if args.paged {
+        let filter = ReservationFilter {
+            user_id: args.user_id,
+            resource_id: args.resource_id,
+            statuses,
+            start,
+            end,
+            label_selectors: args.label,
+            page_size: args.page_size,
+            ..Default::default()
+        };
+        bulk::export_filter(manager, filter, &mut writer).await?
+    } else {
+        let query = ReservationQuery {
+            user_id: args.user_id,
+            resource_id: args.resource_id,
+            statuses,
+            start,
+            end,
+            label_selectors: args.label,
+            ..Default::default()
+        };
+        bulk::export_query(manager, query, &mut writer).await?
+    };
+    eprintln!("exported {} reservations", count);
+    Ok(())
+}
+
+async fn import<R: Rsvp + Sync>(manager: &R, args: ImportArgs) -> Result<()> {
+    let rejected = BufWriter::new(File::create(&args.rejected)?);
+    let summary = match &args.input {
+        Some(path) => {
+            let input = BufReader::new(File::open(path)?);
+            bulk::import(manager, args.format, input, rejected, args.chunk_size).await?
+        }
+        None => {
+            let input = io::stdin().lock();
+            bulk::import(manager, args.format, input, rejected, args.chunk_size).await?
+        }
+    };
+    eprintln!(
+        "created {} reservations, rejected {} rows, see {}",
+        summary.created,
+        summary.rejected,
+        args.rejected.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,643 @@
+//! bulk export and import of reservations as CSV or JSON Lines, see the `reservation-bulk` tool
+
+use abi::{
+    convert_to_timestamp, convert_to_utc_time, Reservation, ReservationFilter, ReservationQuery,
+    ReservationStatus, Validator,
+};
+use anyhow::anyhow;
+use chrono::{DateTime, SecondsFormat, Utc};
+use reservation::Rsvp;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Read, Write},
+    str::FromStr,
+};
+
+/// reason of the rows that can't be read as a reservation at all
+const INVALID_ROW: &str = "INVALID_ROW";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    /// JSON Lines, a JSON object per line
+    Jsonl,
+}
+
+/// a reservation as a flat row. CSV has no maps, so the labels are a JSON object in text there.
+/// id, tenant_id, pending_user_id and created_at are exported for reference, the import ignores
+/// them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReservationRecord<L = BTreeMap<String, String>> {
+    pub id: i64,
+    pub tenant_id: String,
+    pub user_id: String,
+    pub resource_id: String,
+    /// pending, confirmed or blocked, pending if empty
+    pub status: String,
+    /// RFC 3339
+    pub start: String,
+    pub end: String,
+    pub note: String,
+    pub pending_user_id: String,
+    pub labels: L,
+    pub created_at: String,
+}
+
+/// number of rows of an import
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub rejected: usize,
+}
+
+/// writes reservations one by one, nothing is buffered but the writer itself
+pub struct RecordWriter<W: Write> {
+    inner: WriterInner<W>,
+}
+
+enum WriterInner<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Jsonl(W),
+}
+
+/// a row of the input, with what's needed to write it back if rejected
+struct Row {
+    line: u64,
+    raw: RawRow,
+    record: Result<Reservation, RowError>,
+}
+
+/// the rows of the input, read one by one
+type Rows<'a> = Box<dyn Iterator<Item = Result<Row, anyhow::Error>> + 'a>;
+
+enum RawRow {
+    Csv(csv::StringRecord),
+    Jsonl(String),
+}
+
+/// why a row is rejected
+struct RowError {
+    reason: String,
+    description: String,
+}
+
+/// rejected rows in the format of the input, with the line, reason and description of the error.
+/// For CSV they are the first three columns
+enum RejectedWriter<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Jsonl(W),
+}
+
+#[derive(Serialize)]
+struct RejectedJsonRow<'a> {
+    line: u64,
+    reason: &'a str,
+    error: &'a str,
+    row: &'a str,
+}
+
+/// write every reservation of the query as it arrives, returns the number of reservations
+pub async fn export_query<R: Rsvp, W: Write>(
+    manager: &R,
+    query: ReservationQuery,
+    writer: &mut RecordWriter<W>,
+) -> Result<usize, anyhow::Error> {
+    let mut rx = manager.query(query).await;
+    let mut count = 0;
+    while let Some(rsvp) = rx.recv().await {
+        writer.write(&rsvp?)?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// write every page of the filter, returns the number of reservations
+pub async fn export_filter<R: Rsvp, W: Write>(
+    manager: &R,
+    mut filter: ReservationFilter,
+    writer: &mut RecordWriter<W>,
+) -> Result<usize, anyhow::Error> {
+    let mut count = 0;
+    loop {
+        let (pager, rsvps) = manager.filter(filter.clone()).await?;
+        for rsvp in &rsvps {
+            writer.write(rsvp)?;
+        }
+        count += rsvps.len();
+        match pager.next {
+            Some(next) => {
+                filter.cursor = Some(next);
+                filter.cursor_key = pager.next_key;
+            }
+            None => break,
+        }
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// validate every row and reserve the valid ones `chunk_size` rows at a time, each chunk is
+/// committed on its own. Invalid and conflicting rows are written to `rejected`
+pub async fn import<R, I, W>(
+    manager: &R,
+    format: Format,
+    input: I,
+    rejected: W,
+    chunk_size: usize,
+) -> Result<ImportSummary, anyhow::Error>
+where
+    R: Rsvp + Sync,
+    I: Read,
+    W: Write,
+{
+    let chunk_size = chunk_size.max(1);
+    let mut summary = ImportSummary::default();
+    let (rows, headers) = read_rows(format, input)?;
+    let mut rejected = RejectedWriter::new(format, rejected, headers.as_ref())?;
+
+    let mut chunk = Vec::with_capacity(chunk_size);
+    for row in rows {
+        let mut row = row?;
+        match row.record.as_ref().map(Validator::validate) {
+            Ok(Err(e)) => row.record = Err(e.into()),
+            Ok(Ok(_)) => {
+                chunk.push(row);
+                if chunk.len() == chunk_size {
+                    reserve_chunk(manager, &mut chunk, &mut rejected, &mut summary).await?;
+                }
+                continue;
+            }
+            Err(_) => {}
+        }
+        rejected.write(&row)?;
+        summary.rejected += 1;
+    }
+    reserve_chunk(manager, &mut chunk, &mut rejected, &mut summary).await?;
+    rejected.flush()?;
+    Ok(summary)
+}
+
+async fn reserve_chunk<R: Rsvp + Sync, W: Write>(
+    manager: &R,
+    chunk: &mut Vec<Row>,
+    rejected: &mut RejectedWriter<W>,
+    summary: &mut ImportSummary,
+) -> Result<(), anyhow::Error> {
+    if chunk.is_empty() {
+        return Ok(());
+    }
+    let rows = std::mem::take(chunk);
+    let rsvps = rows
+        .iter()
+        .filter_map(|row| row.record.as_ref().ok().cloned())
+        .collect();
+    let results = manager.reserve_batch(rsvps).await?;
+    for (mut row, result) in rows.into_iter().zip(results) {
+        match result {
+            Ok(_) => summary.created += 1,
+            Err(e) => {
+                row.record = Err(e.into());
+                rejected.write(&row)?;
+                summary.rejected += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// the rows of the input, and the headers for CSV
+fn read_rows<'a, I: Read + 'a>(
+    format: Format,
+    input: I,
+) -> Result<(Rows<'a>, Option<csv::StringRecord>), anyhow::Error> {
+    match format {
+        Format::Csv => {
+            // rows with missing or extra fields are reported like other invalid rows
+            let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(input);
+            let headers = reader.headers()?.clone();
+            let rows_headers = headers.clone();
+            let rows = reader.into_records().map(move |record| {
+                let record = record?;
+                let line = record.position().map_or(0, |p| p.line());
+                let parsed = record
+                    .deserialize::<ReservationRecord<String>>(Some(&rows_headers))
+                    .map_err(|e| RowError::invalid_row(e.to_string()))
+                    .and_then(|r| Ok(ReservationRecord::from_csv(r)?.try_into()?));
+                Ok(Row {
+                    line,
+                    raw: RawRow::Csv(record),
+                    record: parsed,
+                })
+            });
+            Ok((Box::new(rows), Some(headers)))
+        }
+        Format::Jsonl => {
+            let rows = BufReader::new(input)
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !matches!(line, Ok(l) if l.trim().is_empty()))
+                .map(|(i, line)| {
+                    let line_text = line?;
+                    let parsed = serde_json::from_str::<ReservationRecord>(&line_text)
+                        .map_err(|e| RowError::invalid_row(e.to_string()))
+                        .and_then(|r| Ok(r.try_into()?));
+                    Ok(Row {
+                        line: i as u64 + 1,
+                        raw: RawRow::Jsonl(line_text),
+                        record: parsed,
+                    })
+                });
+            Ok((Box::new(rows), None))
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "jsonl" | "ndjson" => Ok(Format::Jsonl),
+            _ => Err(anyhow!("unknown format {}, expect csv or jsonl", s)),
+        }
+    }
+}
+
+impl From<&Reservation> for ReservationRecord {
+    fn from(rsvp: &Reservation) -> Self {
+        let time = |ts: Option<&prost_types::Timestamp>| {
+            ts.map(|ts| convert_to_utc_time(ts).to_rfc3339_opts(SecondsFormat::AutoSi, true))
+                .unwrap_or_default()
+        };
+        let status = ReservationStatus::from_i32(rsvp.status).unwrap_or(ReservationStatus::Unknown);
+        Self {
+            id: rsvp.id,
+            tenant_id: rsvp.tenant_id.clone(),
+            user_id: rsvp.user_id.clone(),
+            resource_id: rsvp.resource_id.clone(),
+            status: status.to_string(),
+            start: time(rsvp.start.as_ref()),
+            end: time(rsvp.end.as_ref()),
+            note: rsvp.note.clone(),
+            pending_user_id: rsvp.pending_user_id.clone(),
+            labels: rsvp.labels.clone().into_iter().collect(),
+            created_at: time(rsvp.created_at.as_ref()),
+        }
+    }
+}
+
+/// the reservation to create from the row, the columns only for reference are left out
+impl TryFrom<ReservationRecord> for Reservation {
+    type Error = abi::Error;
+
+    fn try_from(record: ReservationRecord) -> Result<Self, Self::Error> {
+        let time = |s: &str| -> Result<_, abi::Error> {
+            let dt = DateTime::parse_from_rfc3339(s.trim()).map_err(|_| abi::Error::InvalidTime)?;
+            Ok(Some(convert_to_timestamp(&dt.with_timezone(&Utc))))
+        };
+        let status = match record.status.trim() {
+            "" => ReservationStatus::Pending,
+            status => status.parse()?,
+        };
+        Ok(Reservation {
+            user_id: record.user_id,
+            resource_id: record.resource_id,
+            status: status as i32,
+            start: time(&record.start)?,
+            end: time(&record.end)?,
+            note: record.note,
+            labels: record.labels.into_iter().collect(),
+            ..Default::default()
+        })
+    }
+}
+
+impl ReservationRecord {
+    fn into_csv(self) -> ReservationRecord<String> {
+        let labels = match self.labels.is_empty() {
+            true => String::new(),
+            false => serde_json::to_string(&self.labels).unwrap(),
+        };
+        ReservationRecord {
+            id: self.id,
+            tenant_id: self.tenant_id,
+            user_id: self.user_id,
+            resource_id: self.resource_id,
+            status: self.status,
+            start: self.start,
+            end: self.end,
+            note: self.note,
+            pending_user_id: self.pending_user_id,
+            labels,
+            created_at: self.created_at,
+        }
+    }
+
+    fn from_csv(record: ReservationRecord<String>) -> Result<Self, abi::Error> {
+        let labels = match record.labels.trim() {
+            "" => BTreeMap::new(),
+            text => serde_json::from_str(text)
+                .map_err(|_| abi::Error::InvalidLabel(record.labels.clone()))?,
+        };
+        Ok(ReservationRecord {
+            id: record.id,
+            tenant_id: record.tenant_id,
+            user_id: record.user_id,
+            resource_id: record.resource_id,
+            status: record.status,
+            start: record.start,
+            end: record.end,
+            note: record.note,
+            pending_user_id: record.pending_user_id,
+            labels,
+            created_at: record.created_at,
+        })
+    }
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(format: Format, writer: W) -> Self {
+        let inner = match format {
+            Format::Csv => WriterInner::Csv(Box::new(csv::Writer::from_writer(writer))),
+            Format::Jsonl => WriterInner::Jsonl(writer),
+        };
+        Self { inner }
+    }
+
+    pub fn write(&mut self, rsvp: &Reservation) -> Result<(), anyhow::Error> {
+        let record = ReservationRecord::from(rsvp);
+        match &mut self.inner {
+            WriterInner::Csv(writer) => writer.serialize(record.into_csv())?,
+            WriterInner::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, &record)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), anyhow::Error> {
+        match &mut self.inner {
+            WriterInner::Csv(writer) => writer.flush()?,
+            WriterInner::Jsonl(writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> RejectedWriter<W> {
+    fn new(
+        format: Format,
+        writer: W,
+        headers: Option<&csv::StringRecord>,
+    ) -> Result<Self, anyhow::Error> {
+        Ok(match (format, headers) {
+            (Format::Csv, Some(headers)) => {
+                let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(writer);
+                // the error goes first, the row may have missing or extra fields
+                let mut header = csv::StringRecord::from(vec!["line", "reason", "error"]);
+                header.extend(headers);
+                writer.write_record(&header)?;
+                RejectedWriter::Csv(Box::new(writer))
+            }
+            _ => RejectedWriter::Jsonl(writer),
+        })
+    }
+
+    fn write(&mut self, row: &Row) -> Result<(), anyhow::Error> {
+        let e = match &row.record {
+            Err(e) => e,
+            Ok(_) => return Ok(()),
+        };
+        match (self, &row.raw) {
+            (RejectedWriter::Csv(writer), RawRow::Csv(record)) => {
+                let line = row.line.to_string();
+                let mut rejected =
+                    csv::StringRecord::from(vec![line.as_str(), &e.reason, &e.description]);
+                rejected.extend(record);
+                writer.write_record(&rejected)?;
+            }
+            (RejectedWriter::Jsonl(writer), RawRow::Jsonl(text)) => {
+                let rejected = RejectedJsonRow {
+                    line: row.line,
+                    reason: &e.reason,
+                    error: &e.description,
+                    row: text,
+                };
+                serde_json::to_writer(&mut *writer, &rejected)?;
+                writer.write_all(b"\n")?;
+            }
+            _ => unreachable!("rejected rows are written in the format of the input"),
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), anyhow::Error> {
+        match self {
+            RejectedWriter::Csv(writer) => writer.flush()?,
+            RejectedWriter::Jsonl(writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl RowError {
+    fn invalid_row(description: String) -> Self {
+        Self {
+            reason: INVALID_ROW.to_string(),
+            description,
+        }
+    }
+}
+
+impl From<abi::Error> for RowError {
+    fn from(e: abi::Error) -> Self {
+        Self {
+            reason: e.reason().to_string(),
+            description: e.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reservation::InMemoryReservationManager;
+
+    async fn seed(manager: &InMemoryReservationManager) {
+        let rsvps = [
+            (
+                "tyr",
+                "room-1",
+                "2022-12-26T15:00:00-0700",
+                "2022-12-27T12:00:00-0700",
+            ),
+            (
+                "tyr",
+                "room-2",
+                "2022-12-26T15:00:00-0700",
+                "2022-12-27T12:00:00-0700",
+            ),
+            (
+                "alice",
+                "room-1",
+                "2022-12-27T15:00:00-0700",
+                "2022-12-28T12:00:00-0700",
+            ),
+        ];
+        for (i, (user_id, resource_id, start, end)) in rsvps.into_iter().enumerate() {
+            let mut rsvp = Reservation::new_pending(
+                user_id,
+                resource_id,
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "note, with \"quotes\"",
+            );
+            rsvp.labels.insert("seq".into(), i.to_string());
+            rsvp.status = ReservationStatus::Confirmed as i32;
+            manager.reserve(rsvp).await.unwrap();
+        }
+    }
+
+    async fn all(manager: &InMemoryReservationManager) -> Vec<ReservationRecord> {
+        let mut rx = manager.query(ReservationQuery::default()).await;
+        let mut records = vec![];
+        while let Some(rsvp) = rx.recv().await {
+            let mut record = ReservationRecord::from(&rsvp.unwrap());
+            record.id = 0;
+            record.created_at = String::new();
+            records.push(record);
+        }
+        records
+    }
+
+    #[tokio::test]
+    async fn export_then_import_should_round_trip() {
+        let manager = InMemoryReservationManager::new();
+        seed(&manager).await;
+
+        for format in [Format::Csv, Format::Jsonl] {
+            let mut output = RecordWriter::new(format, vec![]);
+            let count = export_query(&manager, ReservationQuery::default(), &mut output)
+                .await
+                .unwrap();
+            assert_eq!(count, 3);
+            let exported = match output.inner {
+                WriterInner::Csv(writer) => writer.into_inner().unwrap(),
+                WriterInner::Jsonl(writer) => writer,
+            };
+
+            let imported = InMemoryReservationManager::new();
+            let mut rejected = vec![];
+            let summary = import(&imported, format, &exported[..], &mut rejected, 2)
+                .await
+                .unwrap();
+            assert_eq!(
+                summary,
+                ImportSummary {
+                    created: 3,
+                    rejected: 0
+                }
+            );
+            assert_eq!(all(&imported).await, all(&manager).await);
+        }
+
+        for i in 0..10 {
+            let start: DateTime<chrono::FixedOffset> = "2023-01-01T00:00:00Z".parse().unwrap();
+            let start = start + chrono::Duration::days(i);
+            let end = start + chrono::Duration::hours(1);
+            let rsvp = Reservation::new_pending("bob", "room-3", start, end, "");
+            manager.reserve(rsvp).await.unwrap();
+        }
+        let filter = ReservationFilter {
+            page_size: 10,
+            ..Default::default()
+        };
+        let mut output = RecordWriter::new(Format::Jsonl, vec![]);
+        let count = export_filter(&manager, filter, &mut output).await.unwrap();
+        assert_eq!(count, 13);
+    }
+
+    #[tokio::test]
+    async fn import_should_reject_invalid_and_conflicting_rows() {
+        let manager = InMemoryReservationManager::new();
+        let input = "\
+user_id,resource_id,start,end,note
+tyr,room-1,2022-12-26T15:00:00-07:00,2022-12-27T12:00:00-07:00,ok
+tyr,room-1,tomorrow,2022-12-27T12:00:00-07:00,bad time
+alice,room-1,2022-12-26T16:00:00-07:00,2022-12-26T18:00:00-07:00,conflict
+,room-2,2022-12-26T15:00:00-07:00,2022-12-27T12:00:00-07:00,no user
+bob,room-2,2022-12-26T15:00:00-07:00,2022-12-27T12:00:00-07:00,
+carol,room-3
+";
+        let mut rejected = vec![];
+        let summary = import(&manager, Format::Csv, input.as_bytes(), &mut rejected, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                created: 2,
+                rejected: 4
+            }
+        );
+
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(&rejected[..]);
+        let headers = reader.headers().unwrap().clone();
+        assert_eq!(
+            headers.iter().take(3).collect::<Vec<_>>(),
+            ["line", "reason", "error"]
+        );
+        let rows: Vec<_> = reader
+            .records()
+            .map(|r| {
+                let r = r.unwrap();
+                (
+                    r[0].to_string(),
+                    r[1].to_string(),
+                    r.get(7).unwrap_or("").to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("3".into(), "INVALID_TIME".into(), "bad time".into()),
+                ("4".into(), "CONFLICT_RESERVATION".into(), "conflict".into()),
+                ("5".into(), "INVALID_USER_ID".into(), "no user".into()),
+                ("7".into(), "INVALID_ROW".into(), "".into()),
+            ]
+        );
+
+        let input = "{\"user_id\": 1}\n\n{\"user_id\": \"tyr\", \"resource_id\": \"room-3\", \
+            \"start\": \"2022-12-26T15:00:00Z\", \"end\": \"2022-12-27T12:00:00Z\", \
+            \"status\": \"done\"}\n";
+        let mut rejected = vec![];
+        let summary = import(&manager, Format::Jsonl, input.as_bytes(), &mut rejected, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                created: 0,
+                rejected: 2
+            }
+        );
+        let rejected = String::from_utf8(rejected).unwrap();
+        let rows: Vec<serde_json::Value> = rejected
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(rows[0]["line"], 1);
+        assert_eq!(rows[0]["reason"], INVALID_ROW);
+        assert_eq!(rows[0]["row"], "{\"user_id\": 1}");
+        assert_eq!(rows[1]["line"], 3);
+        assert_eq!(rows[1]["reason"], "INVALID_STATUS");
+    }
+}
@@ -0,0 +1,631 @@
+//! HTTP/JSON gateway of the rpcs, for clients that can't speak grpc. Every rpc is a
+//! `POST /v1/<rpc>` with the JSON of the request message as body, and the tenant in the
+//! `x-tenant-id` header like the grpc metadata. `query` and `listen` stream NDJSON, or Server-Sent
+//! Events if the client accepts `text/event-stream`. The OpenAPI document is at `/openapi.json`.
+//! With auth configured, the rpcs need the same bearer token as the grpc ones
+
+use std::{convert::Infallible, sync::Arc};
+
+use abi::{
+    reservation_service_server::ReservationService, AcceptTransferRequest, AcceptTransferResponse,
+    CalendarMapping, CancelRequest, CancelResponse, CheckRequest, CheckResponse, ConfirmRequest,
+    ConfirmResponse, ExportCalendarRequest, ExportCalendarResponse, FilterPager, FilterRequest,
+    FilterResponse, FreeSlot, GetRequest, GetResponse, ImportCalendarRequest,
+    ImportCalendarResponse, ImportResult, LabelSelector, ListenRequest, ListenResponse,
+    QueryRequest, Reservation, ReservationFilter, ReservationProblem, ReservationQuery,
+    ReservationSearch, ReserveRequest, ReserveResponse, SearchHit, SearchRequest, SearchResponse,
+    SuggestRequest, SuggestResponse, TransferRequest, TransferResponse, UpdateRequest,
+    UpdateResponse,
+};
+use axum::{
+    body::StreamBody,
+    extract::{rejection::JsonRejection, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{self, post},
+    Extension, Json, Router,
+};
+use futures::{future, Stream, StreamExt};
+use reservation::Rsvp;
+use serde::Serialize;
+use tonic::{metadata::MetadataMap, Code, Request, Status};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    auth::{http_middleware, Authenticator, Principal},
+    http::status_code,
+    RsvpService,
+};
+
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const EVENT_STREAM_CONTENT_TYPE: &str = "text/event-stream";
+
+/// body of an error response, and the data of an `error` event of a stream
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// HTTP status code
+    pub code: u16,
+    /// machine readable reason, e.g. CONFLICT_RESERVATION
+    pub reason: String,
+    pub message: String,
+}
+
+/// an error line of an NDJSON stream, the stream ends after it
+#[derive(Serialize)]
+struct StreamError {
+    error: ErrorBody,
+}
+
+/// an error of the rpc as an HTTP response
+pub struct ApiError {
+    status: StatusCode,
+    body: ErrorBody,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "reservation",
+        description = "HTTP/JSON gateway of the reservation service, the grpc rpcs as JSON"
+    ),
+    paths(
+        reserve,
+        check,
+        suggest,
+        confirm,
+        update,
+        cancel,
+        transfer,
+        accept_transfer,
+        get,
+        query,
+        export_calendar,
+        import_calendar,
+        filter,
+        search,
+        listen
+    ),
+    components(schemas(
+        Reservation,
+        LabelSelector,
+        ReserveRequest,
+        ReserveResponse,
+        CheckRequest,
+        CheckResponse,
+        ReservationProblem,
+        SuggestRequest,
+        SuggestResponse,
+        FreeSlot,
+        ConfirmRequest,
+        ConfirmResponse,
+        UpdateRequest,
+        UpdateResponse,
+        CancelRequest,
+        CancelResponse,
+        TransferRequest,
+        TransferResponse,
+        AcceptTransferRequest,
+        AcceptTransferResponse,
+        GetRequest,
+        GetResponse,
+        QueryRequest,
+        ReservationQuery,
+        ExportCalendarRequest,
+        ExportCalendarResponse,
+        ImportCalendarRequest,
+        ImportCalendarResponse,
+        CalendarMapping,
+        ImportResult,
+        FilterRequest,
+        FilterResponse,
+        ReservationFilter,
+        FilterPager,
+        SearchRequest,
+        SearchResponse,
+        ReservationSearch,
+        SearchHit,
+        ListenRequest,
+        ListenResponse,
+        ErrorBody
+    ))
+)]
+struct ApiDoc;
+
+pub fn router<R>(service: Arc<RsvpService<R>>, auth: Option<Authenticator>) -> Router
+where
+    R: Rsvp + Send + Sync + 'static,
+{
+    let router = Router::new()
+        .route("/v1/reserve", post(reserve::<R>))
+        .route("/v1/check", post(check::<R>))
+        .route("/v1/suggest", post(suggest::<R>))
+        .route("/v1/confirm", post(confirm::<R>))
+        .route("/v1/update", post(update::<R>))
+        .route("/v1/cancel", post(cancel::<R>))
+        .route("/v1/transfer", post(transfer::<R>))
+        .route("/v1/accept_transfer", post(accept_transfer::<R>))
+        .route("/v1/get", post(get::<R>))
+        .route("/v1/query", post(query::<R>))
+        .route("/v1/export_calendar", post(export_calendar::<R>))
+        .route("/v1/import_calendar", post(import_calendar::<R>))
+        .route("/v1/filter", post(filter::<R>))
+        .route("/v1/search", post(search::<R>))
+        .route("/v1/listen", post(listen::<R>));
+    // the OpenAPI document stays public
+    let router = match auth {
+        Some(auth) => router.route_layer(middleware::from_fn_with_state(auth, http_middleware)),
+        None => router,
+    };
+    router
+        .route("/openapi.json", routing::get(openapi_json))
+        .with_state(service)
+}
+
+/// the OpenAPI document of the gateway
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
+async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(openapi())
+}
+
+/// handlers of the unary rpcs, the request and response messages as they are
+macro_rules! unary {
+    ($($(#[doc = $doc:literal])* $rpc:ident($path:literal, $req:ty) -> $resp:ty;)*) => {
+        $(
+            $(#[doc = $doc])*
+            #[utoipa::path(
+                post,
+                path = $path,
+                request_body = $req,
+                params(("x-tenant-id" = Option<String>, Header, description = "tenant of the call")),
+                responses(
+                    (status = 200, body = $resp),
+                    (status = "default", body = ErrorBody)
+                )
+            )]
+            async fn $rpc<R: Rsvp + Send + Sync + 'static>(
+                State(service): State<Arc<RsvpService<R>>>,
+                principal: Option<Extension<Principal>>,
+                headers: HeaderMap,
+                body: Result<Json<$req>, JsonRejection>,
+            ) -> Result<Json<$resp>, ApiError> {
+                let Json(body) = body?;
+                let response = service.$rpc(grpc_request(headers, principal, body)).await?;
+                Ok(Json(response.into_inner()))
+            }
+        )*
+    };
+}
+
+unary! {
+    /// make a reservation
+    reserve("/v1/reserve", ReserveRequest) -> ReserveResponse;
+    /// check a reservation without making it
+    check("/v1/check", CheckRequest) -> CheckResponse;
+    /// suggest other windows and resources for a reservation
+    suggest("/v1/suggest", SuggestRequest) -> SuggestResponse;
+    /// confirm a pending reservation
+    confirm("/v1/confirm", ConfirmRequest) -> ConfirmResponse;
+    /// update the reservation note
+    update("/v1/update", UpdateRequest) -> UpdateResponse;
+    /// cancel a reservation
+    cancel("/v1/cancel", CancelRequest) -> CancelResponse;
+    /// transfer a reservation to another user
+    transfer("/v1/transfer", TransferRequest) -> TransferResponse;
+    /// accept a pending transfer
+    accept_transfer("/v1/accept_transfer", AcceptTransferRequest) -> AcceptTransferResponse;
+    /// get a reservation by id
+    get("/v1/get", GetRequest) -> GetResponse;
+    /// export the reservations of the query as an iCalendar
+    export_calendar("/v1/export_calendar", ExportCalendarRequest) -> ExportCalendarResponse;
+    /// create reservations from the events of an iCalendar
+    import_calendar("/v1/import_calendar", ImportCalendarRequest) -> ImportCalendarResponse;
+    /// filter reservations page by page
+    filter("/v1/filter", FilterRequest) -> FilterResponse;
+    /// full text search over reservation notes
+    search("/v1/search", SearchRequest) -> SearchResponse;
+}
+
+/// query reservations, a reservation per NDJSON line or event
+#[utoipa::path(
+    post,
+    path = "/v1/query",
+    request_body = QueryRequest,
+    params(("x-tenant-id" = Option<String>, Header, description = "tenant of the call")),
+    responses(
+        (status = 200, body = Reservation, content_type = ["application/x-ndjson", "text/event-stream"]),
+        (status = "default", body = ErrorBody)
+    )
+)]
+async fn query<R: Rsvp + Send + Sync + 'static>(
+    State(service): State<Arc<RsvpService<R>>>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+    body: Result<Json<QueryRequest>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(body) = body?;
+    let event_stream = accepts_event_stream(&headers);
+    let stream = service
+        .query(grpc_request(headers, principal, body))
+        .await?;
+    Ok(stream_response(stream.into_inner(), event_stream))
+}
+
+/// listen to the changes of reservations, a change per NDJSON line or event
+#[utoipa::path(
+    post,
+    path = "/v1/listen",
+    request_body = ListenRequest,
+    params(("x-tenant-id" = Option<String>, Header, description = "tenant of the call")),
+    responses(
+        (status = 200, body = ListenResponse, content_type = ["application/x-ndjson", "text/event-stream"]),
+        (status = "default", body = ErrorBody)
+    )
+)]
+async fn listen<R: Rsvp + Send + Sync + 'static>(
+    State(service): State<Arc<RsvpService<R>>>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+    body: Result<Json<ListenRequest>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(body) = body?;
+    let event_stream = accepts_event_stream(&headers);
+    let stream = service
+        .listen(grpc_request(headers, principal, body))
+        .await?;
+    Ok(stream_response(stream.into_inner(), event_stream))
+}
+
+/// the message with the headers as metadata, so the tenant and the like get to the rpc, and the
+/// principal the auth middleware found like the grpc interceptor does
+fn grpc_request<T>(
+    headers: HeaderMap,
+    principal: Option<Extension<Principal>>,
+    message: T,
+) -> Request<T> {
+    let mut request = Request::new(message);
+    *request.metadata_mut() = MetadataMap::from_headers(headers);
+    if let Some(Extension(principal)) = principal {
+        request.extensions_mut().insert(principal);
+    }
+    request
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.contains(EVENT_STREAM_CONTENT_TYPE))
+}
+
+/// the items of the stream as NDJSON lines or events, an error is the last of them like in grpc
+fn stream_response<T, S>(stream: S, event_stream: bool) -> Response
+where
+    T: Serialize + Send + 'static,
+    S: Stream<Item = Result<T, Status>> + Send + 'static,
+{
+    let stream = stream.scan(false, |failed, item| {
+        if *failed {
+            return future::ready(None);
+        }
+        *failed = item.is_err();
+        future::ready(Some(item.map_err(|e| ApiError::from(e).body)))
+    });
+
+    if event_stream {
+        let events = stream.map(|item| {
+            let event = match item {
+                Ok(item) => Event::default().json_data(item),
+                Err(e) => Event::default().event("error").json_data(e),
+            };
+            Ok::<_, Infallible>(event.unwrap_or_else(|e| Event::default().comment(e.to_string())))
+        });
+        return Sse::new(events)
+            .keep_alive(KeepAlive::default())
+            .into_response();
+    }
+
+    let lines = stream.map(|item| {
+        let mut line = match item {
+            Ok(item) => serde_json::to_vec(&item),
+            Err(error) => serde_json::to_vec(&StreamError { error }),
+        }
+        .unwrap_or_default();
+        line.push(b'\n');
+        Ok::<_, Infallible>(line)
+    });
+    (
+        [(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)],
+        StreamBody::new(lines),
+    )
+        .into_response()
+}
+
+impl ApiError {
+    fn new(status: StatusCode, reason: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: ErrorBody {
+                code: status.as_u16(),
+                reason: reason.into(),
+                message: message.into(),
+            },
+        }
+    }
+}
+
+/// decode the status back into the error of the service, a status without the error details
+/// (e.g. a missing message) is mapped by its code
+impl From<Status> for ApiError {
+    fn from(status: Status) -> Self {
+        let code = status.code();
+        let message = status.message().to_string();
+        match abi::Error::from(status) {
+            abi::Error::Unknown if code != Code::Unknown => {
+                let (status, reason) = match code {
+                    Code::InvalidArgument | Code::OutOfRange | Code::FailedPrecondition => {
+                        (StatusCode::BAD_REQUEST, "INVALID_ARGUMENT")
+                    }
+                    Code::Unauthenticated => (StatusCode::UNAUTHORIZED, "UNAUTHENTICATED"),
+                    Code::PermissionDenied => (StatusCode::FORBIDDEN, "PERMISSION_DENIED"),
+                    Code::AlreadyExists | Code::Aborted => (StatusCode::CONFLICT, "ABORTED"),
+                    Code::ResourceExhausted => {
+                        (StatusCode::TOO_MANY_REQUESTS, "RESOURCE_EXHAUSTED")
+                    }
+                    Code::Unimplemented => (StatusCode::NOT_IMPLEMENTED, "UNIMPLEMENTED"),
+                    Code::Unavailable => (StatusCode::SERVICE_UNAVAILABLE, "UNAVAILABLE"),
+                    Code::DeadlineExceeded => (StatusCode::GATEWAY_TIMEOUT, "DEADLINE_EXCEEDED"),
+                    _ => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL"),
+                };
+                Self::new(status, reason, message)
+            }
+            e => Self::new(status_code(&e), e.reason(), message),
+        }
+    }
+}
+
+impl From<abi::Error> for ApiError {
+    fn from(e: abi::Error) -> Self {
+        Self::new(status_code(&e), e.reason(), e.to_string())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection.status(), "INVALID_JSON", rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::tests::{auth_config, token},
+        service::TENANT_ID_KEY,
+    };
+    use abi::ServerConfig;
+    use axum::{body::Body, http::Request as HttpRequest};
+    use reservation::InMemoryReservationManager;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    fn gateway() -> Router {
+        let config = ServerConfig {
+            host: "127.0.0.1".into(),
+            port: 0,
+            page_token_secret: "secret".into(),
+            allow_raw_cursor: false,
+            http_port: None,
+            gateway_port: None,
+            cors_origins: vec![],
+            tls: None,
+            auth: None,
+        };
+        let service = RsvpService::new(InMemoryReservationManager::new(), &config);
+        router(Arc::new(service), None)
+    }
+
+    async fn post_json(
+        router: &Router,
+        path: &str,
+        accept: &str,
+        body: Value,
+    ) -> (StatusCode, String) {
+        let request = HttpRequest::post(path)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, accept)
+            .header(TENANT_ID_KEY, "acme")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn reservation(resource_id: &str, start: &str, end: &str) -> Value {
+        json!({
+            "reservation": {
+                "user_id": "tyr",
+                "resource_id": resource_id,
+                "status": "RESERVATION_STATUS_PENDING",
+                "start": start,
+                "end": end,
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn unary_rpcs_should_map_to_json() {
+        let router = gateway();
+        let body = reservation(
+            "room-1",
+            "2022-12-26T15:00:00-07:00",
+            "2022-12-27T12:00:00-07:00",
+        );
+        let (status, ret) = post_json(&router, "/v1/reserve", "application/json", body).await;
+        assert_eq!(status, StatusCode::OK);
+        let ret: Value = serde_json::from_str(&ret).unwrap();
+        let rsvp = &ret["reservation"];
+        assert_eq!(rsvp["tenant_id"], "acme");
+        assert_eq!(rsvp["status"], "RESERVATION_STATUS_PENDING");
+        assert_eq!(rsvp["start"], "2022-12-26T22:00:00Z");
+
+        let body = json!({ "id": rsvp["id"] });
+        let (status, ret) = post_json(&router, "/v1/confirm", "application/json", body).await;
+        assert_eq!(status, StatusCode::OK);
+        let ret: Value = serde_json::from_str(&ret).unwrap();
+        assert_eq!(ret["reservation"]["status"], "RESERVATION_STATUS_CONFIRMED");
+
+        let body = reservation(
+            "room-1",
+            "2022-12-26T16:00:00-07:00",
+            "2022-12-26T18:00:00-07:00",
+        );
+        let (status, ret) = post_json(&router, "/v1/reserve", "application/json", body).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let ret: Value = serde_json::from_str(&ret).unwrap();
+        assert_eq!(ret["code"], 409);
+        assert_eq!(ret["reason"], "CONFLICT_RESERVATION");
+
+        let (status, ret) =
+            post_json(&router, "/v1/get", "application/json", json!({ "id": 42 })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(ret.contains("NOT_FOUND"));
+
+        let (status, ret) = post_json(&router, "/v1/reserve", "application/json", json!({})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(ret.contains("INVALID_ARGUMENT"));
+
+        let body = json!({ "reservation": { "status": "CONFIRMED" } });
+        let (status, ret) = post_json(&router, "/v1/reserve", "application/json", body).await;
+        assert!(status.is_client_error());
+        assert!(ret.contains("INVALID_JSON"));
+    }
+
+    #[tokio::test]
+    async fn rpcs_should_act_as_the_token_principal_with_auth() {
+        let auth = Authenticator::new(&auth_config()).await.unwrap();
+        let config = ServerConfig {
+            host: "127.0.0.1".into(),
+            port: 0,
+            page_token_secret: "secret".into(),
+            allow_raw_cursor: false,
+            http_port: None,
+            gateway_port: None,
+            cors_origins: vec![],
+            tls: None,
+            auth: Some(auth_config()),
+        };
+        let service = RsvpService::new(InMemoryReservationManager::new(), &config);
+        let router = router(Arc::new(service), Some(auth));
+        let reserve = |user_id: &str, resource_id: &str, token: Option<String>| {
+            let body = json!({
+                "reservation": {
+                    "user_id": user_id,
+                    "resource_id": resource_id,
+                    "start": "2022-12-26T15:00:00-07:00",
+                    "end": "2022-12-27T12:00:00-07:00",
+                }
+            });
+            let mut request =
+                HttpRequest::post("/v1/reserve").header(header::CONTENT_TYPE, "application/json");
+            if let Some(token) = token {
+                request = request.header(header::AUTHORIZATION, token);
+            }
+            let request = request.body(Body::from(body.to_string())).unwrap();
+            let router = router.clone();
+            async move {
+                let response = router.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                (status, serde_json::from_slice::<Value>(&body).unwrap())
+            }
+        };
+
+        let (status, ret) = reserve("tyr", "room-1", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(ret["reason"], "UNAUTHENTICATED");
+
+        // the user id defaults to the caller
+        let (status, ret) = reserve("", "room-1", Some(token(json!({})))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ret["reservation"]["user_id"], "tyr");
+
+        let (status, ret) = reserve("alice", "room-2", Some(token(json!({})))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(ret["reason"], "PERMISSION_DENIED");
+
+        let claims = json!({ "sub": "booking-bot", "scp": ["reservation:on_behalf"] });
+        let (status, ret) = reserve("alice", "room-2", Some(token(claims))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ret["reservation"]["user_id"], "alice");
+
+        let request = HttpRequest::get("/openapi.json")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn query_should_stream_ndjson_or_events() {
+        let router = gateway();
+        for (resource_id, start, end) in [
+            (
+                "room-1",
+                "2022-12-26T15:00:00-07:00",
+                "2022-12-27T12:00:00-07:00",
+            ),
+            (
+                "room-2",
+                "2022-12-26T15:00:00-07:00",
+                "2022-12-27T12:00:00-07:00",
+            ),
+        ] {
+            let body = reservation(resource_id, start, end);
+            let (status, _) = post_json(&router, "/v1/reserve", "application/json", body).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let body = json!({ "query": { "user_id": "tyr" } });
+        let (status, ret) =
+            post_json(&router, "/v1/query", NDJSON_CONTENT_TYPE, body.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let rsvps: Vec<Value> = ret
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(rsvps.len(), 2);
+        assert_eq!(rsvps[0]["resource_id"], "room-1");
+
+        let (status, ret) = post_json(&router, "/v1/query", EVENT_STREAM_CONTENT_TYPE, body).await;
+        assert_eq!(status, StatusCode::OK);
+        let events = ret.lines().filter(|l| l.starts_with("data:")).count();
+        assert_eq!(events, 2);
+    }
+
+    #[test]
+    fn openapi_should_cover_every_rpc() {
+        let doc = serde_json::to_value(openapi()).unwrap();
+        let paths = doc["paths"].as_object().unwrap();
+        assert_eq!(paths.len(), 15);
+        assert!(paths.contains_key("/v1/accept_transfer"));
+        let schema = &doc["components"]["schemas"]["Reservation"]["properties"];
+        assert_eq!(schema["start"]["format"], "date-time");
+        assert_eq!(schema["status"]["type"], "string");
+    }
+}
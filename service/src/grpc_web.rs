@@ -0,0 +1,418 @@
+//! gRPC-Web for browser clients, without a translating proxy in front. A gRPC-Web call is turned
+//! into a grpc call for the services, and its response back: the trailers become the last frame
+//! of the body, as browsers can't read HTTP trailers. Calls of `application/grpc-web-text` are
+//! base64 encoded both ways. Server streaming (`query`, `listen`) works over HTTP/1.1 as well
+
+use std::{
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::{
+    body::{boxed, BoxBody, StreamBody},
+    BoxError,
+};
+use futures::{future::BoxFuture, stream, Stream, StreamExt};
+use hyper::{
+    body::{Bytes, HttpBody},
+    header::{self, HeaderName, HeaderValue, InvalidHeaderValue},
+    HeaderMap, Method, Request, Response,
+};
+use tower::{Layer, Service};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::service::TENANT_ID_KEY;
+
+const GRPC_WEB: &str = "application/grpc-web";
+const GRPC_WEB_PROTO: &str = "application/grpc-web+proto";
+const GRPC_WEB_TEXT: &str = "application/grpc-web-text";
+const GRPC_WEB_TEXT_PROTO: &str = "application/grpc-web-text+proto";
+
+/// the flag of a frame holding the trailers, rather than a message
+const TRAILERS_FLAG: u8 = 0x80;
+
+/// how long browsers may cache a preflight response
+const PREFLIGHT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// translates gRPC-Web calls, others are passed through
+#[derive(Debug, Clone, Default)]
+pub struct GrpcWebLayer;
+
+#[derive(Debug, Clone)]
+pub struct GrpcWeb<S> {
+    inner: S,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Binary,
+    /// base64
+    Text,
+}
+
+impl<S> Layer<S> for GrpcWebLayer {
+    type Service = GrpcWeb<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcWeb { inner }
+    }
+}
+
+impl<S, B> Service<Request<hyper::Body>> for GrpcWeb<S>
+where
+    S: Service<Request<hyper::Body>, Response = Response<B>>,
+    S::Future: Send + 'static,
+    B: HttpBody<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        match Encoding::of(req.headers()) {
+            Some(encoding) if req.method() == Method::POST => {
+                let fut = self.inner.call(coerce_request(req, encoding));
+                Box::pin(async move { Ok(coerce_response(fut.await?, encoding)) })
+            }
+            _ => {
+                let fut = self.inner.call(req);
+                Box::pin(async move { Ok(fut.await?.map(boxed)) })
+            }
+        }
+    }
+}
+
+impl Encoding {
+    fn of(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+        match content_type {
+            GRPC_WEB | GRPC_WEB_PROTO => Some(Encoding::Binary),
+            GRPC_WEB_TEXT | GRPC_WEB_TEXT_PROTO => Some(Encoding::Text),
+            _ => None,
+        }
+    }
+
+    fn content_type(&self) -> HeaderValue {
+        match self {
+            Encoding::Binary => HeaderValue::from_static(GRPC_WEB_PROTO),
+            Encoding::Text => HeaderValue::from_static(GRPC_WEB_TEXT_PROTO),
+        }
+    }
+}
+
+/// CORS for gRPC-Web calls from the origins, `*` for any. Preflight requests are answered here
+pub fn cors(origins: &[String]) -> Result<CorsLayer, InvalidHeaderValue> {
+    let layer = CorsLayer::new()
+        .allow_methods([Method::POST])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            HeaderName::from_static("x-grpc-web"),
+            HeaderName::from_static("x-user-agent"),
+            HeaderName::from_static("grpc-timeout"),
+            HeaderName::from_static(TENANT_ID_KEY),
+        ])
+        .expose_headers([
+            HeaderName::from_static("grpc-status"),
+            HeaderName::from_static("grpc-message"),
+            HeaderName::from_static("grpc-status-details-bin"),
+        ])
+        .max_age(PREFLIGHT_MAX_AGE);
+    if origins.iter().any(|o| o == "*") {
+        return Ok(layer.allow_origin(Any));
+    }
+    let origins = origins
+        .iter()
+        .map(|o| HeaderValue::from_str(o))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(layer.allow_origin(AllowOrigin::list(origins)))
+}
+
+fn coerce_request(req: Request<hyper::Body>, encoding: Encoding) -> Request<hyper::Body> {
+    let (mut parts, body) = req.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/grpc"),
+    );
+    parts
+        .headers
+        .insert(header::TE, HeaderValue::from_static("trailers"));
+    let body = match encoding {
+        Encoding::Binary => body,
+        Encoding::Text => hyper::Body::wrap_stream(decode_text(body)),
+    };
+    Request::from_parts(parts, body)
+}
+
+fn coerce_response<B>(res: Response<B>, encoding: Encoding) -> Response<BoxBody>
+where
+    B: HttpBody<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    let (mut parts, body) = res.into_parts();
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, encoding.content_type());
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let frames = web_frames(body).map(move |frame| match encoding {
+        Encoding::Binary => frame,
+        Encoding::Text => frame.map(|bytes| Bytes::from(base64::encode(bytes))),
+    });
+    Response::from_parts(parts, boxed(StreamBody::new(frames)))
+}
+
+/// the data of the body, then its trailers as a frame
+fn web_frames<B>(body: B) -> impl Stream<Item = Result<Bytes, BoxError>> + Send
+where
+    B: HttpBody<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    let body = Box::pin(body);
+    stream::unfold(Some(body), |body| async move {
+        let mut body = body?;
+        match body.data().await {
+            Some(Ok(data)) => return Some((Ok(data), Some(body))),
+            Some(Err(e)) => return Some((Err(e.into()), None)),
+            None => {}
+        }
+        match body.trailers().await {
+            Ok(Some(trailers)) => Some((Ok(trailers_frame(&trailers)), None)),
+            Ok(None) => None,
+            Err(e) => Some((Err(e.into()), None)),
+        }
+    })
+}
+
+/// `0x80`, the length, then the trailers like HTTP/1.1 headers
+fn trailers_frame(trailers: &HeaderMap) -> Bytes {
+    let mut block = Vec::new();
+    for (name, value) in trailers {
+        block.extend_from_slice(name.as_str().as_bytes());
+        block.push(b':');
+        block.extend_from_slice(value.as_bytes());
+        block.extend_from_slice(b"\r\n");
+    }
+    let mut frame = Vec::with_capacity(5 + block.len());
+    frame.push(TRAILERS_FLAG);
+    frame.extend_from_slice(&(block.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&block);
+    frame.into()
+}
+
+/// decode a base64 body as it arrives, chunks needn't be aligned to the base64 groups
+fn decode_text(body: hyper::Body) -> impl Stream<Item = Result<Bytes, BoxError>> + Send {
+    stream::unfold((Some(body), Vec::new()), |(body, mut pending)| async move {
+        let mut body = body?;
+        loop {
+            match body.data().await {
+                Some(Ok(chunk)) => {
+                    pending.extend_from_slice(&chunk);
+                    let len = pending.len() / 4 * 4;
+                    if len == 0 {
+                        continue;
+                    }
+                    let rest = pending.split_off(len);
+                    let decoded = decode_groups(&pending);
+                    return Some((decoded, (Some(body), rest)));
+                }
+                Some(Err(e)) => return Some((Err(e.into()), (None, pending))),
+                None if pending.is_empty() => return None,
+                None => {
+                    let e = "truncated base64 body".into();
+                    return Some((Err(e), (None, Vec::new())));
+                }
+            }
+        }
+    })
+}
+
+/// base64 of whole groups, possibly padded in the middle as every message may be encoded apart
+fn decode_groups(input: &[u8]) -> Result<Bytes, BoxError> {
+    let mut decoded = Vec::with_capacity(input.len() / 4 * 3);
+    let mut start = 0;
+    for (i, group) in input.chunks(4).enumerate() {
+        let end = (i + 1) * 4;
+        if group.contains(&b'=') || end == input.len() {
+            base64::decode_config_buf(&input[start..end], base64::STANDARD, &mut decoded)?;
+            start = end;
+        }
+    }
+    Ok(decoded.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::{
+        reservation_service_server::ReservationServiceServer, QueryRequest, Reservation,
+        ReservationQueryBuilder, ReserveRequest, ReserveResponse, ServerConfig,
+    };
+    use prost::Message;
+    use reservation::InMemoryReservationManager;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use crate::RsvpService;
+
+    fn grpc_web() -> GrpcWeb<ReservationServiceServer<RsvpService<InMemoryReservationManager>>> {
+        let config = ServerConfig {
+            host: "127.0.0.1".into(),
+            port: 0,
+            page_token_secret: "secret".into(),
+            allow_raw_cursor: false,
+            http_port: None,
+            gateway_port: None,
+            cors_origins: vec![],
+            tls: None,
+            auth: None,
+        };
+        let service = RsvpService::new(InMemoryReservationManager::new(), &config);
+        GrpcWebLayer.layer(ReservationServiceServer::from_arc(Arc::new(service)))
+    }
+
+    /// a message framed like grpc: not compressed, the length, the message
+    fn frame(msg: &impl Message) -> Vec<u8> {
+        let msg = msg.encode_to_vec();
+        let mut frame = vec![0];
+        frame.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&msg);
+        frame
+    }
+
+    /// split a gRPC-Web body to its frames, as (flag, payload)
+    fn split_frames(mut body: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut frames = vec![];
+        while !body.is_empty() {
+            let len = u32::from_be_bytes(body[1..5].try_into().unwrap()) as usize;
+            frames.push((body[0], body[5..5 + len].to_vec()));
+            body = &body[5 + len..];
+        }
+        frames
+    }
+
+    async fn call(
+        svc: &mut GrpcWeb<ReservationServiceServer<RsvpService<InMemoryReservationManager>>>,
+        path: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> (Response<BoxBody>, Vec<u8>) {
+        let req = Request::post(path)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(TENANT_ID_KEY, "acme")
+            .body(hyper::Body::from(body))
+            .unwrap();
+        let res = svc.ready().await.unwrap().call(req).await.unwrap();
+        let (parts, body) = res.into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap().to_vec();
+        (
+            Response::from_parts(parts, boxed(hyper::Body::empty())),
+            body,
+        )
+    }
+
+    #[tokio::test]
+    async fn grpc_web_calls_should_work() {
+        let mut svc = grpc_web();
+        let rsvp = Reservation::new_pending(
+            "tyr",
+            "room-1",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-27T12:00:00-0700".parse().unwrap(),
+            "note",
+        );
+        let body = frame(&ReserveRequest::new(rsvp));
+        let path = "/reservation.ReservationService/reserve";
+        let (res, body) = call(&mut svc, path, GRPC_WEB_PROTO, body).await;
+        assert_eq!(res.headers()[header::CONTENT_TYPE], GRPC_WEB_PROTO);
+        let frames = split_frames(&body);
+        assert_eq!(frames.len(), 2);
+        let reserved = ReserveResponse::decode(frames[0].1.as_slice()).unwrap();
+        assert_eq!(reserved.reservation.unwrap().tenant_id, "acme");
+        assert_eq!(frames[1].0, TRAILERS_FLAG);
+        let trailers = String::from_utf8(frames[1].1.clone()).unwrap();
+        assert!(trailers.contains("grpc-status:0\r\n"));
+
+        // a server streaming call, base64 encoded with the request sent in odd chunks
+        let query = ReservationQueryBuilder::default()
+            .user_id("tyr")
+            .build()
+            .unwrap();
+        let body = base64::encode(frame(&QueryRequest::new(query)));
+        let path = "/reservation.ReservationService/query";
+        let (res, body) = call(&mut svc, path, GRPC_WEB_TEXT, body.into_bytes()).await;
+        assert_eq!(res.headers()[header::CONTENT_TYPE], GRPC_WEB_TEXT_PROTO);
+        let body = decode_groups(&body).unwrap();
+        let frames = split_frames(&body);
+        assert_eq!(frames.len(), 2);
+        let rsvp = Reservation::decode(frames[0].1.as_slice()).unwrap();
+        assert_eq!(rsvp.resource_id, "room-1");
+        assert_eq!(frames[1].0, TRAILERS_FLAG);
+    }
+
+    #[tokio::test]
+    async fn decode_text_should_handle_unaligned_chunks_and_padding() {
+        let encoded = format!(
+            "{}{}",
+            base64::encode(b"hello"),
+            base64::encode(b"grpc-web")
+        );
+        let chunks: Vec<Result<_, std::io::Error>> = encoded
+            .as_bytes()
+            .chunks(3)
+            .map(|c| Ok(c.to_vec()))
+            .collect();
+        let body = hyper::Body::wrap_stream(stream::iter(chunks));
+        let decoded: Vec<_> = decode_text(body)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(decoded.concat(), b"hellogrpc-web");
+    }
+
+    #[tokio::test]
+    async fn cors_should_answer_preflight_for_allowed_origins() {
+        let origins = vec!["https://app.example.com".to_string()];
+        let mut svc = tower::ServiceBuilder::new()
+            .layer(cors(&origins).unwrap())
+            .service(grpc_web());
+        let req = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/reservation.ReservationService/query")
+            .header(header::ORIGIN, "https://app.example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(
+                header::ACCESS_CONTROL_REQUEST_HEADERS,
+                "content-type,x-grpc-web",
+            )
+            .body(hyper::Body::empty())
+            .unwrap();
+        let res = svc.ready().await.unwrap().call(req).await.unwrap();
+        let headers = res.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        let allowed = headers[header::ACCESS_CONTROL_ALLOW_HEADERS]
+            .to_str()
+            .unwrap();
+        assert!(allowed.contains("x-grpc-web") && allowed.contains(TENANT_ID_KEY));
+
+        let req = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/reservation.ReservationService/query")
+            .header(header::ORIGIN, "https://evil.example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let res = svc.ready().await.unwrap().call(req).await.unwrap();
+        assert!(!res
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+}
@@ -0,0 +1,221 @@
+//! the standard grpc health service for probes and load balancers. The server ("") and the
+//! reservation service are SERVING while the storage is reachable, and NOT_SERVING once the
+//! server starts to shut down
+
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use abi::{
+    health::{
+        health_check_response::ServingStatus, health_server::Health, HealthCheckRequest,
+        HealthCheckResponse,
+    },
+    reservation_service_server::ReservationServiceServer,
+};
+use futures::{stream, Stream};
+use reservation::Rsvp;
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
+use tonic::{server::NamedService, Request, Response, Status};
+use tracing::warn;
+
+use crate::RsvpService;
+
+/// how often the storage is checked
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+type HealthStream = Pin<Box<dyn Stream<Item = Result<HealthCheckResponse, Status>> + Send>>;
+
+/// sets the serving status reported by the `HealthService`
+#[derive(Debug, Clone)]
+pub struct HealthReporter {
+    status: Arc<watch::Sender<ServingStatus>>,
+    shutting_down: Arc<AtomicBool>,
+}
+
+pub struct HealthService {
+    status: watch::Receiver<ServingStatus>,
+}
+
+/// the reporter and the service sharing its status, NOT_SERVING until the reporter says otherwise
+pub fn health_service() -> (HealthReporter, HealthService) {
+    let (tx, rx) = watch::channel(ServingStatus::NotServing);
+    let reporter = HealthReporter {
+        status: Arc::new(tx),
+        shutting_down: Arc::new(AtomicBool::new(false)),
+    };
+    (reporter, HealthService { status: rx })
+}
+
+impl HealthReporter {
+    /// set the status, it stays NOT_SERVING once shutting down
+    pub fn set(&self, status: ServingStatus) {
+        let status = match self.shutting_down.load(Ordering::SeqCst) {
+            true => ServingStatus::NotServing,
+            false => status,
+        };
+        if *self.status.borrow() != status {
+            self.status.send_replace(status);
+        }
+    }
+
+    /// NOT_SERVING from now on, so the clients move away while the server drains
+    pub fn shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        self.set(ServingStatus::NotServing);
+    }
+
+    /// ping the storage once and then every `PROBE_INTERVAL`, SERVING while it answers
+    pub async fn probe<R>(&self, manager: R) -> JoinHandle<()>
+    where
+        R: Rsvp + Send + Sync + 'static,
+    {
+        self.probe_once(&manager).await;
+        let reporter = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(PROBE_INTERVAL).await;
+                if reporter.shutting_down.load(Ordering::SeqCst) {
+                    return;
+                }
+                reporter.probe_once(&manager).await;
+            }
+        })
+    }
+
+    async fn probe_once<R: Rsvp + Sync>(&self, manager: &R) {
+        let status = match manager.ping().await {
+            Ok(_) => ServingStatus::Serving,
+            Err(e) => {
+                warn!("Storage is not reachable: {:?}", e);
+                ServingStatus::NotServing
+            }
+        };
+        self.set(status);
+    }
+}
+
+impl HealthService {
+    /// the status of the service, None if the service is unknown
+    fn status(&self, service: &str) -> Option<ServingStatus> {
+        let known = service.is_empty()
+            || service == <ReservationServiceServer<RsvpService> as NamedService>::NAME;
+        known.then(|| *self.status.borrow())
+    }
+}
+
+#[tonic::async_trait]
+impl Health for HealthService {
+    async fn check(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        let service = request.into_inner().service;
+        match self.status(&service) {
+            Some(status) => Ok(Response::new(HealthCheckResponse {
+                status: status as i32,
+            })),
+            None => Err(Status::not_found(format!("unknown service {}", service))),
+        }
+    }
+
+    type WatchStream = HealthStream;
+
+    /// the current status, and every change after it. An unknown service is SERVICE_UNKNOWN
+    async fn watch(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let service = request.into_inner().service;
+        let known = self.status(&service).is_some();
+        let mut status = self.status.clone();
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            loop {
+                let current = match known {
+                    true => *status.borrow_and_update(),
+                    false => ServingStatus::ServiceUnknown,
+                };
+                let response = HealthCheckResponse {
+                    status: current as i32,
+                };
+                if tx.send(Ok(response)).await.is_err() {
+                    return;
+                }
+                tokio::select! {
+                    changed = status.changed(), if known => {
+                        if changed.is_err() {
+                            return;
+                        }
+                    }
+                    _ = tx.closed() => return,
+                }
+            }
+        });
+        let stream = stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|item| (item, rx))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use reservation::InMemoryReservationManager;
+
+    fn check_request(service: &str) -> Request<HealthCheckRequest> {
+        Request::new(HealthCheckRequest {
+            service: service.into(),
+        })
+    }
+
+    #[tokio::test]
+    async fn health_should_follow_storage_and_shutdown() {
+        let (reporter, service) = health_service();
+        let status = service.check(check_request("")).await.unwrap().into_inner();
+        assert_eq!(status.status, ServingStatus::NotServing as i32);
+
+        reporter.probe(InMemoryReservationManager::new()).await;
+        let name = "reservation.ReservationService";
+        let status = service
+            .check(check_request(name))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(status.status, ServingStatus::Serving as i32);
+
+        let err = service.check(check_request("foo")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        let mut changes = service.watch(check_request("")).await.unwrap().into_inner();
+        let first = changes.next().await.unwrap().unwrap();
+        assert_eq!(first.status, ServingStatus::Serving as i32);
+
+        reporter.shutdown();
+        let next = changes.next().await.unwrap().unwrap();
+        assert_eq!(next.status, ServingStatus::NotServing as i32);
+
+        // probes can't bring it back
+        reporter.set(ServingStatus::Serving);
+        let status = service.check(check_request("")).await.unwrap().into_inner();
+        assert_eq!(status.status, ServingStatus::NotServing as i32);
+
+        let mut changes = service
+            .watch(check_request("foo"))
+            .await
+            .unwrap()
+            .into_inner();
+        let first = changes.next().await.unwrap().unwrap();
+        assert_eq!(first.status, ServingStatus::ServiceUnknown as i32);
+    }
+}
@@ -0,0 +1,183 @@
+//! the HTTP side of the service: iCalendar feeds of users and resources, for calendar apps to
+//! subscribe to. Calendar apps can't send metadata, so the tenant comes from the `tenant` query
+//! parameter, e.g. `/calendars/users/tyr.ics?tenant=acme`
+
+use abi::{validate_tenant_id, ReservationQuery, ReservationQueryBuilder, TenantId};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use reservation::Rsvp;
+use serde::Deserialize;
+
+use crate::export_calendar;
+
+const CALENDAR_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+#[derive(Debug, Default, Deserialize)]
+struct FeedParams {
+    /// the default tenant if missing
+    #[serde(default)]
+    tenant: TenantId,
+}
+
+/// an error of the manager as an HTTP response
+struct HttpError(abi::Error);
+
+pub fn router<R>(manager: R) -> Router
+where
+    R: Rsvp + Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/calendars/users/:user_id", get(user_calendar::<R>))
+        .route(
+            "/calendars/resources/:resource_id",
+            get(resource_calendar::<R>),
+        )
+        .with_state(manager)
+}
+
+/// every reservation of the user
+async fn user_calendar<R: Rsvp>(
+    State(manager): State<R>,
+    Path(user_id): Path<String>,
+    Query(params): Query<FeedParams>,
+) -> Result<Response, HttpError> {
+    let user_id = strip_ics(&user_id);
+    let query = ReservationQueryBuilder::default()
+        .user_id(user_id)
+        .build()?;
+    calendar_feed(&manager, params.tenant, user_id, query).await
+}
+
+/// every reservation of the resource
+async fn resource_calendar<R: Rsvp>(
+    State(manager): State<R>,
+    Path(resource_id): Path<String>,
+    Query(params): Query<FeedParams>,
+) -> Result<Response, HttpError> {
+    let resource_id = strip_ics(&resource_id);
+    let query = ReservationQueryBuilder::default()
+        .resource_id(resource_id)
+        .build()?;
+    calendar_feed(&manager, params.tenant, resource_id, query).await
+}
+
+async fn calendar_feed<R: Rsvp>(
+    manager: &R,
+    tenant_id: TenantId,
+    name: &str,
+    query: ReservationQuery,
+) -> Result<Response, HttpError> {
+    validate_tenant_id(&tenant_id)?;
+    let manager = manager.with_tenant(tenant_id);
+    let calendar = export_calendar(&manager, name, query).await?;
+    Ok(([(header::CONTENT_TYPE, CALENDAR_CONTENT_TYPE)], calendar).into_response())
+}
+
+/// calendar apps like the feed url to end with .ics
+fn strip_ics(id: &str) -> &str {
+    id.strip_suffix(".ics").unwrap_or(id)
+}
+
+impl From<abi::Error> for HttpError {
+    fn from(e: abi::Error) -> Self {
+        Self(e)
+    }
+}
+
+impl IntoResponse for HttpError {
+    fn into_response(self) -> Response {
+        (status_code(&self.0), self.0.to_string()).into_response()
+    }
+}
+
+/// HTTP status of the error, shared with the gateway
+pub(crate) fn status_code(e: &abi::Error) -> StatusCode {
+    match e {
+        abi::Error::NotFound => StatusCode::NOT_FOUND,
+        abi::Error::ConflictReservation(_) => StatusCode::CONFLICT,
+        abi::Error::Unauthenticated(_) => StatusCode::UNAUTHORIZED,
+        abi::Error::PermissionDenied(_) => StatusCode::FORBIDDEN,
+        abi::Error::DbError(_)
+        | abi::Error::StorageError(_)
+        | abi::Error::ConfigReadError
+        | abi::Error::ConfigParseError
+        | abi::Error::Unknown => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::Reservation;
+    use axum::{body::Body, http::Request};
+    use reservation::InMemoryReservationManager;
+    use tower::ServiceExt;
+
+    async fn get_feed(router: Router, uri: &str) -> (StatusCode, String) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn calendar_feeds_should_work() {
+        let manager = InMemoryReservationManager::new();
+        let acme = manager.with_tenant("acme".into());
+        let owners = [
+            (
+                "tyr",
+                "room-1",
+                "2022-12-26T15:00:00-0700",
+                "2022-12-27T12:00:00-0700",
+            ),
+            (
+                "tyr",
+                "room-2",
+                "2022-12-26T15:00:00-0700",
+                "2022-12-27T12:00:00-0700",
+            ),
+            (
+                "alice",
+                "room-1",
+                "2022-12-27T15:00:00-0700",
+                "2022-12-28T12:00:00-0700",
+            ),
+        ];
+        for (user_id, resource_id, start, end) in owners {
+            let rsvp = Reservation::new_pending(
+                user_id,
+                resource_id,
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            );
+            acme.reserve(rsvp).await.unwrap();
+        }
+        let router = router(manager);
+
+        let uri = "/calendars/users/tyr.ics?tenant=acme";
+        let (status, ics) = get_feed(router.clone(), uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(ics.contains("X-WR-CALNAME:tyr\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+
+        let uri = "/calendars/resources/room-1?tenant=acme";
+        let (_, ics) = get_feed(router.clone(), uri).await;
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+
+        // the reservations of acme aren't in the default tenant
+        let (_, ics) = get_feed(router.clone(), "/calendars/users/tyr").await;
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 0);
+
+        let (status, _) = get_feed(router, "/calendars/users/tyr?tenant=a%20b").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
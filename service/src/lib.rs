@@ -1,16 +1,43 @@
+pub mod auth;
+pub mod bulk;
+pub mod gateway;
+pub mod grpc_web;
+pub mod health;
+mod http;
+pub mod reflection;
 mod service;
 #[cfg(test)]
 pub mod test_utils;
+pub mod tls;
 
-use abi::{reservation_service_server::ReservationServiceServer, Config, Reservation};
-use futures::Stream;
-use reservation::ReservationManager;
-use std::pin::Pin;
-use tokio::sync::mpsc;
-use tonic::{transport::Server, Status};
+use abi::{
+    health::health_server::HealthServer,
+    reflection::server_reflection_server::ServerReflectionServer,
+    reservation_service_server::ReservationServiceServer, Config, DbDriver, ListenResponse,
+    PageTokenCodec, Reservation, ReservationQuery, ServerConfig, StorageBackend,
+};
+use axum::Router;
+use futures::{Future, Stream};
+use reservation::{
+    EmbeddedReservationManager, InMemoryReservationManager, ReservationManager, Rsvp,
+};
+use std::{net::SocketAddr, path::Path, pin::Pin, sync::Arc, time::Duration};
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, Notify},
+};
+use tonic::{codegen::InterceptedService, transport::Server, Status};
 
-pub struct RsvpService {
-    manager: ReservationManager,
+/// how long NOT_SERVING is reported before the server stops accepting calls, so the probes see it
+const DRAIN_DELAY: Duration = Duration::from_secs(5);
+/// the most time the calls in flight get to finish after that, `listen` streams only end with
+/// their clients
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct RsvpService<R = ReservationManager> {
+    manager: R,
+    page_tokens: PageTokenCodec,
+    allow_raw_cursor: bool,
 }
 
 pub struct TonicReceiverStream<T> {
@@ -18,14 +45,249 @@ pub struct TonicReceiverStream<T> {
 }
 
 type ReservationStream = Pin<Box<dyn Stream<Item = Result<Reservation, Status>> + Send>>;
+type ListenStream = Pin<Box<dyn Stream<Item = Result<ListenResponse, Status>> + Send>>;
+
+/// `RESERVATION_CONFIG`, or the first existing of ./reservation.yml, ~/.config/reservation.yml
+/// and /etc/reservation.yml
+pub fn find_config_file() -> Option<String> {
+    if let Ok(filename) = std::env::var("RESERVATION_CONFIG") {
+        return Some(filename);
+    }
+    let home = shellexpand::tilde("~/.config/reservation.yml");
+    let filename = ["./reservation.yml", home.as_ref(), "/etc/reservation.yml"]
+        .into_iter()
+        .find(|p| Path::new(p).exists())
+        .map(|p| p.to_string());
+    filename
+}
 
 pub async fn start_server(config: &Config) -> Result<(), anyhow::Error> {
     let addr = format!("{}:{}", config.server.host, config.server.port).parse()?;
 
-    let svc = RsvpService::from_config(config).await?;
-    let svc = ReservationServiceServer::new(svc);
+    match config.storage.backend {
+        StorageBackend::Db => serve_db(config, addr).await,
+        StorageBackend::Embedded => {
+            let path = shellexpand::tilde(&config.storage.path);
+            let manager = EmbeddedReservationManager::open(path.as_ref())?;
+            serve(manager, &config.server, addr).await
+        }
+        StorageBackend::Memory => {
+            let manager = InMemoryReservationManager::new();
+            serve(manager, &config.server, addr).await
+        }
+    }
+}
+
+async fn serve_db(config: &Config, addr: SocketAddr) -> Result<(), anyhow::Error> {
+    match config.db.driver {
+        DbDriver::Postgres => {
+            let manager = ReservationManager::from_config(&config.db).await?;
+            serve(manager, &config.server, addr).await
+        }
+        #[cfg(feature = "sqlite")]
+        DbDriver::Sqlite => {
+            let manager = reservation::SqliteReservationManager::from_config(&config.db).await?;
+            serve(manager, &config.server, addr).await
+        }
+        #[cfg(not(feature = "sqlite"))]
+        DbDriver::Sqlite => Err(anyhow::anyhow!(
+            "sqlite is not supported, please build with the sqlite feature"
+        )),
+    }
+}
+
+/// serve the grpc service, and the HTTP side and the gateway if their ports are set
+async fn serve<R>(manager: R, config: &ServerConfig, addr: SocketAddr) -> Result<(), anyhow::Error>
+where
+    R: Rsvp + Clone + Send + Sync + 'static,
+{
+    if config.auth.is_some() && config.http_port.is_some() {
+        // calendar apps can't send tokens, the feeds would leak every calendar of every tenant
+        return Err(anyhow::anyhow!(
+            "the calendar feeds of http_port can't be authenticated, unset http_port to use auth"
+        ));
+    }
+    let service = Arc::new(RsvpService::new(manager.clone(), config));
+    let auth = match &config.auth {
+        Some(auth) => {
+            let auth = auth::Authenticator::new(auth).await?;
+            auth.watch();
+            Some(auth)
+        }
+        None => None,
+    };
+    if let Some(port) = config.http_port {
+        spawn_http("HTTP", &config.host, port, http::router(manager.clone()))?;
+    }
+    if let Some(port) = config.gateway_port {
+        spawn_http(
+            "gateway",
+            &config.host,
+            port,
+            gateway::router(service.clone(), auth.clone()),
+        )?;
+    }
+
+    let (reporter, health) = health::health_service();
+    reporter.probe(manager).await;
+    let reflection = reflection::ReflectionService::new()?;
+
+    let svc = ReservationServiceServer::from_arc(service);
+    let mut router = Server::builder()
+        .accept_http1(true)
+        .layer(grpc_web::cors(&config.cors_origins)?)
+        .layer(grpc_web::GrpcWebLayer)
+        .add_service(HealthServer::new(health))
+        .add_service(ServerReflectionServer::new(reflection));
+    // health checks and reflection stay public
+    router = match auth {
+        Some(auth) => router.add_service(InterceptedService::new(svc, auth)),
+        None => router.add_service(svc),
+    };
+    let stopping = Arc::new(Notify::new());
+    let shutdown = shutdown_signal(reporter, stopping.clone());
+    match &config.tls {
+        Some(tls) => {
+            let tls = tls::ReloadableTlsConfig::load(tls)?;
+            tls.watch();
+            let listener = TcpListener::bind(addr).await?;
+            println!("Listening on {} with TLS", addr);
+            let server = router.serve_with_incoming_shutdown(tls.incoming(listener), shutdown);
+            serve_until_drained(server, stopping).await
+        }
+        None => {
+            println!("Listening on {}", addr);
+            let server = router.serve_with_shutdown(addr, shutdown);
+            serve_until_drained(server, stopping).await
+        }
+    }
+}
+
+/// run the server, giving the calls in flight at most `SHUTDOWN_TIMEOUT` once it stops accepting
+async fn serve_until_drained<F>(server: F, stopping: Arc<Notify>) -> Result<(), anyhow::Error>
+where
+    F: Future<Output = Result<(), tonic::transport::Error>>,
+{
+    let timeout = async {
+        stopping.notified().await;
+        tokio::time::sleep(SHUTDOWN_TIMEOUT).await;
+    };
+    tokio::select! {
+        ret = server => Ok(ret?),
+        _ = timeout => {
+            tracing::warn!("calls still in flight after {:?}, shutting down anyway", SHUTDOWN_TIMEOUT);
+            Ok(())
+        }
+    }
+}
+
+/// resolves on ctrl-c or SIGTERM, `DRAIN_DELAY` after reporting NOT_SERVING so the clients move
+/// away before the server stops accepting calls. `stopping` is notified then
+async fn shutdown_signal(reporter: health::HealthReporter, stopping: Arc<Notify>) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("failed to listen for ctrl-c: {}", e);
+            futures::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("failed to listen for SIGTERM: {}", e);
+                futures::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = futures::future::pending::<()>();
 
-    println!("Listening on {}", addr);
-    Server::builder().add_service(svc).serve(addr).await?;
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    println!("Shutting down");
+    reporter.shutdown();
+    tokio::time::sleep(DRAIN_DELAY).await;
+    stopping.notify_one();
+}
+
+fn spawn_http(
+    name: &'static str,
+    host: &str,
+    port: u16,
+    router: Router,
+) -> Result<(), anyhow::Error> {
+    let addr: SocketAddr = format!("{}:{}", host, port).parse()?;
+    // bind here, so a busy port fails the startup rather than the spawned server
+    let server = axum::Server::try_bind(&addr)
+        .map_err(|e| anyhow::anyhow!("{} can't listen on {}: {}", name, addr, e))?;
+    println!("{} listening on {}", name, addr);
+    tokio::spawn(async move {
+        if let Err(e) = server.serve(router.into_make_service()).await {
+            tracing::error!("{} server failed: {}", name, e);
+        }
+    });
     Ok(())
 }
+
+/// the reservations of the query as an iCalendar named `name`
+async fn export_calendar<R: Rsvp>(
+    manager: &R,
+    name: &str,
+    query: ReservationQuery,
+) -> Result<String, abi::Error> {
+    let mut rx = manager.query(query).await;
+    let mut rsvps = vec![];
+    while let Some(rsvp) = rx.recv().await {
+        rsvps.push(rsvp?);
+    }
+    Ok(abi::to_ics(name, &rsvps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spawn_http_should_fail_on_a_busy_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let err = spawn_http("HTTP", "127.0.0.1", port, Router::new()).unwrap_err();
+        assert!(err.to_string().contains("can't listen"));
+    }
+
+    #[tokio::test]
+    async fn serve_should_refuse_calendar_feeds_with_auth() {
+        let mut config = test_utils::TestConfig::default().server.clone();
+        config.http_port = Some(0);
+        config.auth = Some(auth::tests::auth_config());
+        let err = serve(
+            InMemoryReservationManager::new(),
+            &config,
+            "127.0.0.1:0".parse().unwrap(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("http_port"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn serve_until_drained_should_give_up_on_calls_in_flight() {
+        let stopping = Arc::new(Notify::new());
+        let server = futures::future::pending();
+        let drained = tokio::spawn(serve_until_drained(server, stopping.clone()));
+
+        tokio::time::sleep(SHUTDOWN_TIMEOUT * 2).await;
+        assert!(!drained.is_finished());
+
+        stopping.notify_one();
+        tokio::time::sleep(SHUTDOWN_TIMEOUT + Duration::from_secs(1)).await;
+        assert!(drained.is_finished());
+        drained.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+//! grpc server reflection (v1alpha, what grpcurl and most tools speak) from the descriptor set of
+//! the compiled protos, so the services can be explored without the proto files
+
+use std::{collections::HashMap, pin::Pin, sync::Arc};
+
+use abi::reflection::{
+    server_reflection_request::MessageRequest, server_reflection_response::MessageResponse,
+    server_reflection_server::ServerReflection, ErrorResponse, ExtensionNumberResponse,
+    FileDescriptorResponse, ListServiceResponse, ServerReflectionRequest, ServerReflectionResponse,
+    ServiceResponse,
+};
+use futures::{stream, Stream};
+use prost::Message;
+use prost_types::{DescriptorProto, EnumDescriptorProto, FileDescriptorProto, FileDescriptorSet};
+use tokio::sync::mpsc;
+use tonic::{Code, Request, Response, Status, Streaming};
+
+type ReflectionStream =
+    Pin<Box<dyn Stream<Item = Result<ServerReflectionResponse, Status>> + Send>>;
+
+#[derive(Debug, Clone)]
+pub struct ReflectionService {
+    inner: Arc<Descriptors>,
+}
+
+/// the files of the descriptor set, and where to find each symbol
+#[derive(Debug, Default)]
+struct Descriptors {
+    files: HashMap<String, FileDescriptorProto>,
+    /// fully qualified name of services, methods, messages and enums to their file
+    symbols: HashMap<String, String>,
+    services: Vec<String>,
+}
+
+impl ReflectionService {
+    /// reflect the protos compiled into abi
+    pub fn new() -> Result<Self, prost::DecodeError> {
+        Self::from_descriptor_set(abi::FILE_DESCRIPTOR_SET)
+    }
+
+    /// reflect an encoded `FileDescriptorSet`
+    pub fn from_descriptor_set(bytes: &[u8]) -> Result<Self, prost::DecodeError> {
+        let set = FileDescriptorSet::decode(bytes)?;
+        let mut descriptors = Descriptors::default();
+        for file in set.file {
+            descriptors.add_file(file);
+        }
+        Ok(Self {
+            inner: Arc::new(descriptors),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl ServerReflection for ReflectionService {
+    type ServerReflectionInfoStream = ReflectionStream;
+
+    async fn server_reflection_info(
+        &self,
+        request: Request<Streaming<ServerReflectionRequest>>,
+    ) -> Result<Response<Self::ServerReflectionInfoStream>, Status> {
+        let mut requests = request.into_inner();
+        let descriptors = self.inner.clone();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let response = match requests.message().await {
+                    Ok(Some(request)) => Ok(descriptors.respond(request)),
+                    Ok(None) => return,
+                    Err(e) => Err(e),
+                };
+                let failed = response.is_err();
+                if tx.send(response).await.is_err() || failed {
+                    return;
+                }
+            }
+        });
+        let stream = stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|item| (item, rx))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+impl Descriptors {
+    fn add_file(&mut self, file: FileDescriptorProto) {
+        let name = file.name().to_string();
+        let prefix = match file.package() {
+            "" => String::new(),
+            package => format!("{}.", package),
+        };
+        for service in &file.service {
+            let service_name = format!("{}{}", prefix, service.name());
+            for method in &service.method {
+                let method_name = format!("{}.{}", service_name, method.name());
+                self.symbols.insert(method_name, name.clone());
+            }
+            self.symbols.insert(service_name.clone(), name.clone());
+            self.services.push(service_name);
+        }
+        self.add_messages(&prefix, &file.message_type, &name);
+        self.add_enums(&prefix, &file.enum_type, &name);
+        self.files.insert(name, file);
+    }
+
+    fn add_messages(&mut self, prefix: &str, messages: &[DescriptorProto], file: &str) {
+        for message in messages {
+            let message_name = format!("{}{}", prefix, message.name());
+            let nested_prefix = format!("{}.", message_name);
+            self.add_messages(&nested_prefix, &message.nested_type, file);
+            self.add_enums(&nested_prefix, &message.enum_type, file);
+            self.symbols.insert(message_name, file.to_string());
+        }
+    }
+
+    fn add_enums(&mut self, prefix: &str, enums: &[EnumDescriptorProto], file: &str) {
+        for e in enums {
+            let enum_name = format!("{}{}", prefix, e.name());
+            self.symbols.insert(enum_name, file.to_string());
+        }
+    }
+
+    fn respond(&self, request: ServerReflectionRequest) -> ServerReflectionResponse {
+        let response = match &request.message_request {
+            Some(MessageRequest::FileByFilename(name)) => self.file_with_dependencies(name),
+            Some(MessageRequest::FileContainingSymbol(symbol)) => match self.symbols.get(symbol) {
+                Some(name) => self.file_with_dependencies(name),
+                None => not_found(format!("symbol {} not found", symbol)),
+            },
+            Some(MessageRequest::FileContainingExtension(ext)) => not_found(format!(
+                "extension {} of {} not found",
+                ext.extension_number, ext.containing_type
+            )),
+            Some(MessageRequest::AllExtensionNumbersOfType(name)) => {
+                match self.symbols.contains_key(name) {
+                    true => MessageResponse::AllExtensionNumbersResponse(ExtensionNumberResponse {
+                        base_type_name: name.clone(),
+                        extension_number: vec![],
+                    }),
+                    false => not_found(format!("type {} not found", name)),
+                }
+            }
+            Some(MessageRequest::ListServices(_)) => {
+                MessageResponse::ListServicesResponse(ListServiceResponse {
+                    service: self
+                        .services
+                        .iter()
+                        .map(|name| ServiceResponse { name: name.clone() })
+                        .collect(),
+                })
+            }
+            None => MessageResponse::ErrorResponse(ErrorResponse {
+                error_code: Code::InvalidArgument as i32,
+                error_message: "missing message request".into(),
+            }),
+        };
+        ServerReflectionResponse {
+            valid_host: request.host.clone(),
+            original_request: Some(request),
+            message_response: Some(response),
+        }
+    }
+
+    /// the file followed by everything it imports, directly or not
+    fn file_with_dependencies(&self, name: &str) -> MessageResponse {
+        if !self.files.contains_key(name) {
+            return not_found(format!("file {} not found", name));
+        }
+        let mut names = vec![name.to_string()];
+        let mut i = 0;
+        while i < names.len() {
+            if let Some(file) = self.files.get(&names[i]) {
+                for dependency in &file.dependency {
+                    if !names.contains(dependency) {
+                        names.push(dependency.clone());
+                    }
+                }
+            }
+            i += 1;
+        }
+        let file_descriptor_proto = names
+            .iter()
+            .filter_map(|name| self.files.get(name))
+            .map(|file| file.encode_to_vec())
+            .collect();
+        MessageResponse::FileDescriptorResponse(FileDescriptorResponse {
+            file_descriptor_proto,
+        })
+    }
+}
+
+fn not_found(message: String) -> MessageResponse {
+    MessageResponse::ErrorResponse(ErrorResponse {
+        error_code: Code::NotFound as i32,
+        error_message: message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn respond(request: MessageRequest) -> MessageResponse {
+        let service = ReflectionService::new().unwrap();
+        let request = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(request),
+        };
+        service.inner.respond(request).message_response.unwrap()
+    }
+
+    fn file_names(response: MessageResponse) -> Vec<String> {
+        match response {
+            MessageResponse::FileDescriptorResponse(files) => files
+                .file_descriptor_proto
+                .iter()
+                .map(|bytes| {
+                    FileDescriptorProto::decode(bytes.as_slice())
+                        .unwrap()
+                        .name()
+                        .to_string()
+                })
+                .collect(),
+            response => panic!("unexpected response {:?}", response),
+        }
+    }
+
+    #[test]
+    fn reflection_should_list_services() {
+        let response = respond(MessageRequest::ListServices(String::new()));
+        let services = match response {
+            MessageResponse::ListServicesResponse(services) => services.service,
+            response => panic!("unexpected response {:?}", response),
+        };
+        let names: Vec<_> = services.iter().map(|s| s.name.as_str()).collect();
+        assert!(names.contains(&"reservation.ReservationService"));
+        assert!(names.contains(&"grpc.health.v1.Health"));
+        assert!(names.contains(&"grpc.reflection.v1alpha.ServerReflection"));
+    }
+
+    #[test]
+    fn reflection_should_find_files_with_dependencies() {
+        let symbols = [
+            "reservation.ReservationService",
+            "reservation.ReservationService.reserve",
+            "reservation.Reservation",
+            "reservation.ReservationStatus",
+        ];
+        for symbol in symbols {
+            let names = file_names(respond(MessageRequest::FileContainingSymbol(symbol.into())));
+            assert_eq!(names[0], "reservation.proto");
+            assert!(names.contains(&"google/protobuf/timestamp.proto".to_string()));
+        }
+
+        let symbol = "grpc.health.v1.HealthCheckResponse.ServingStatus";
+        let names = file_names(respond(MessageRequest::FileContainingSymbol(symbol.into())));
+        assert_eq!(names, ["grpc/health/v1/health.proto"]);
+
+        let response = respond(MessageRequest::FileContainingSymbol("foo.Bar".into()));
+        assert!(
+            matches!(response, MessageResponse::ErrorResponse(e) if e.error_code == Code::NotFound as i32)
+        );
+    }
+}
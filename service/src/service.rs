@@ -1,49 +1,192 @@
 use std::{pin::Pin, task::Poll};
 
 use abi::{
-    reservation_service_server::ReservationService, CancelRequest, CancelResponse, Config,
-    ConfirmRequest, ConfirmResponse, FilterRequest, FilterResponse, GetRequest, GetResponse,
-    ListenRequest, QueryRequest, ReserveRequest, ReserveResponse, UpdateRequest, UpdateResponse,
+    reservation_service_server::ReservationService, validate_tenant_id, AcceptTransferRequest,
+    AcceptTransferResponse, CancelRequest, CancelResponse, CheckRequest, CheckResponse, Config,
+    ConfirmRequest, ConfirmResponse, ExportCalendarRequest, ExportCalendarResponse, FilterRequest,
+    FilterResponse, GetRequest, GetResponse, ImportCalendarRequest, ImportCalendarResponse,
+    ListenRequest, ListenResponse, PageTokenCodec, QueryRequest, ReserveRequest, ReserveResponse,
+    SearchRequest, SearchResponse, ServerConfig, SuggestRequest, SuggestResponse, TenantId,
+    TransferRequest, TransferResponse, UpdateRequest, UpdateResponse, DEFAULT_SUGGEST_LIMIT,
 };
 use futures::Stream;
 use reservation::{ReservationManager, Rsvp};
 use tokio::sync::mpsc;
 use tonic::{async_trait, Request, Response, Status};
 
-use crate::{ReservationStream, RsvpService, TonicReceiverStream};
+use crate::{
+    auth::{act_as, owner, read_as, Principal},
+    export_calendar, ListenStream, ReservationStream, RsvpService, TonicReceiverStream,
+};
+
+/// request metadata carrying the caller's tenant, if missing the default tenant is used. With auth
+/// the tenant is the one of the token, the metadata may only repeat it
+pub const TENANT_ID_KEY: &str = "x-tenant-id";
 
 impl RsvpService {
     pub async fn from_config(config: &Config) -> Result<Self, anyhow::Error> {
-        Ok(Self {
-            manager: ReservationManager::from_config(&config.db).await?,
-        })
+        let manager = ReservationManager::from_config(&config.db).await?;
+        Ok(Self::new(manager, &config.server))
     }
 }
 
+impl<R: Rsvp> RsvpService<R> {
+    /// serve the reservations of the given manager, e.g. an `InMemoryReservationManager`
+    pub fn new(manager: R, config: &ServerConfig) -> Self {
+        let secret = match config.page_token_secret.as_str() {
+            "" => rand::random::<[u8; 32]>().to_vec(),
+            secret => secret.as_bytes().to_vec(),
+        };
+        Self {
+            manager,
+            page_tokens: PageTokenCodec::new(secret),
+            allow_raw_cursor: config.allow_raw_cursor,
+        }
+    }
+
+    /// get the manager scoped to the tenant of the request
+    fn tenant_manager<T>(&self, request: &Request<T>) -> Result<R, abi::Error> {
+        Ok(self.manager.with_tenant(tenant_id(request)?))
+    }
+}
+
+/// fail unless the principal may change the reservation, anything goes without one (no auth
+/// configured)
+/// the changes of the reservations the principal may read, all of them without a principal (no
+/// auth configured) or for a service account
+fn readable_changes(
+    principal: Option<Principal>,
+    mut changes: mpsc::Receiver<Result<ListenResponse, abi::Error>>,
+) -> mpsc::Receiver<Result<ListenResponse, abi::Error>> {
+    let principal = match principal {
+        Some(principal) if !principal.service_account => principal,
+        _ => return changes,
+    };
+
+    let (tx, rx) = mpsc::channel(128);
+    tokio::spawn(async move {
+        loop {
+            let change = tokio::select! {
+                change = changes.recv() => change,
+                _ = tx.closed() => return,
+            };
+            let change = match change {
+                Some(change) => change,
+                None => return,
+            };
+            let readable = match &change {
+                Ok(change) => change
+                    .reservation
+                    .as_ref()
+                    .is_some_and(|rsvp| principal.check_reader(rsvp).is_ok()),
+                Err(_) => true,
+            };
+            if readable && tx.send(change).await.is_err() {
+                // rx is dropped, so client disconnected
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// tenant of the request: the one of its principal with auth, else from metadata, the default
+/// tenant if missing
+fn tenant_id<T>(request: &Request<T>) -> Result<TenantId, abi::Error> {
+    let requested = match request.metadata().get(TENANT_ID_KEY) {
+        Some(v) => Some(
+            v.to_str()
+                .map_err(|_| {
+                    abi::Error::InvalidTenantId(String::from_utf8_lossy(v.as_bytes()).into())
+                })?
+                .to_string(),
+        ),
+        None => None,
+    };
+    let tenant_id = match (request.extensions().get::<Principal>(), requested) {
+        (Some(principal), Some(requested)) if requested != principal.tenant_id => {
+            return Err(abi::Error::PermissionDenied(format!(
+                "{} can't access tenant {}",
+                principal.user_id, requested
+            )));
+        }
+        (Some(principal), _) => principal.tenant_id.clone(),
+        (None, requested) => requested.unwrap_or_default(),
+    };
+    validate_tenant_id(&tenant_id)?;
+    Ok(tenant_id)
+}
+
 #[async_trait]
-impl ReservationService for RsvpService {
+impl<R> ReservationService for RsvpService<R>
+where
+    R: Rsvp + Send + Sync + 'static,
+{
     /// make a reservation
     async fn reserve(
         &self,
         request: Request<ReserveRequest>,
     ) -> Result<Response<ReserveResponse>, Status> {
-        let request = request.into_inner();
-        if request.reservation.is_none() {
-            return Err(Status::invalid_argument("missing reservation"));
-        }
-        let reservation = self.manager.reserve(request.reservation.unwrap()).await?;
+        let manager = self.tenant_manager(&request)?;
+        let mut reservation = request
+            .get_ref()
+            .reservation
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("missing reservation"))?;
+        act_as(&request, &mut reservation.user_id)?;
+        let reservation = manager.reserve(reservation).await?;
         Ok(Response::new(ReserveResponse {
             reservation: Some(reservation),
         }))
     }
 
+    /// check a reservation without making it
+    async fn check(
+        &self,
+        request: Request<CheckRequest>,
+    ) -> Result<Response<CheckResponse>, Status> {
+        let manager = self.tenant_manager(&request)?;
+        let mut reservation = request
+            .get_ref()
+            .reservation
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("missing reservation"))?;
+        act_as(&request, &mut reservation.user_id)?;
+        let response = manager.check(reservation).await?;
+        Ok(Response::new(response))
+    }
+
+    /// suggest other windows and resources for a reservation
+    async fn suggest(
+        &self,
+        request: Request<SuggestRequest>,
+    ) -> Result<Response<SuggestResponse>, Status> {
+        let manager = self.tenant_manager(&request)?;
+        let mut reservation = request
+            .get_ref()
+            .reservation
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("missing reservation"))?;
+        act_as(&request, &mut reservation.user_id)?;
+        let request = request.into_inner();
+        let limit = match request.limit {
+            limit if limit > 0 => limit as usize,
+            _ => DEFAULT_SUGGEST_LIMIT,
+        };
+        let response = manager
+            .suggest(reservation, limit, request.resource_pool)
+            .await?;
+        Ok(Response::new(response))
+    }
+
     /// confirm a pending reservation, if reservation is not pending, do nothing
     async fn confirm(
         &self,
         request: Request<ConfirmRequest>,
     ) -> Result<Response<ConfirmResponse>, Status> {
-        let request = request.into_inner();
-        let reservation = self.manager.change_status(request.id).await?;
+        let manager = self.tenant_manager(&request)?;
+        let owner = owner(&request);
+        let reservation = manager.change_status(request.get_ref().id, owner).await?;
         Ok(Response::new(ConfirmResponse {
             reservation: Some(reservation),
         }))
@@ -54,8 +197,10 @@ impl ReservationService for RsvpService {
         &self,
         request: Request<UpdateRequest>,
     ) -> Result<Response<UpdateResponse>, Status> {
+        let manager = self.tenant_manager(&request)?;
+        let owner = owner(&request);
         let request = request.into_inner();
-        let reservation = self.manager.update_note(request.id, request.note).await?;
+        let reservation = manager.update_note(request.id, request.note, owner).await?;
         Ok(Response::new(UpdateResponse {
             reservation: Some(reservation),
         }))
@@ -66,17 +211,58 @@ impl ReservationService for RsvpService {
         &self,
         request: Request<CancelRequest>,
     ) -> Result<Response<CancelResponse>, Status> {
-        let request = request.into_inner();
-        let reservation = self.manager.delete(request.id).await?;
+        let manager = self.tenant_manager(&request)?;
+        let owner = owner(&request);
+        let reservation = manager.delete(request.get_ref().id, owner).await?;
         Ok(Response::new(CancelResponse {
             reservation: Some(reservation),
         }))
     }
 
+    /// transfer a reservation to another user, optionally waiting for the new owner to accept it
+    async fn transfer(
+        &self,
+        request: Request<TransferRequest>,
+    ) -> Result<Response<TransferResponse>, Status> {
+        let manager = self.tenant_manager(&request)?;
+        let owner = owner(&request);
+        let request = request.into_inner();
+        let reservation = manager
+            .transfer(
+                request.id,
+                request.user_id,
+                request.require_acceptance,
+                owner,
+            )
+            .await?;
+        Ok(Response::new(TransferResponse {
+            reservation: Some(reservation),
+        }))
+    }
+
+    /// accept a pending transfer, NOT_FOUND if there's no pending transfer for the user
+    async fn accept_transfer(
+        &self,
+        request: Request<AcceptTransferRequest>,
+    ) -> Result<Response<AcceptTransferResponse>, Status> {
+        let manager = self.tenant_manager(&request)?;
+        let mut user_id = request.get_ref().user_id.clone();
+        act_as(&request, &mut user_id)?;
+        let reservation = manager
+            .accept_transfer(request.get_ref().id, user_id)
+            .await?;
+        Ok(Response::new(AcceptTransferResponse {
+            reservation: Some(reservation),
+        }))
+    }
+
     /// get a reservation by id
     async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
-        let request = request.into_inner();
-        let reservation = self.manager.get(request.id).await?;
+        let manager = self.tenant_manager(&request)?;
+        let reservation = manager.get(request.get_ref().id).await?;
+        if let Some(principal) = request.extensions().get::<Principal>() {
+            principal.check_reader(&reservation)?;
+        }
         Ok(Response::new(GetResponse {
             reservation: Some(reservation),
         }))
@@ -89,40 +275,112 @@ impl ReservationService for RsvpService {
         &self,
         request: Request<QueryRequest>,
     ) -> Result<Response<Self::queryStream>, Status> {
-        let request = request.into_inner();
-        if request.query.is_none() {
-            return Err(Status::invalid_argument("missing query params"));
-        }
-        let rsvps = self.manager.query(request.query.unwrap()).await;
+        let manager = self.tenant_manager(&request)?;
+        let mut query = request
+            .get_ref()
+            .query
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("missing query params"))?;
+        read_as(&request, &mut query.user_id)?;
+        let rsvps = manager.query(query).await;
         let stream = TonicReceiverStream::new(rsvps);
         Ok(Response::new(Box::pin(stream)))
     }
 
+    /// export the reservations of the query as an iCalendar
+    async fn export_calendar(
+        &self,
+        request: Request<ExportCalendarRequest>,
+    ) -> Result<Response<ExportCalendarResponse>, Status> {
+        let manager = self.tenant_manager(&request)?;
+        let mut query = request
+            .get_ref()
+            .query
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("missing query params"))?;
+        read_as(&request, &mut query.user_id)?;
+        let calendar = export_calendar(&manager, "reservations", query).await?;
+        Ok(Response::new(ExportCalendarResponse { calendar }))
+    }
+
+    /// create reservations from the events of an iCalendar
+    async fn import_calendar(
+        &self,
+        request: Request<ImportCalendarRequest>,
+    ) -> Result<Response<ImportCalendarResponse>, Status> {
+        let manager = self.tenant_manager(&request)?;
+        let mut events = request
+            .get_ref()
+            .mapping
+            .clone()
+            .unwrap_or_default()
+            .parse(&request.get_ref().calendar)?;
+        // an event the caller can't import is reported like an invalid one
+        for event in &mut events {
+            if let Ok(rsvp) = &mut event.reservation {
+                if let Err(e) = act_as(&request, &mut rsvp.user_id) {
+                    event.reservation = Err(e);
+                }
+            }
+        }
+        let results = manager.import(events, request.get_ref().dry_run).await?;
+        Ok(Response::new(ImportCalendarResponse { results }))
+    }
+
     /// filter reservations, order by reservation id
     async fn filter(
         &self,
         request: Request<FilterRequest>,
     ) -> Result<Response<FilterResponse>, Status> {
-        let request = request.into_inner();
+        let tenant_id = tenant_id(&request)?;
+        let mut filter = request
+            .get_ref()
+            .filter
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("missing filter params"))?;
+        // the tenant and user are part of the page token fingerprint, so set them before checking
+        // the token
+        read_as(&request, &mut filter.user_id)?;
+        filter.tenant_id = tenant_id.clone();
+        filter.apply_page_token(&self.page_tokens, self.allow_raw_cursor)?;
 
-        if request.filter.is_none() {
-            return Err(Status::invalid_argument("missing filter params"));
-        }
-        let (pager, reservations) = self.manager.filter(request.filter.unwrap()).await?;
+        let manager = self.manager.with_tenant(tenant_id);
+        let (mut pager, reservations) = manager.filter(filter.clone()).await?;
+        pager.sign(&filter, &self.page_tokens);
         Ok(Response::new(FilterResponse {
             pager: Some(pager),
             reservations,
         }))
     }
 
+    /// full text search over reservation notes, ranked by relevance
+    async fn search(
+        &self,
+        request: Request<SearchRequest>,
+    ) -> Result<Response<SearchResponse>, Status> {
+        let manager = self.tenant_manager(&request)?;
+        let mut search = request
+            .get_ref()
+            .search
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("missing search params"))?;
+        read_as(&request, &mut search.user_id)?;
+        let response = manager.search(search).await?;
+        Ok(Response::new(response))
+    }
+
     ///Server streaming response type for the listen method.
-    type listenStream = ReservationStream;
+    type listenStream = ListenStream;
     /// another system could monitor newly added/confirmed/cancelled reservations
     async fn listen(
         &self,
-        _request: Request<ListenRequest>,
+        request: Request<ListenRequest>,
     ) -> Result<Response<Self::listenStream>, Status> {
-        todo!()
+        let manager = self.tenant_manager(&request)?;
+        let principal = request.extensions().get::<Principal>().cloned();
+        let changes = readable_changes(principal, manager.listen().await);
+        let stream = TonicReceiverStream::new(changes);
+        Ok(Response::new(Box::pin(stream)))
     }
 }
 
@@ -152,7 +410,13 @@ impl<T> Stream for TonicReceiverStream<T> {
 mod tests {
     use super::*;
     use crate::test_utils::TestConfig;
-    use abi::Reservation;
+    use abi::{
+        CalendarMapping, CalendarResourceSource, ImportOutcome, Reservation, ReservationFilter,
+        ReservationFilterBuilder, ReservationQuery, ReservationQueryBuilder, ReservationSearch,
+        ReservationSearchBuilder,
+    };
+    use futures::StreamExt;
+    use reservation::InMemoryReservationManager;
 
     #[tokio::test]
     async fn rpc_reserve_should_work() {
@@ -180,4 +444,443 @@ mod tests {
         assert_eq!(reservation1.note, reservation.note);
         assert_eq!(reservation1.status, reservation.status);
     }
+
+    #[tokio::test]
+    async fn rpc_should_be_scoped_to_tenant_in_metadata() {
+        let config = TestConfig::default();
+
+        let service = RsvpService::from_config(&config).await.unwrap();
+        let reservation = Reservation::new_pending(
+            "tyr",
+            "ixia-3230",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "test device reservation",
+        );
+        let mut request = tonic::Request::new(ReserveRequest::new(reservation.clone()));
+        request
+            .metadata_mut()
+            .insert(TENANT_ID_KEY, "acme".parse().unwrap());
+        let reservation = service
+            .reserve(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        assert_eq!(reservation.tenant_id, "acme");
+
+        // default tenant can't see it
+        let request = tonic::Request::new(GetRequest::new(reservation.id));
+        let status = service.get(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        let mut request = tonic::Request::new(GetRequest::new(reservation.id));
+        request
+            .metadata_mut()
+            .insert(TENANT_ID_KEY, "acme".parse().unwrap());
+        let ret = service.get(request).await.unwrap().into_inner();
+        assert_eq!(ret.reservation, Some(reservation));
+    }
+
+    /// the message as sent by the user of the tenant, authenticated
+    fn authenticated<T>(message: T, user_id: &str, tenant_id: &str) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        request.extensions_mut().insert(Principal {
+            user_id: user_id.into(),
+            tenant_id: tenant_id.into(),
+            service_account: user_id == "booking-bot",
+        });
+        request
+    }
+
+    #[tokio::test]
+    async fn rpc_should_only_let_owners_change_reservations() {
+        let config = TestConfig::default();
+        let service = RsvpService::new(InMemoryReservationManager::new(), &config.server);
+        let reservation = Reservation::new_pending(
+            "bob",
+            "ixia-3230",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "test device reservation",
+        );
+        let request = authenticated(ReserveRequest::new(reservation), "bob", "");
+        let reservation = service
+            .reserve(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        let id = reservation.id;
+
+        let denied = |status: Status| assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        fn alice<T>(message: T) -> tonic::Request<T> {
+            authenticated(message, "alice", "")
+        }
+        denied(service.get(alice(GetRequest::new(id))).await.unwrap_err());
+        denied(
+            service
+                .confirm(alice(ConfirmRequest::new(id)))
+                .await
+                .unwrap_err(),
+        );
+        denied(
+            service
+                .update(alice(UpdateRequest::new(id, "mine".into())))
+                .await
+                .unwrap_err(),
+        );
+        denied(
+            service
+                .cancel(alice(CancelRequest::new(id)))
+                .await
+                .unwrap_err(),
+        );
+        let request = authenticated(TransferRequest::new(id, "alice", false), "alice", "");
+        denied(service.transfer(request).await.unwrap_err());
+
+        // the owner and service accounts can
+        let request = authenticated(UpdateRequest::new(id, "hello".into()), "bob", "");
+        service.update(request).await.unwrap();
+        let request = authenticated(ConfirmRequest::new(id), "booking-bot", "");
+        service.confirm(request).await.unwrap();
+
+        // the user it's being transferred to can read it
+        let request = authenticated(TransferRequest::new(id, "alice", true), "bob", "");
+        service.transfer(request).await.unwrap();
+        let ret = service.get(alice(GetRequest::new(id))).await.unwrap();
+        assert_eq!(ret.into_inner().reservation.unwrap().note, "hello");
+        denied(
+            service
+                .cancel(alice(CancelRequest::new(id)))
+                .await
+                .unwrap_err(),
+        );
+    }
+
+    #[tokio::test]
+    async fn rpc_should_only_let_users_read_their_own_reservations() {
+        let config = TestConfig::default();
+        let service = RsvpService::new(InMemoryReservationManager::new(), &config.server);
+        let mut changes = service
+            .listen(authenticated(ListenRequest {}, "bob", ""))
+            .await
+            .unwrap()
+            .into_inner();
+        for (user_id, resource_id) in [("alice", "ixia-3230"), ("bob", "ixia-3231")] {
+            let reservation = Reservation::new_pending(
+                user_id,
+                resource_id,
+                "2022-12-26T15:00:00-0700".parse().unwrap(),
+                "2022-12-30T12:00:00-0700".parse().unwrap(),
+                "test device reservation",
+            );
+            let request = authenticated(ReserveRequest::new(reservation), user_id, "");
+            service.reserve(request).await.unwrap();
+        }
+
+        let denied = |status: Status| assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        fn bob<T>(message: T) -> tonic::Request<T> {
+            authenticated(message, "bob", "")
+        }
+        let query = ReservationQueryBuilder::default().build().unwrap();
+        let rsvps: Vec<_> = service
+            .query(bob(QueryRequest::new(query.clone())))
+            .await
+            .unwrap()
+            .into_inner()
+            .collect()
+            .await;
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].as_ref().unwrap().user_id, "bob");
+
+        let filter = ReservationFilterBuilder::default().build().unwrap();
+        let ret = service
+            .filter(bob(FilterRequest::new(filter.clone())))
+            .await;
+        let reservations = ret.unwrap().into_inner().reservations;
+        assert!(reservations.iter().all(|r| r.user_id == "bob"));
+        assert_eq!(reservations.len(), 1);
+
+        let search = ReservationSearchBuilder::default()
+            .text("device")
+            .build()
+            .unwrap();
+        let ret = service
+            .search(bob(SearchRequest::new(search.clone())))
+            .await;
+        let hits = ret.unwrap().into_inner().hits;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].reservation.as_ref().unwrap().user_id, "bob");
+
+        let request = bob(ExportCalendarRequest::new(query.clone()));
+        let calendar = service.export_calendar(request).await.unwrap();
+        let calendar = calendar.into_inner().calendar;
+        assert!(calendar.contains("ixia-3231") && !calendar.contains("ixia-3230"));
+
+        // asking for alice's explicitly is denied
+        let alices = ReservationQuery {
+            user_id: "alice".into(),
+            ..query
+        };
+        let ret = service.query(bob(QueryRequest::new(alices.clone()))).await;
+        denied(ret.err().unwrap());
+        let request = bob(ExportCalendarRequest::new(alices));
+        denied(service.export_calendar(request).await.unwrap_err());
+        let request = bob(FilterRequest::new(ReservationFilter {
+            user_id: "alice".into(),
+            ..filter
+        }));
+        denied(service.filter(request).await.unwrap_err());
+        let request = bob(SearchRequest::new(ReservationSearch {
+            user_id: "alice".into(),
+            ..search.clone()
+        }));
+        denied(service.search(request).await.unwrap_err());
+
+        // service accounts read everyone's
+        let request = authenticated(SearchRequest::new(search), "booking-bot", "");
+        let hits = service.search(request).await.unwrap().into_inner().hits;
+        assert_eq!(hits.len(), 2);
+
+        // only bob's own reservation is sent to his listener
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.reservation.unwrap().user_id, "bob");
+    }
+
+    #[tokio::test]
+    async fn rpc_should_be_scoped_to_tenant_of_principal() {
+        let config = TestConfig::default();
+        let service = RsvpService::new(InMemoryReservationManager::new(), &config.server);
+        let reservation = Reservation::new_pending(
+            "tyr",
+            "ixia-3230",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "test device reservation",
+        );
+        let request = authenticated(ReserveRequest::new(reservation), "tyr", "acme");
+        let reservation = service
+            .reserve(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        assert_eq!(reservation.tenant_id, "acme");
+
+        // the metadata may repeat the tenant of the token
+        let mut request = authenticated(GetRequest::new(reservation.id), "tyr", "acme");
+        request
+            .metadata_mut()
+            .insert(TENANT_ID_KEY, "acme".parse().unwrap());
+        let ret = service.get(request).await.unwrap().into_inner();
+        assert_eq!(ret.reservation, Some(reservation.clone()));
+
+        // but not name another one
+        let mut request = authenticated(GetRequest::new(reservation.id), "alice", "other");
+        request
+            .metadata_mut()
+            .insert(TENANT_ID_KEY, "acme".parse().unwrap());
+        let status = service.get(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        let request = authenticated(GetRequest::new(reservation.id), "alice", "other");
+        let status = service.get(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn rpc_filter_should_page_with_tokens() {
+        let config = TestConfig::default();
+
+        let service = RsvpService::from_config(&config).await.unwrap();
+        for i in 0..15 {
+            let reservation = Reservation::new_pending(
+                "tyr",
+                format!("router-{}", i),
+                "2022-12-26T15:00:00-0700".parse().unwrap(),
+                "2022-12-30T12:00:00-0700".parse().unwrap(),
+                "",
+            );
+            let request = tonic::Request::new(ReserveRequest::new(reservation));
+            service.reserve(request).await.unwrap();
+        }
+
+        let filter = ReservationFilterBuilder::default()
+            .user_id("tyr")
+            .sort_by(abi::ReservationSortBy::CreatedAt as i32)
+            .build()
+            .unwrap();
+        let request = tonic::Request::new(FilterRequest::new(filter.clone()));
+        let ret = service.filter(request).await.unwrap().into_inner();
+        assert_eq!(ret.reservations.len(), 10);
+        let pager = ret.pager.unwrap();
+        assert!(!pager.next_token.is_empty());
+
+        let next = ReservationFilter {
+            page_token: pager.next_token.clone(),
+            ..filter.clone()
+        };
+        let request = tonic::Request::new(FilterRequest::new(next));
+        let ret = service.filter(request).await.unwrap().into_inner();
+        assert_eq!(ret.reservations.len(), 5);
+        assert!(ret.pager.unwrap().next_token.is_empty());
+
+        // the token is bound to the filter
+        let other = ReservationFilter {
+            user_id: "alice".into(),
+            page_token: pager.next_token,
+            ..filter
+        };
+        let request = tonic::Request::new(FilterRequest::new(other));
+        let status = service.filter(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn rpc_should_work_with_in_memory_manager() {
+        let config = ServerConfig {
+            host: "127.0.0.1".into(),
+            port: 0,
+            page_token_secret: "secret".into(),
+            allow_raw_cursor: false,
+            http_port: None,
+            gateway_port: None,
+            cors_origins: vec![],
+            tls: None,
+            auth: None,
+        };
+        let service = RsvpService::new(InMemoryReservationManager::new(), &config);
+        for i in 0..12 {
+            let reservation = Reservation::new_pending(
+                "tyr",
+                format!("router-{}", i),
+                "2022-12-26T15:00:00-0700".parse().unwrap(),
+                "2022-12-30T12:00:00-0700".parse().unwrap(),
+                "",
+            );
+            let request = tonic::Request::new(ReserveRequest::new(reservation));
+            service.reserve(request).await.unwrap();
+        }
+
+        let reservation = Reservation::new_pending(
+            "alice",
+            "router-1",
+            "2022-12-27T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let request = tonic::Request::new(ReserveRequest::new(reservation));
+        let status = service.reserve(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        match abi::Error::from(status) {
+            abi::Error::ConflictReservation(conflict) => assert_eq!(conflict.conflicts.len(), 1),
+            e => panic!("expect conflict, got {:?}", e),
+        }
+
+        let reservation = Reservation::new_pending(
+            "alice",
+            "router-1",
+            "2022-12-27T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let request = tonic::Request::new(CheckRequest::new(reservation));
+        let ret = service.check(request).await.unwrap().into_inner();
+        assert!(!ret.ok);
+        assert_eq!(ret.problems[0].reason, "CONFLICT_RESERVATION");
+
+        let reservation = Reservation::new_pending(
+            "alice",
+            "router-1",
+            "2022-12-27T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let pool = vec!["router-11".into(), "router-12".into()];
+        let request = tonic::Request::new(SuggestRequest::new(reservation, 0, pool));
+        let ret = service.suggest(request).await.unwrap().into_inner();
+        // the free windows of 2022 are past
+        assert!(ret.slots.is_empty());
+        assert_eq!(ret.free_resources, vec!["router-12"]);
+
+        let query = ReservationQueryBuilder::default()
+            .resource_id("router-1")
+            .build()
+            .unwrap();
+        let request = tonic::Request::new(ExportCalendarRequest::new(query));
+        let ret = service.export_calendar(request).await.unwrap().into_inner();
+        assert_eq!(ret.calendar.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ret.calendar.contains("\r\nSUMMARY:router-1\r\n"));
+
+        // the exported reservation of tyr conflicts with itself
+        let calendar = ret.calendar.replace("UID:", "ORGANIZER:mailto:tyr\r\nUID:");
+        let request = tonic::Request::new(ImportCalendarRequest {
+            calendar,
+            mapping: Some(CalendarMapping {
+                resource_source: CalendarResourceSource::Summary as i32,
+                ..Default::default()
+            }),
+            dry_run: true,
+        });
+        let ret = service.import_calendar(request).await.unwrap().into_inner();
+        assert_eq!(ret.results[0].outcome(), ImportOutcome::Conflict);
+
+        let filter = ReservationFilterBuilder::default()
+            .user_id("tyr")
+            .build()
+            .unwrap();
+        let request = tonic::Request::new(FilterRequest::new(filter.clone()));
+        let ret = service.filter(request).await.unwrap().into_inner();
+        assert_eq!(ret.reservations.len(), 10);
+
+        let next = ReservationFilter {
+            page_token: ret.pager.unwrap().next_token,
+            ..filter
+        };
+        let request = tonic::Request::new(FilterRequest::new(next));
+        let ret = service.filter(request).await.unwrap().into_inner();
+        assert_eq!(ret.reservations.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rpc_listen_should_stream_changes() {
+        let config = TestConfig::default();
+        let service = RsvpService::new(InMemoryReservationManager::new(), &config.server);
+        let mut stream = service
+            .listen(tonic::Request::new(ListenRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let reservation = Reservation::new_pending(
+            "tyr",
+            "router-1",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let request = tonic::Request::new(ReserveRequest::new(reservation));
+        let reservation = service.reserve(request).await.unwrap().into_inner();
+
+        let change = stream.next().await.unwrap().unwrap();
+        assert_eq!(change.op, abi::ReservationUpdateType::Create as i32);
+        assert_eq!(change.reservation, reservation.reservation);
+    }
+
+    #[tokio::test]
+    async fn rpc_with_invalid_tenant_should_reject() {
+        let config = TestConfig::default();
+
+        let service = RsvpService::from_config(&config).await.unwrap();
+        let mut request = tonic::Request::new(GetRequest::new(1));
+        request
+            .metadata_mut()
+            .insert(TENANT_ID_KEY, "acme' --".parse().unwrap());
+        let status = service.get(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
 }
@@ -0,0 +1,246 @@
+//! TLS for the grpc port, mutual if a client CA is configured. The PEM files are checked every
+//! `RELOAD_INTERVAL` and a renewed certificate is used by the connections accepted after it, the
+//! ones already open keep theirs. If the new files can't be loaded, the current ones stay in use
+
+use std::{
+    fs,
+    io::{self, BufReader},
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use abi::TlsConfig;
+use anyhow::{anyhow, Context, Result};
+use futures::{stream, Stream};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{server::AllowAnyAuthenticatedClient, Certificate, PrivateKey, RootCertStore},
+    server::TlsStream,
+    TlsAcceptor,
+};
+use tracing::{info, warn};
+
+/// how often the PEM files are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+/// connections not done with the handshake by then are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// the rustls config of the PEM files, swapped when they change
+#[derive(Debug, Clone)]
+pub struct ReloadableTlsConfig {
+    config: TlsConfig,
+    current: Arc<RwLock<Loaded>>,
+}
+
+#[derive(Debug)]
+struct Loaded {
+    /// content of the cert, key and client CA files, to tell if they changed
+    pems: Vec<Vec<u8>>,
+    server_config: Arc<tokio_rustls::rustls::ServerConfig>,
+}
+
+impl ReloadableTlsConfig {
+    pub fn load(config: &TlsConfig) -> Result<Self> {
+        let pems = read_pems(config)?;
+        let server_config = server_config(&pems)?;
+        Ok(Self {
+            config: config.clone(),
+            current: Arc::new(RwLock::new(Loaded {
+                pems,
+                server_config: Arc::new(server_config),
+            })),
+        })
+    }
+
+    /// the config for the next connection
+    pub fn server_config(&self) -> Arc<tokio_rustls::rustls::ServerConfig> {
+        self.current.read().unwrap().server_config.clone()
+    }
+
+    /// load the files again if any of them changed, true if the config was swapped
+    pub fn reload(&self) -> Result<bool> {
+        let pems = read_pems(&self.config)?;
+        if self.current.read().unwrap().pems == pems {
+            return Ok(false);
+        }
+        let server_config = Arc::new(server_config(&pems)?);
+        *self.current.write().unwrap() = Loaded {
+            pems,
+            server_config,
+        };
+        Ok(true)
+    }
+
+    /// check the files every `RELOAD_INTERVAL` for as long as the process runs
+    pub fn watch(&self) {
+        let config = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(RELOAD_INTERVAL).await;
+                match config.reload() {
+                    Ok(true) => info!("TLS certificates reloaded"),
+                    Ok(false) => {}
+                    Err(e) => warn!(
+                        "Failed to reload TLS certificates, keeping the current ones: {:#}",
+                        e
+                    ),
+                }
+            }
+        });
+    }
+
+    /// TLS connections of the listener, handshakes run apart so a slow client doesn't hold the
+    /// others back. Connections failing the handshake are dropped
+    pub fn incoming(
+        &self,
+        listener: TcpListener,
+    ) -> impl Stream<Item = Result<TlsStream<TcpStream>, io::Error>> {
+        let config = self.clone();
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            while !tx.is_closed() {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(v) => v,
+                    Err(e) => {
+                        warn!("Failed to accept a connection: {}", e);
+                        continue;
+                    }
+                };
+                let _ = stream.set_nodelay(true);
+                let acceptor = TlsAcceptor::from(config.server_config());
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match handshake(acceptor, stream, peer).await {
+                        Ok(stream) => {
+                            let _ = tx.send(Ok(stream)).await;
+                        }
+                        Err(e) => warn!("{:#}", e),
+                    }
+                });
+            }
+        });
+        stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|item| (item, rx))
+        })
+    }
+}
+
+async fn handshake(
+    acceptor: TlsAcceptor,
+    stream: TcpStream,
+    peer: SocketAddr,
+) -> Result<TlsStream<TcpStream>> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream))
+        .await
+        .map_err(|_| anyhow!("TLS handshake with {} timed out", peer))?
+        .with_context(|| format!("TLS handshake with {} failed", peer))
+}
+
+fn read_pems(config: &TlsConfig) -> Result<Vec<Vec<u8>>> {
+    let mut paths = vec![&config.cert, &config.key];
+    paths.extend(&config.client_ca);
+    paths
+        .into_iter()
+        .map(|path| fs::read(path).with_context(|| format!("failed to read {}", path)))
+        .collect()
+}
+
+/// the rustls config of the cert, key and optional client CA PEMs, speaking h2 and http/1.1 for
+/// gRPC-Web
+fn server_config(pems: &[Vec<u8>]) -> Result<tokio_rustls::rustls::ServerConfig> {
+    let chain = certs(&pems[0]).context("invalid server certificate")?;
+    let key = private_key(&pems[1])?;
+    let builder = tokio_rustls::rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = match pems.get(2) {
+        Some(pem) => {
+            let mut roots = RootCertStore::empty();
+            for cert in certs(pem).context("invalid client CA")? {
+                roots.add(&cert).context("invalid client CA")?;
+            }
+            builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots))
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder
+        .with_single_cert(chain, key)
+        .context("invalid server certificate or key")?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn certs(pem: &[u8]) -> Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(pem))?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificate found"));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn private_key(pem: &[u8]) -> Result<PrivateKey> {
+    for item in rustls_pemfile::read_all(&mut BufReader::new(pem))? {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(PrivateKey(key)),
+            _ => {}
+        }
+    }
+    Err(anyhow!("no private key found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = "fixtures/tls";
+
+    fn fixture(name: &str) -> String {
+        format!("{}/{}", FIXTURES, name)
+    }
+
+    #[test]
+    fn tls_config_should_load_and_reload_on_change() {
+        let dir = std::env::temp_dir().join(format!("reservation-tls-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        for name in ["server.pem", "server.key", "ca.pem"] {
+            fs::copy(fixture(name), path(name)).unwrap();
+        }
+        let config = TlsConfig {
+            cert: path("server.pem"),
+            key: path("server.key"),
+            client_ca: Some(path("ca.pem")),
+        };
+        let tls = ReloadableTlsConfig::load(&config).unwrap();
+        let first = tls.server_config();
+        assert!(!tls.reload().unwrap());
+        assert!(Arc::ptr_eq(&first, &tls.server_config()));
+
+        // a renewed certificate is picked up
+        fs::copy(fixture("server2.pem"), path("server.pem")).unwrap();
+        fs::copy(fixture("server2.key"), path("server.key")).unwrap();
+        assert!(tls.reload().unwrap());
+        let second = tls.server_config();
+        assert!(!Arc::ptr_eq(&first, &second));
+
+        // a broken one is not, the current config stays
+        fs::write(path("server.key"), "not a key").unwrap();
+        assert!(tls.reload().is_err());
+        assert!(Arc::ptr_eq(&second, &tls.server_config()));
+
+        // nor a missing file
+        fs::write(
+            path("server.key"),
+            fs::read(fixture("server2.key")).unwrap(),
+        )
+        .unwrap();
+        fs::remove_file(path("ca.pem")).unwrap();
+        assert!(tls.reload().is_err());
+        assert!(Arc::ptr_eq(&second, &tls.server_config()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,16 +2,26 @@
 mod test_utils;
 
 use abi::{
-    reservation_service_client::ReservationServiceClient, Config, ConfirmRequest, FilterRequest,
-    FilterResponse, QueryRequest, Reservation, ReservationFilterBuilder, ReservationQueryBuilder,
-    ReservationStatus, ReserveRequest,
+    health::{
+        health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+    },
+    reflection::{
+        server_reflection_client::ServerReflectionClient,
+        server_reflection_request::MessageRequest, server_reflection_response::MessageResponse,
+        ServerReflectionRequest,
+    },
+    reservation_service_client::ReservationServiceClient,
+    Config, ConfirmRequest, FilterRequest, FilterResponse, QueryRequest, Reservation,
+    ReservationFilterBuilder, ReservationQueryBuilder, ReservationStatus, ReserveRequest,
+    TlsConfig,
 };
 use futures::StreamExt;
+use prost::Message;
 use reservation_service::start_server;
 use std::time::Duration;
 use test_utils::TestConfig;
 use tokio::time;
-use tonic::transport::Channel;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
 
 #[tokio::test]
 async fn grpc_server_should_work() {
@@ -34,7 +44,9 @@ async fn grpc_server_should_work() {
         .reservation
         .unwrap();
 
+    assert!(ret.created_at.is_some());
     rsvp.id = ret.id;
+    rsvp.created_at = ret.created_at.clone();
     assert_eq!(ret, rsvp);
 
     // then we try to make a conflicting reservation
@@ -149,19 +161,152 @@ async fn grpc_filter_should_work() {
     assert_eq!(reservations.len(), 5);
 }
 
+#[tokio::test]
+async fn grpc_health_and_reflection_should_work() {
+    let tconfig = TestConfig::with_server_port(50003);
+    get_test_client(&tconfig).await;
+    let url = tconfig.config.server.url();
+
+    let mut health = HealthClient::connect(url.clone()).await.unwrap();
+    let request = HealthCheckRequest {
+        service: "reservation.ReservationService".into(),
+    };
+    let ret = health.check(request).await.unwrap().into_inner();
+    assert_eq!(ret.status, ServingStatus::Serving as i32);
+
+    let mut reflection = ServerReflectionClient::connect(url).await.unwrap();
+    let request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(MessageRequest::ListServices(String::new())),
+    };
+    let mut responses = reflection
+        .server_reflection_info(futures::stream::iter([request]))
+        .await
+        .unwrap()
+        .into_inner();
+    let response = responses.message().await.unwrap().unwrap();
+    let services = match response.message_response {
+        Some(MessageResponse::ListServicesResponse(services)) => services.service,
+        response => panic!("unexpected response {:?}", response),
+    };
+    assert!(services
+        .iter()
+        .any(|s| s.name == "reservation.ReservationService"));
+}
+
+#[tokio::test]
+async fn grpc_web_should_work_over_http1() {
+    let mut tconfig = TestConfig::with_server_port(50004);
+    tconfig.config.server.cors_origins = vec!["*".into()];
+    get_test_client(&tconfig).await;
+
+    let query = ReservationQueryBuilder::default()
+        .user_id("alice")
+        .build()
+        .unwrap();
+    let msg = QueryRequest::new(query).encode_to_vec();
+    let mut body = vec![0];
+    body.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+    body.extend_from_slice(&msg);
+
+    let url = format!(
+        "{}/reservation.ReservationService/query",
+        tconfig.config.server.url()
+    );
+    let req = hyper::Request::post(url)
+        .header("content-type", "application/grpc-web+proto")
+        .header("origin", "https://app.example.com")
+        .body(hyper::Body::from(body))
+        .unwrap();
+    let res = hyper::Client::new().request(req).await.unwrap();
+    assert_eq!(res.version(), hyper::Version::HTTP_11);
+    assert_eq!(res.headers()["content-type"], "application/grpc-web+proto");
+    assert_eq!(res.headers()["access-control-allow-origin"], "*");
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    // no reservations, only the trailers frame
+    assert_eq!(body[0], 0x80);
+    assert!(String::from_utf8_lossy(&body[5..]).contains("grpc-status:0"));
+}
+
+#[tokio::test]
+async fn grpc_server_should_require_client_certificates_with_mtls() {
+    let mut tconfig = TestConfig::with_server_port(50005);
+    tconfig.config.server.host = "127.0.0.1".into();
+    tconfig.config.server.tls = Some(TlsConfig {
+        cert: "fixtures/tls/server.pem".into(),
+        key: "fixtures/tls/server.key".into(),
+        client_ca: Some("fixtures/tls/ca.pem".into()),
+    });
+    setup_server(&tconfig.config);
+    let url = tconfig.config.server.url();
+    assert!(url.starts_with("https://"));
+
+    let ca = Certificate::from_pem(std::fs::read("fixtures/tls/ca.pem").unwrap());
+    let identity = Identity::from_pem(
+        std::fs::read("fixtures/tls/client.pem").unwrap(),
+        std::fs::read("fixtures/tls/client.key").unwrap(),
+    );
+    let tls = ClientTlsConfig::new()
+        .domain_name("localhost")
+        .ca_certificate(ca.clone())
+        .identity(identity);
+    let endpoint = Channel::from_shared(url.clone())
+        .unwrap()
+        .tls_config(tls)
+        .unwrap();
+    let fut = async {
+        loop {
+            match endpoint.connect().await {
+                Ok(channel) => return channel,
+                Err(_) => time::sleep(Duration::from_millis(10)).await,
+            }
+        }
+    };
+    let channel = time::timeout(Duration::from_secs(5), fut).await.unwrap();
+    let mut client = ReservationServiceClient::new(channel);
+    let query = ReservationQueryBuilder::default()
+        .user_id("alice")
+        .build()
+        .unwrap();
+    let mut rsvps = client
+        .query(QueryRequest::new(query))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(rsvps.next().await.is_none());
+
+    // without a client certificate the handshake fails
+    let tls = ClientTlsConfig::new()
+        .domain_name("localhost")
+        .ca_certificate(ca);
+    let endpoint = Channel::from_shared(url).unwrap().tls_config(tls).unwrap();
+    let ret = match endpoint.connect().await {
+        Ok(channel) => {
+            let query = ReservationQueryBuilder::default().build().unwrap();
+            ReservationServiceClient::new(channel)
+                .query(QueryRequest::new(query))
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    assert!(ret.is_err());
+}
+
 async fn get_test_client(tconfig: &TestConfig) -> ReservationServiceClient<Channel> {
     let config = &tconfig.config;
     setup_server(config);
 
     let fut = async move {
         // if error on conn keep retry until timeout
-        while ReservationServiceClient::connect(config.server.url(false))
+        while ReservationServiceClient::connect(config.server.url())
             .await
             .is_err()
         {
             time::sleep(Duration::from_millis(10)).await;
         }
-        ReservationServiceClient::connect(config.server.url(false))
+        ReservationServiceClient::connect(config.server.url())
             .await
             .unwrap()
     };
@@ -184,7 +329,7 @@ async fn make_reservations(client: &mut ReservationServiceClient<Channel>, count
             format!("router-{}", i),
             "2022-12-26T15:00:00-0700".parse().unwrap(),
             "2022-12-30T12:00:00-0700".parse().unwrap(),
-            &format!("test device reservation {}", i),
+            format!("test device reservation {}", i),
         );
         let ret = client
             .reserve(ReserveRequest::new(rsvp.clone()))
@@ -195,6 +340,7 @@ async fn make_reservations(client: &mut ReservationServiceClient<Channel>, count
             .unwrap();
 
         rsvp.id = ret.id;
+        rsvp.created_at = ret.created_at.clone();
         assert_eq!(ret, rsvp);
     }
 }